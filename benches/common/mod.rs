@@ -0,0 +1,47 @@
+//! Allocation counting shared by the benches.
+#![allow(dead_code)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use forge_interpreter::Interpreter;
+
+/// Counts every allocation, so a bench can report allocations per iteration.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Allocations made and seconds taken by running `script` on a fresh interpreter.
+pub fn measure(script: &str) -> (usize, f64) {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let mut interp = Interpreter::new();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    runtime.block_on(interp.run(script)).expect("script failed");
+    let elapsed = start.elapsed().as_secs_f64();
+    (ALLOCATIONS.load(Ordering::Relaxed) - before, elapsed)
+}
+
+/// Allocations per iteration of the loop `script(n)` builds, and the seconds
+/// `script(2 * n)` takes. Subtracting a run of `n` iterations from a run of
+/// `2 * n` leaves out parsing and setup.
+pub fn per_iteration(n: u32, script: impl Fn(u32) -> String) -> (f64, f64) {
+    let (short, _) = measure(&script(n));
+    let (long, seconds) = measure(&script(2 * n));
+    ((long - short) as f64 / n as f64, seconds)
+}
@@ -0,0 +1,49 @@
+//! Variable lookups by interned `Symbol` against lookups by name, and a loop
+//! over five variables.
+//!
+//! `cargo bench --bench interning`
+
+mod common;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use forge_interpreter::symbol::Symbol;
+use forge_interpreter::{Env, Value};
+
+const LOOKUPS: u32 = 1_000_000;
+
+/// The loop below on the commit before interning, where `Env` was keyed by
+/// `String` (100000 iterations: about 88 ms there and 67 ms with it, release).
+const ALLOCATIONS_BEFORE: f64 = 8.5;
+
+/// Nanoseconds per call of `lookup`.
+fn time_lookups(lookup: impl Fn() -> Option<Value>) -> f64 {
+    let start = Instant::now();
+    for _ in 0..LOOKUPS {
+        black_box(lookup());
+    }
+    start.elapsed().as_secs_f64() * 1e9 / LOOKUPS as f64
+}
+
+fn main() {
+    let mut env = Env::new();
+    for name in ["alpha", "beta", "total", "count", "tmp", "other"] {
+        env.set_var(name, Value::Int(1));
+    }
+    // `get_var` hashes the name first, as the `String`-keyed map did for
+    // every access before interning; the parser hands `Env` symbols instead
+    let alpha = Symbol::intern("alpha");
+    let by_name = time_lookups(|| env.get_var(black_box("alpha")));
+    let by_symbol = time_lookups(|| env.get_sym(black_box(alpha)));
+    println!("variable lookup: {:.1} ns by name, {:.1} ns by symbol", by_name, by_symbol);
+
+    let (allocations, seconds) = common::per_iteration(50_000, |n| {
+        format!(
+            "alpha = 7\nbeta = 3\ntotal = 0\ncount = 0\nfor i = 1, {} do\n    tmp = i % alpha\n    other = tmp + beta\n    total = total + other\n    count = count + 1\n",
+            n
+        )
+    });
+    println!("five-variable loop, 100000 iterations: {:.1} ms", seconds * 1000.0);
+    println!("  allocations per iteration: {:.1} (before interning: {:.1})", allocations, ALLOCATIONS_BEFORE);
+}
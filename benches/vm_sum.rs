@@ -0,0 +1,35 @@
+//! Sum of 1..1_000_000 on the tree-walking evaluator and on the bytecode VM.
+//!
+//! `cargo bench --bench vm_sum`
+
+use std::time::{Duration, Instant};
+
+use forge_interpreter::{Interpreter, Value};
+
+const SCRIPT: &str = "total = 0\nfor i = 1, 1000000 do\n    total = total + i\n";
+const RUNS: u32 = 5;
+
+/// Best of `RUNS` timings of the script.
+fn best_time(vm: bool) -> Duration {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    (0..RUNS)
+        .map(|_| {
+            let mut interp = Interpreter::new();
+            interp.set_vm(vm);
+            let start = Instant::now();
+            runtime.block_on(interp.run(SCRIPT)).expect("script failed");
+            let elapsed = start.elapsed();
+            assert!(matches!(interp.get_var("total"), Some(Value::Int(500000500000))));
+            elapsed
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let ast = best_time(false);
+    let vm = best_time(true);
+    println!("sum 1..1_000_000, best of {}", RUNS);
+    println!("  evaluator: {:>8.1} ms", ast.as_secs_f64() * 1000.0);
+    println!("  vm:        {:>8.1} ms ({:.2}x)", vm.as_secs_f64() * 1000.0, ast.as_secs_f64() / vm.as_secs_f64());
+}
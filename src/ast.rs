@@ -1,12 +1,15 @@
 use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::symbol::Symbol;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expr {
     Number(f64),
+    Int(i64),          // целочисленный литерал без дробной части
     String(String),
     Boolean(bool),
     Null,
-    Variable(String),
+    Variable(Symbol),
     BinaryOp {
         left: Box<Expr>,
         op: BinaryOpKind,
@@ -16,8 +19,12 @@ pub enum Expr {
         op: UnaryOpKind,
         expr: Box<Expr>,
     },
+    Coalesce {             // `left ?? right`: right вычисляется, только если left == null
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
     Call {
-        name: String,
+        name: Symbol,
         args: Vec<Expr>,
     },
     Index {
@@ -27,6 +34,8 @@ pub enum Expr {
     GetAttr {
         object: Box<Expr>,
         attr: String,
+        #[serde(default)]
+        optional: bool,    // `a?.b`: null, если `a` равно null
     },
     SetAttr {
         object: Box<Expr>,
@@ -43,24 +52,33 @@ pub enum Expr {
     },
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum BinaryOpKind {
     Add, Sub, Mul, Div, Mod,
     Eq, Ne, Lt, Le, Gt, Ge,
     And, Or,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum UnaryOpKind {
     Not,
     Neg,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Stmt {
     Expr(Expr),
     Assign {
-        name: String,
+        name: Symbol,
+        value: Expr,
+    },
+    MultiAssign {
+        targets: Vec<Symbol>,   // `a, b = arr`, `[a, b] = arr` или `a, b = b, a`
+        values: Vec<Expr>,      // одно выражение — распаковка массива
+    },
+    IndexAssign {               // `arr[i] = value`, `map["key"] = value`
+        target: Expr,
+        index: Expr,
         value: Expr,
     },
     If {
@@ -72,24 +90,36 @@ pub enum Stmt {
     While {
         condition: Expr,
         body: Vec<Stmt>,
+        #[serde(default)]
+        label: Option<Symbol>,  // `outer: while ...:` для `break outer` / `continue outer`
     },
     For {
-        var: String,
+        var: Symbol,
         start: Expr,
         end: Expr,
+        step: Option<Expr>,     // по умолчанию 1; отрицательный шаг — убывающий цикл
         body: Vec<Stmt>,
+        #[serde(default)]
+        label: Option<Symbol>,
     },
     ForIn {
-        var: String,
+        var: Symbol,
         array: Expr,
         body: Vec<Stmt>,
+        #[serde(default)]
+        label: Option<Symbol>,
     },
-    Return(Expr),
+    Return(Vec<Expr>),          // `return a, b` возвращает массив `[a, b]`
+    Yield(Expr),                // делает функцию генератором, см. [`contains_yield`]
+    Break(Option<Symbol>),      // без метки --- ближайший цикл
+    Continue(Option<Symbol>),
     FunctionDef {
         name: String,
-        params: Vec<String>,
+        params: Vec<Symbol>,
         body: Vec<Stmt>,
         is_async: bool,
+        #[serde(default)]
+        is_static: bool, // `static function` в теле класса: вызывается без экземпляра
     },
     Print(Vec<Expr>),
     LoadFrom {
@@ -111,14 +141,66 @@ pub enum Stmt {
         name: String,      // оригинальное имя функции
         alias: String,     // имя в языке
     },
+    Line(usize),           // маркер номера строки исходника для следующего оператора
+    Compiled {             // цикл, скомпилированный в байткод (--vm)
+        chunk: std::rc::Rc<crate::vm::Chunk>,
+        original: Box<Stmt>,   // для --trace и отладки
+    },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LoadTarget {
     All,
     File(String),
 }
 
+impl Stmt {
+    /// Short description used by `--trace`, e.g. `Assign x` or `Call foo`.
+    pub fn summary(&self) -> String {
+        match self {
+            Stmt::Expr(Expr::Call { name, .. }) => format!("Call {}", name),
+            Stmt::Expr(Expr::CallMethod { method, .. }) => format!("CallMethod {}", method),
+            Stmt::Expr(Expr::SetAttr { attr, .. }) => format!("SetAttr {}", attr),
+            Stmt::Expr(_) => "Expr".to_string(),
+            Stmt::Assign { name, .. } => format!("Assign {}", name),
+            Stmt::MultiAssign { targets, .. } => format!("MultiAssign {}", targets.iter().map(|t| t.as_str()).collect::<Vec<_>>().join(", ")),
+            Stmt::IndexAssign { .. } => "IndexAssign".to_string(),
+            Stmt::If { .. } => "If".to_string(),
+            Stmt::While { .. } => "While".to_string(),
+            Stmt::For { var, .. } => format!("For {}", var),
+            Stmt::ForIn { var, .. } => format!("ForIn {}", var),
+            Stmt::Return(_) => "Return".to_string(),
+            Stmt::Yield(_) => "Yield".to_string(),
+            Stmt::Break(_) => "Break".to_string(),
+            Stmt::Continue(_) => "Continue".to_string(),
+            Stmt::FunctionDef { name, .. } => format!("FunctionDef {}", name),
+            Stmt::Print(_) => "Print".to_string(),
+            Stmt::LoadFrom { folder, .. } => format!("LoadFrom {}", folder),
+            Stmt::TryCatch { .. } => "TryCatch".to_string(),
+            Stmt::ClassDef { name, .. } => format!("ClassDef {}", name),
+            Stmt::ImportDll { alias, .. } => format!("ImportDll {}", alias),
+            Stmt::Line(line) => format!("Line {}", line),
+            Stmt::Compiled { original, .. } => format!("Compiled {}", original.summary()),
+        }
+    }
+}
+
+/// Whether a function body contains `yield` (outside nested functions and
+/// classes), i.e. whether calling it produces an array of the yielded values.
+pub fn contains_yield(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Yield(_) => true,
+        Stmt::If { then_branch, elif_branches, else_branch, .. } => {
+            contains_yield(then_branch)
+                || elif_branches.iter().any(|(_, body)| contains_yield(body))
+                || else_branch.as_deref().is_some_and(contains_yield)
+        }
+        Stmt::While { body, .. } | Stmt::For { body, .. } | Stmt::ForIn { body, .. } => contains_yield(body),
+        Stmt::TryCatch { try_body, catch_body } => contains_yield(try_body) || contains_yield(catch_body),
+        _ => false,
+    })
+}
+
 impl fmt::Display for BinaryOpKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -56,26 +56,28 @@ fn run() -> Result<(), String> {
     let new_main = format!(
         r#"
 // --- Автоматически сгенерировано builder'ом ---
-mod ast;
-mod env;
-mod eval;
-mod parser;
-mod builtins;
-mod value;
+use forge_interpreter::{{Interpreter, BUILT_STACK_SIZE}};
 
 const EMBEDDED_SCRIPT: &str = "{}";
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<(), String> {{
-    run_script(EMBEDDED_SCRIPT).await
+fn main() -> Result<(), String> {{
+    // Большой стек: глубокая рекурсия Forge должна упираться в лимит глубины, а не в переполнение
+    let handle = std::thread::Builder::new()
+        .stack_size(BUILT_STACK_SIZE)
+        .spawn(|| {{
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| format!("Failed to start runtime: {{}}", e))?
+                .block_on(run_script(EMBEDDED_SCRIPT))
+        }})
+        .map_err(|e| format!("Failed to start interpreter thread: {{}}", e))?;
+    handle.join().map_err(|_| "Interpreter thread panicked".to_string())?
 }}
 
 async fn run_script(source: &str) -> Result<(), String> {{
-    let lines: Vec<String> = source.lines().map(|s| s.trim_end().to_string()).collect();
-    let stmts = parser::parse(&lines)?;
-    let mut env = env::Env::new();
-    builtins::install(&mut env);
-    eval::eval_block(&stmts, &mut env).await?;
+    // Релизная сборка: проверки debug_assert пропускаются
+    Interpreter::for_built_executable().run(source).await?;
     Ok(())
 }}
 "#,
@@ -124,6 +126,13 @@ fn copy_interpreter_sources(dest: &Path) -> Result<(), String> {
         "src/parser.rs",
         "src/builtins.rs",
         "src/value.rs",
+        "src/optimize.rs",
+        "src/cache.rs",
+        "src/ctype.rs",
+        "src/vm.rs",
+        "src/symbol.rs",
+        "src/ffi.rs",
+        "src/lib.rs",
         "src/main.rs",
     ];
 
@@ -136,12 +145,36 @@ fn copy_interpreter_sources(dest: &Path) -> Result<(), String> {
         if let Some(parent) = dest_path.parent() {
             fs::create_dir_all(parent).unwrap();
         }
+        if *file == "Cargo.toml" {
+            // benches/ не копируется, поэтому и цели [[bench]] из манифеста убираем
+            let manifest = fs::read_to_string(src_path)
+                .map_err(|e| format!("Не удалось прочитать {}: {}", file, e))?;
+            fs::write(&dest_path, manifest_without_benches(&manifest))
+                .map_err(|e| format!("Не удалось записать {}: {}", file, e))?;
+            continue;
+        }
         fs::copy(src_path, &dest_path)
             .map_err(|e| format!("Не удалось скопировать {}: {}", file, e))?;
     }
     Ok(())
 }
 
+fn manifest_without_benches(manifest: &str) -> String {
+    let mut out = String::with_capacity(manifest.len());
+    let mut in_bench = false;
+    for line in manifest.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_bench = trimmed == "[[bench]]";
+        }
+        if !in_bench {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
 fn escape_rust_string(s: &str) -> String {
     let mut escaped = String::with_capacity(s.len() + 10);
     for c in s.chars() {
@@ -4,16 +4,18 @@ use std::fs;
 use std::cell::RefCell;
 use std::io::Write;
 use std::path::Path;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::sync::Mutex;
 use tokio::time;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use crossterm::event::{self, Event};
+use crate::ctype::CType;
 use crate::env::{Env, BuiltinFn};
-use crate::value::Value;
+use crate::value::{array_mut, freeze_array, is_frozen, ArrayCell, MapValue, SetKey, Value};
 use crate::eval::BoxFuture;
 use libloading::Library;
 use lazy_static::lazy_static;
+use regex::Regex;
 
 macro_rules! builtin {
     ($name:ident, $f:expr) => {
@@ -32,9 +34,10 @@ builtin!(sleep_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Va
         if args.len() != 1 {
             return Err("sleep expects 1 argument".to_string());
         }
-        let ms = match &args[0] {
-            Value::Number(n) => *n as u64,
-            _ => return Err("sleep argument must be number".to_string()),
+        let ms = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as u64,
+            Some(n) => return Err(format!("sleep: duration must not be negative, got {}", n)),
+            None => return Err("sleep argument must be number".to_string()),
         };
         time::sleep(Duration::from_millis(ms)).await;
         Ok(Value::Null)
@@ -43,7 +46,7 @@ builtin!(sleep_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Va
 
 builtin!(array_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        Ok(Value::Array(Rc::new(RefCell::new(args))))
+        Ok(Value::Array(Rc::new(ArrayCell::new(args))))
     })
 });
 
@@ -55,9 +58,9 @@ builtin!(push_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Val
         let (arr_val, val) = (args[0].clone(), args[1].clone());
         match arr_val {
             Value::Array(arr_rc) => {
-                let mut arr = arr_rc.borrow_mut();
+                let mut arr = array_mut("push", &arr_rc)?;
                 arr.push(val);
-                Ok(Value::Number(arr.len() as f64))
+                Ok(Value::Int(arr.len() as i64))
             }
             _ => Err("push: first argument must be array".to_string()),
         }
@@ -71,7 +74,7 @@ builtin!(pop_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Valu
         }
         match args[0].clone() {
             Value::Array(arr_rc) => {
-                let mut arr = arr_rc.borrow_mut();
+                let mut arr = array_mut("pop", &arr_rc)?;
                 arr.pop().ok_or_else(|| "pop from empty array".to_string())
             }
             _ => Err("pop: argument must be array".to_string()),
@@ -96,9 +99,7 @@ builtin!(exit_fn, |_args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Va
             Ok(_) => {
                 std::process::exit(0);
             }
-            Err(e) => {
-                return Err(format!("exit: failed to read event: {}", e));
-            }
+            Err(e) => Err(format!("exit: failed to read event: {}", e)),
         }
     })
 });
@@ -111,10 +112,25 @@ builtin!(length_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<V
         match &args[0] {
             Value::Array(arr_rc) => {
                 let arr = arr_rc.borrow();
-                Ok(Value::Number(arr.len() as f64))
+                Ok(Value::Int(arr.len() as i64))
             }
-            Value::String(s) => Ok(Value::Number(s.len() as f64)),
-            _ => Err("length: argument must be array or string".to_string()),
+            Value::String(s) => Ok(Value::Int(s.len() as i64)),
+            Value::Map(map) => Ok(Value::Int(map.borrow().len() as i64)),
+            Value::Set(set) => Ok(Value::Int(set.borrow().len() as i64)),
+            _ => Err("length: argument must be array, string, map or set".to_string()),
+        }
+    })
+});
+
+builtin!(is_empty_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        match args.as_slice() {
+            [Value::Array(arr)] => Ok(Value::Boolean(arr.borrow().is_empty())),
+            [Value::String(s)] => Ok(Value::Boolean(s.is_empty())),
+            [Value::Map(map)] => Ok(Value::Boolean(map.borrow().is_empty())),
+            [Value::Set(set)] => Ok(Value::Boolean(set.borrow().is_empty())),
+            [other] => Err(format!("is_empty: argument must be array, string, map or set, got {}", other.type_name())),
+            _ => Err("is_empty expects 1 argument".to_string()),
         }
     })
 });
@@ -124,11 +140,11 @@ builtin!(slice_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Va
         if args.len() != 3 {
             return Err("slice expects 3 arguments".to_string());
         }
-        match (&args[0], &args[1], &args[2]) {
-            (Value::Array(arr_rc), Value::Number(start), Value::Number(end)) => {
+        match (&args[0], args[1].as_i64(), args[2].as_i64()) {
+            (Value::Array(arr_rc), Some(start), Some(end)) => {
                 let arr = arr_rc.borrow();
-                let s = *start as usize;
-                let e = *end as usize;
+                let s = start as usize;
+                let e = end as usize;
                 if s > e {
                     return Err("slice: start index must be <= end index".to_string());
                 }
@@ -136,37 +152,157 @@ builtin!(slice_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Va
                     return Err("slice: end index out of bounds".to_string());
                 }
                 let sliced = arr[s..e].to_vec();
-                Ok(Value::Array(Rc::new(RefCell::new(sliced))))
+                Ok(Value::Array(Rc::new(ArrayCell::new(sliced))))
             }
             _ => Err("slice: first argument must be array, start and end must be numbers".to_string()),
         }
     })
 });
 
-builtin!(input_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
-    Box::pin(async move {
-        let prompt = if args.is_empty() {
-            String::new()
-        } else {
-            match &args[0] {
-                Value::String(s) => s.clone(),
-                _ => return Err("input prompt must be string".to_string()),
+type StdinLines = tokio::sync::mpsc::UnboundedReceiver<Result<String, String>>;
+
+lazy_static! {
+    // Строки stdin читает отдельный поток и передаёт через канал. Блокирующее
+    // чтение в пуле tokio не давало runtime завершиться, пока `input_timeout`
+    // ждал ввода; обычный поток процесс не держит, а строка, пришедшая после
+    // таймаута, достанется следующему `input`.
+    static ref STDIN: tokio::sync::Mutex<StdinLines> = tokio::sync::Mutex::new(spawn_stdin_reader());
+}
+
+fn spawn_stdin_reader() -> StdinLines {
+    use std::io::BufRead;
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        let stdin = std::io::stdin();
+        loop {
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) => break, // EOF: канал закрывается, дальше читаются пустые строки
+                Ok(_) => {
+                    if tx.send(Ok(line)).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e.to_string()));
+                    break;
+                }
             }
+        }
+    });
+    rx
+}
+
+fn prompt_arg(name: &str, args: &[Value]) -> Result<String, String> {
+    match args.first() {
+        None => Ok(String::new()),
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(_) => Err(format!("{} prompt must be string", name)),
+    }
+}
+
+/// Print `prompt` to the script output (stdout or the sink, see
+/// [`Env::write_out`]) and read one line from stdin without the trailing newline.
+async fn read_input_line(name: &str, prompt: &str, env: &Env) -> Result<String, String> {
+    env.write_out(prompt).map_err(|e| format!("{}: {}", name, e))?;
+    let line = match STDIN.lock().await.recv().await {
+        Some(Ok(line)) => line,
+        Some(Err(e)) => return Err(format!("{}: failed to read line: {}", name, e)),
+        None => String::new(),
+    };
+    Ok(line.trim_end().to_string())
+}
+
+// input(prompt) or input(prompt, default): the default is returned for an empty line.
+builtin!(input_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() > 2 {
+            return Err("input expects at most 2 arguments: prompt, default".to_string());
+        }
+        let prompt = prompt_arg("input", &args)?;
+        let line = read_input_line("input", &prompt, env).await?;
+        match args.get(1) {
+            Some(default) if line.is_empty() => Ok(default.clone()),
+            _ => Ok(Value::String(line)),
+        }
+    })
+});
+
+// input_timeout(prompt, ms): null if no line arrives in time.
+builtin!(input_timeout_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("input_timeout expects 2 arguments: prompt, ms".to_string());
+        }
+        let prompt = prompt_arg("input_timeout", &args)?;
+        let ms = match args[1].as_i64() {
+            Some(ms) if ms >= 0 => ms as u64,
+            _ => return Err("input_timeout: timeout must be a non-negative number of milliseconds".to_string()),
+        };
+        match time::timeout(Duration::from_millis(ms), read_input_line("input_timeout", &prompt, env)).await {
+            Ok(line) => Ok(Value::String(line?)),
+            Err(_) => Ok(Value::Null),
+        }
+    })
+});
+
+/// Disables raw mode when dropped, so the terminal is restored on every exit path.
+struct RawModeGuard;
+
+impl RawModeGuard {
+    fn enable() -> Result<Self, String> {
+        enable_raw_mode().map_err(|e| format!("failed to enable raw mode: {}", e))?;
+        Ok(RawModeGuard)
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+    }
+}
+
+/// Read a line in raw mode without echo. Ctrl-C aborts with an error.
+fn read_password_line() -> Result<String, String> {
+    use crossterm::event::{KeyCode, KeyEventKind, KeyModifiers};
+    let _guard = RawModeGuard::enable()?;
+    let mut line = String::new();
+    loop {
+        let key = match event::read().map_err(|e| format!("failed to read key: {}", e))? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
         };
-        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-        let mut stdout = tokio::io::stdout();
-        if let Err(e) = stdout.write_all(prompt.as_bytes()).await {
-            return Err(format!("input: failed to write prompt: {}", e));
+        match key.code {
+            KeyCode::Enter => return Ok(line),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Err("interrupted".to_string()),
+            KeyCode::Backspace => {
+                line.pop();
+            }
+            KeyCode::Char(c) => line.push(c),
+            _ => {}
         }
-        if let Err(e) = stdout.flush().await {
-            return Err(format!("input: failed to flush stdout: {}", e));
+    }
+}
+
+// input_password(prompt): like input, but typed characters are not echoed.
+// Without a terminal (piped stdin) it falls back to a plain line read.
+builtin!(input_password_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        use std::io::IsTerminal;
+        if args.len() > 1 {
+            return Err("input_password expects at most 1 argument (prompt)".to_string());
         }
-        let mut reader = BufReader::new(tokio::io::stdin());
-        let mut line = String::new();
-        if let Err(e) = reader.read_line(&mut line).await {
-            return Err(format!("input: failed to read line: {}", e));
+        let prompt = prompt_arg("input_password", &args)?;
+        if !std::io::stdin().is_terminal() {
+            return read_input_line("input_password", &prompt, env).await.map(Value::String);
         }
-        Ok(Value::String(line.trim_end().to_string()))
+        env.write_out(&prompt).map_err(|e| format!("input_password: {}", e))?;
+        let line = tokio::task::spawn_blocking(read_password_line)
+            .await
+            .map_err(|e| format!("input_password: {}", e))?
+            .map_err(|e| format!("input_password: {}", e));
+        env.print_line("")?;
+        line.map(Value::String)
     })
 });
 
@@ -181,7 +317,7 @@ builtin!(write_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Va
         };
         let content = match &args[1] {
             Value::String(s) => s.as_str(),
-            Value::Number(n) => return Ok(Value::Boolean(fs::write(filename, n.to_string()).is_ok())),
+            Value::Number(_) | Value::Int(_) => return Ok(Value::Boolean(fs::write(filename, args[1].to_string()).is_ok())),
             Value::Boolean(b) => return Ok(Value::Boolean(fs::write(filename, b.to_string()).is_ok())),
             _ => return Err("write: second argument must be string, number or boolean".to_string()),
         };
@@ -200,7 +336,7 @@ builtin!(append_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<V
         };
         let content = match &args[1] {
             Value::String(s) => s.as_str(),
-            Value::Number(n) => {
+            n @ (Value::Number(_) | Value::Int(_)) => {
                 return Ok(Value::Boolean(
                     fs::OpenOptions::new().append(true).create(true).open(filename)
                         .and_then(|mut f| write!(f, "{}", n)).is_ok()
@@ -221,6 +357,36 @@ builtin!(append_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<V
     })
 });
 
+// fprintf/fprintln: `format` + append in one call; I/O errors are reported, not swallowed.
+fn append_formatted(name: &str, args: &[Value], newline: bool) -> Result<Value, String> {
+    if args.len() < 2 {
+        return Err(format!("{} expects at least 2 arguments: path, template, ...", name));
+    }
+    let (path, template) = match (&args[0], &args[1]) {
+        (Value::String(p), Value::String(t)) => (p, t),
+        _ => return Err(format!("{}: path and template must be strings", name)),
+    };
+    let mut text = format_template(template, &args[2..]).map_err(|e| format!("{}: {}", name, e))?;
+    if newline {
+        text.push('\n');
+    }
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(text.as_bytes()))
+        .map_err(|e| format!("{}: cannot write '{}': {}", name, path, e))?;
+    Ok(Value::Null)
+}
+
+builtin!(fprintf_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { append_formatted("fprintf", &args, false) })
+});
+
+builtin!(fprintln_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { append_formatted("fprintln", &args, true) })
+});
+
 builtin!(read_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
         if args.len() != 1 {
@@ -261,21 +427,159 @@ builtin!(lower_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Va
     })
 });
 
-builtin!(split_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+// Case conversions work on `char`s, so multibyte (e.g. Cyrillic) input is safe.
+// Mapping follows Unicode default rules and is not locale-aware (no Turkish dotless i etc.).
+
+builtin!(title_case_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 2 {
-            return Err("split expects 2 arguments".to_string());
+        if args.len() != 1 {
+            return Err("title_case expects 1 argument".to_string());
         }
-        match (&args[0], &args[1]) {
-            (Value::String(s), Value::String(sep)) => {
-                let parts: Vec<Value> = s.split(sep).map(|x| Value::String(x.to_string())).collect();
-                Ok(Value::Array(Rc::new(RefCell::new(parts))))
+        match &args[0] {
+            Value::String(s) => {
+                let mut result = String::with_capacity(s.len());
+                let mut word_start = true;
+                for c in s.chars() {
+                    if c.is_whitespace() {
+                        word_start = true;
+                        result.push(c);
+                    } else if word_start {
+                        word_start = false;
+                        result.extend(c.to_uppercase());
+                    } else {
+                        result.push(c);
+                    }
+                }
+                Ok(Value::String(result))
             }
-            _ => Err("split: arguments must be strings".to_string()),
+            _ => Err("title_case: argument must be string".to_string()),
         }
     })
 });
 
+builtin!(capitalize_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("capitalize expects 1 argument".to_string());
+        }
+        match &args[0] {
+            Value::String(s) => {
+                let mut chars = s.chars();
+                let result = match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                };
+                Ok(Value::String(result))
+            }
+            _ => Err("capitalize: argument must be string".to_string()),
+        }
+    })
+});
+
+builtin!(swap_case_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("swap_case expects 1 argument".to_string());
+        }
+        match &args[0] {
+            Value::String(s) => {
+                let mut result = String::with_capacity(s.len());
+                for c in s.chars() {
+                    if c.is_uppercase() {
+                        result.extend(c.to_lowercase());
+                    } else if c.is_lowercase() {
+                        result.extend(c.to_uppercase());
+                    } else {
+                        result.push(c);
+                    }
+                }
+                Ok(Value::String(result))
+            }
+            _ => Err("swap_case: argument must be string".to_string()),
+        }
+    })
+});
+
+/// Character classification (`is_digit` etc.): the argument must be a string
+/// of exactly one character, so that `is_digit("12")` is an error rather than
+/// silently looking at the first character only.
+fn char_class(name: &str, args: &[Value], test: fn(char) -> bool) -> Result<Value, String> {
+    let s = match args {
+        [Value::String(s)] => s,
+        [other] => return Err(format!("{}: argument must be string, got {}", name, other.type_name())),
+        _ => return Err(format!("{} expects 1 argument", name)),
+    };
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Value::Boolean(test(c))),
+        _ => Err(format!("{}: expected a single character, got {:?}", name, s)),
+    }
+}
+
+// Только ASCII 0-9: для разбора чисел другие цифры Юникода не нужны
+builtin!(is_digit_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { char_class("is_digit", &args, |c| c.is_ascii_digit()) })
+});
+
+// Буквы любого алфавита, в том числе кириллица
+builtin!(is_alpha_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { char_class("is_alpha", &args, char::is_alphabetic) })
+});
+
+builtin!(is_alnum_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { char_class("is_alnum", &args, |c| c.is_alphabetic() || c.is_ascii_digit()) })
+});
+
+builtin!(is_whitespace_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { char_class("is_whitespace", &args, char::is_whitespace) })
+});
+
+builtin!(is_upper_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { char_class("is_upper", &args, char::is_uppercase) })
+});
+
+builtin!(is_lower_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { char_class("is_lower", &args, char::is_lowercase) })
+});
+
+/// split(s, sep[, limit]): at most `limit` pieces, the last one keeps the rest
+/// of the string (`str::splitn`). A limit of 0 means no limit, 1 gives `[s]`.
+fn split_string(name: &str, s: &Value, sep: &Value, limit: Option<&Value>) -> Result<Value, String> {
+    let (s, sep) = match (s, sep) {
+        (Value::String(s), Value::String(sep)) => (s, sep),
+        _ => return Err(format!("{}: string and separator must be strings", name)),
+    };
+    let limit = match limit.map(|l| l.as_i64()) {
+        None | Some(Some(0)) => None,
+        Some(Some(n)) if n > 0 => Some(n as usize),
+        _ => return Err(format!("{}: limit must be a non-negative number", name)),
+    };
+    let parts: Vec<Value> = match limit {
+        Some(n) => s.splitn(n, sep.as_str()).map(|x| Value::String(x.to_string())).collect(),
+        None => s.split(sep.as_str()).map(|x| Value::String(x.to_string())).collect(),
+    };
+    Ok(Value::Array(Rc::new(ArrayCell::new(parts))))
+}
+
+builtin!(split_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 && args.len() != 3 {
+            return Err("split expects 2 or 3 arguments: string, separator, limit".to_string());
+        }
+        split_string("split", &args[0], &args[1], args.get(2))
+    })
+});
+
+// splitn(s, sep, n): split with a mandatory limit.
+builtin!(splitn_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 3 {
+            return Err("splitn expects 3 arguments: string, separator, limit".to_string());
+        }
+        split_string("splitn", &args[0], &args[1], Some(&args[2]))
+    })
+});
+
 builtin!(join_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
         if args.len() != 2 {
@@ -325,15 +629,125 @@ builtin!(contains_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result
     })
 });
 
+builtin!(count_occurrences_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("count_occurrences expects 2 arguments".to_string());
+        }
+        match (&args[0], &args[1]) {
+            (Value::String(s), Value::String(needle)) => {
+                if needle.is_empty() {
+                    return Err("count_occurrences: needle must not be empty".to_string());
+                }
+                // `matches` yields non-overlapping occurrences: "aa" in "aaaa" is 2
+                Ok(Value::Int(s.matches(needle.as_str()).count() as i64))
+            }
+            _ => Err("count_occurrences: arguments must be strings".to_string()),
+        }
+    })
+});
+
+/// Substitute `{}` placeholders with `args` in order; `{{` and `}}` are literal braces.
+fn format_template(template: &str, args: &[Value]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut next = args.iter();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                out.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                let arg = next.next().ok_or("not enough arguments for template")?;
+                out.push_str(&arg.to_string());
+            }
+            _ => out.push(c),
+        }
+    }
+    if next.next().is_some() {
+        return Err("too many arguments for template".to_string());
+    }
+    Ok(out)
+}
+
+builtin!(format_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let template = match args.first() {
+            Some(Value::String(t)) => t,
+            _ => return Err("format expects a template string as first argument".to_string()),
+        };
+        format_template(template, &args[1..]).map(Value::String).map_err(|e| format!("format: {}", e))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Regex builtins (compiled patterns are cached by source text)
+// -----------------------------------------------------------------------------
+
+lazy_static! {
+    static ref REGEX_CACHE: Mutex<HashMap<String, Regex>> = Mutex::new(HashMap::new());
+}
+
+fn cached_regex(pattern: &str) -> Result<Regex, String> {
+    let mut cache = REGEX_CACHE.lock().unwrap();
+    if let Some(re) = cache.get(pattern) {
+        return Ok(re.clone());
+    }
+    let re = Regex::new(pattern).map_err(|e| format!("Invalid regex '{}': {}", pattern, e))?;
+    cache.insert(pattern.to_string(), re.clone());
+    Ok(re)
+}
+
+builtin!(regex_split_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("regex_split expects 2 arguments: pattern, text".to_string());
+        }
+        match (&args[0], &args[1]) {
+            (Value::String(pattern), Value::String(text)) => {
+                let re = cached_regex(pattern)?;
+                let parts: Vec<Value> = re.split(text).map(|x| Value::String(x.to_string())).collect();
+                Ok(Value::Array(Rc::new(ArrayCell::new(parts))))
+            }
+            _ => Err("regex_split: arguments must be strings".to_string()),
+        }
+    })
+});
+
+builtin!(regex_captures_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("regex_captures expects 2 arguments: pattern, text".to_string());
+        }
+        match (&args[0], &args[1]) {
+            (Value::String(pattern), Value::String(text)) => {
+                let re = cached_regex(pattern)?;
+                // Group 0 is the whole match; groups that did not participate are null.
+                let groups: Vec<Value> = match re.captures(text) {
+                    Some(caps) => caps
+                        .iter()
+                        .map(|m| m.map_or(Value::Null, |m| Value::String(m.as_str().to_string())))
+                        .collect(),
+                    None => Vec::new(),
+                };
+                Ok(Value::Array(Rc::new(ArrayCell::new(groups))))
+            }
+            _ => Err("regex_captures: arguments must be strings".to_string()),
+        }
+    })
+});
+
 builtin!(get_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
         if args.len() != 2 {
             return Err("get expects 2 arguments".to_string());
         }
-        match (&args[0], &args[1]) {
-            (Value::Array(arr_rc), Value::Number(i)) => {
+        match (&args[0], args[1].as_i64()) {
+            (Value::Array(arr_rc), Some(i)) => {
                 let arr = arr_rc.borrow();
-                let idx = *i as usize;
+                let idx = i as usize;
                 if idx < arr.len() {
                     Ok(arr[idx].clone())
                 } else {
@@ -350,10 +764,10 @@ builtin!(set_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Valu
         if args.len() != 3 {
             return Err("set expects 3 arguments".to_string());
         }
-        match (&args[0], &args[1], &args[2]) {
-            (Value::Array(arr_rc), Value::Number(i), val) => {
-                let mut arr = arr_rc.borrow_mut();
-                let idx = *i as usize;
+        match (&args[0], args[1].as_i64(), &args[2]) {
+            (Value::Array(arr_rc), Some(i), val) => {
+                let mut arr = array_mut("set", arr_rc)?;
+                let idx = i as usize;
                 if idx < arr.len() {
                     arr[idx] = val.clone();
                     Ok(Value::Null)
@@ -366,115 +780,1984 @@ builtin!(set_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Valu
     })
 });
 
-builtin!(file_exists_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+// Longest array that set_grow/fill/resize will build: an index or count typo
+// (`set_grow(a, 10000000000, x)`) is an error instead of an allocation abort.
+const MAX_ARRAY_LEN: usize = 1 << 26;
+
+// Extend or shrink `arr` to `new_len`, filling with `fill`; fails past
+// MAX_ARRAY_LEN or when the memory cannot be reserved.
+fn grow_array(name: &str, arr: &mut Vec<Value>, new_len: usize, fill: Value) -> Result<(), String> {
+    if new_len > MAX_ARRAY_LEN {
+        return Err(format!("{}: array length {} exceeds the limit of {}", name, new_len, MAX_ARRAY_LEN));
+    }
+    arr.try_reserve(new_len.saturating_sub(arr.len()))
+        .map_err(|_| format!("{}: cannot allocate an array of length {}", name, new_len))?;
+    arr.resize(new_len, fill);
+    Ok(())
+}
+
+// set_grow(array, index, value): like set, but an index past the end first
+// extends the array with nulls. Negative indices are out of bounds, as for get/set,
+// and the array cannot grow past MAX_ARRAY_LEN elements.
+builtin!(set_grow_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 1 {
-            return Err("file_exists expects 1 argument".to_string());
+        if args.len() != 3 {
+            return Err("set_grow expects 3 arguments: array, index, value".to_string());
+        }
+        match (&args[0], args[1].as_i64(), &args[2]) {
+            (Value::Array(_), Some(i), _) if i < 0 => Err("set_grow: index out of bounds".to_string()),
+            (Value::Array(arr_rc), Some(i), val) => {
+                let mut arr = array_mut("set_grow", arr_rc)?;
+                let idx = i as usize;
+                if idx >= arr.len() {
+                    grow_array("set_grow", &mut arr, idx.saturating_add(1), Value::Null)?;
+                }
+                arr[idx] = val.clone();
+                Ok(Value::Null)
+            }
+            _ => Err("set_grow: first argument must be array, second must be number".to_string()),
         }
-        let path = match &args[0] {
-            Value::String(s) => s,
-            _ => return Err("file_exists argument must be string".to_string()),
-        };
-        Ok(Value::Boolean(Path::new(path).exists()))
     })
 });
 
-builtin!(mem_read_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+// fill/resize copy the Value itself, so arrays and objects used as the fill
+// value are shared between slots (same as assigning them to several variables).
+builtin!(fill_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 1 {
-            return Err("mem_read expects 1 argument".to_string());
+        if args.len() != 2 {
+            return Err("fill expects 2 arguments: value, count".to_string());
         }
-        let addr = match &args[0] {
-            Value::Number(n) => *n as usize,
-            _ => return Err("mem_read argument must be number".to_string()),
+        let count = match args[1].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            _ => return Err("fill: count must be a non-negative number".to_string()),
         };
-        match env.mem_read(addr) {
-            Ok(byte) => Ok(Value::Number(byte as f64)),
-            Err(e) => Err(e),
-        }
+        let mut arr = Vec::new();
+        grow_array("fill", &mut arr, count, args[0].clone())?;
+        Ok(Value::Array(Rc::new(ArrayCell::new(arr))))
     })
 });
 
-builtin!(mem_write_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+builtin!(resize_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 2 {
-            return Err("mem_write expects 2 arguments".to_string());
+        if args.len() != 3 {
+            return Err("resize expects 3 arguments: array, new_len, fill".to_string());
         }
-        let addr = match &args[0] {
-            Value::Number(n) => *n as usize,
-            _ => return Err("mem_write first argument must be number".to_string()),
+        let arr_rc = match &args[0] {
+            Value::Array(arr_rc) => arr_rc,
+            _ => return Err("resize: first argument must be array".to_string()),
         };
-        let value = match &args[1] {
-            Value::Number(n) => *n as u8,
-            _ => return Err("mem_write second argument must be number".to_string()),
+        let new_len = match args[1].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            _ => return Err("resize: new_len must be a non-negative number".to_string()),
         };
-        match env.mem_write(addr, value) {
-            Ok(()) => Ok(Value::Null),
-            Err(e) => Err(e),
-        }
+        let mut arr = array_mut("resize", arr_rc)?;
+        grow_array("resize", &mut arr, new_len, args[2].clone())?;
+        Ok(Value::Null)
     })
 });
 
-builtin!(get_reg_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+// freeze(arr): make the array itself read-only (push, pop, set, resize, ... fail)
+// and return it. Arrays nested inside stay mutable; see deep_freeze.
+builtin!(freeze_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 1 {
-            return Err("get_reg expects 1 argument".to_string());
+        match args.as_slice() {
+            [Value::Array(arr)] => {
+                freeze_array(arr);
+                Ok(args[0].clone())
+            }
+            [_] => Err("freeze: argument must be array".to_string()),
+            _ => Err("freeze expects 1 argument".to_string()),
         }
-        let name = match &args[0] {
-            Value::String(s) => s,
-            _ => return Err("get_reg argument must be string".to_string()),
+    })
+});
+
+// deep_freeze(arr): freeze the array and every array reachable through it.
+builtin!(deep_freeze_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let root = match args.as_slice() {
+            [Value::Array(arr)] => Rc::clone(arr),
+            [_] => return Err("deep_freeze: argument must be array".to_string()),
+            _ => return Err("deep_freeze expects 1 argument".to_string()),
         };
-        match env.get_reg(name) {
-            Some(val) => Ok(Value::Number(val as f64)),
-            None => Err(format!("Register '{}' not defined", name)),
+        // Обход со стеком; каждый массив посещается один раз, так что циклы не страшны
+        let mut queue = vec![root];
+        let mut seen = std::collections::HashSet::new();
+        while let Some(arr) = queue.pop() {
+            if !seen.insert(Rc::as_ptr(&arr) as usize) {
+                continue;
+            }
+            freeze_array(&arr);
+            for item in arr.borrow().iter() {
+                if let Value::Array(inner) = item {
+                    queue.push(Rc::clone(inner));
+                }
+            }
         }
+        Ok(args[0].clone())
     })
 });
 
-builtin!(set_reg_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+builtin!(is_frozen_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 2 {
-            return Err("set_reg expects 2 arguments".to_string());
+        match args.as_slice() {
+            [Value::Array(arr)] => Ok(Value::Boolean(is_frozen(arr))),
+            [_] => Ok(Value::Boolean(false)),
+            _ => Err("is_frozen expects 1 argument".to_string()),
         }
-        let name = match &args[0] {
-            Value::String(s) => s.clone(),
-            _ => return Err("set_reg first argument must be string".to_string()),
+    })
+});
+
+// Duplicates are detected with deep_eq, so [1, 2] and [1, 2] count as the same element.
+// Scalar keys go through a hash set; only containers are compared pairwise.
+fn dedup_by_keys(items: Vec<Value>, keys: &[Value]) -> Vec<Value> {
+    let mut seen_scalars: HashSet<SetKey> = HashSet::new();
+    let mut seen_others: Vec<&Value> = Vec::new();
+    let mut out = Vec::new();
+    for (item, key) in items.into_iter().zip(keys) {
+        let new = match SetKey::from_value(key) {
+            Ok(scalar) => seen_scalars.insert(scalar),
+            // NaN and containers: deep_eq never matches a scalar against them
+            Err(_) if seen_others.iter().any(|k| k.deep_eq(key)) => false,
+            Err(_) => {
+                seen_others.push(key);
+                true
+            }
         };
-        let value = match &args[1] {
-            Value::Number(n) => *n as i64,
-            _ => return Err("set_reg second argument must be number".to_string()),
+        if new {
+            out.push(item);
+        }
+    }
+    out
+}
+
+builtin!(unique_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("unique expects 1 argument".to_string());
+        }
+        let items = match &args[0] {
+            Value::Array(arr_rc) => arr_rc.borrow().clone(),
+            _ => return Err("unique: argument must be array".to_string()),
         };
-        env.set_reg(name, value);
-        Ok(Value::Null)
+        let keys = items.clone();
+        Ok(Value::Array(Rc::new(ArrayCell::new(dedup_by_keys(items, &keys)))))
     })
 });
 
 // -----------------------------------------------------------------------------
-// DLL-related builtins (with 64‑bit support)
+// Head and tail (first, last, rest, take, drop). An empty array gives null /
+// an empty array; counts past the end are clamped to the array length.
 // -----------------------------------------------------------------------------
 
-builtin!(dll_load_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+type ArrayRef = Rc<ArrayCell>;
+
+fn array_arg<'a>(name: &str, value: &'a Value) -> Result<&'a ArrayRef, String> {
+    match value {
+        Value::Array(arr) => Ok(arr),
+        other => Err(format!("{}: first argument must be array, got {}", name, other.type_name())),
+    }
+}
+
+builtin!(first_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
         if args.len() != 1 {
-            return Err("dll_load expects 1 argument".to_string());
+            return Err("first expects 1 argument".to_string());
         }
-        let path = match &args[0] {
-            Value::String(s) => s,
-            _ => return Err("dll_load argument must be string".to_string()),
-        };
-        unsafe {
-            match Library::new(path) {
-                Ok(lib) => Ok(Value::Dll(Rc::new(lib))),
-                Err(e) => Err(format!("Failed to load DLL: {}", e)),
-            }
+        let first = array_arg("first", &args[0])?.borrow().first().cloned();
+        Ok(first.unwrap_or(Value::Null))
+    })
+});
+
+builtin!(last_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("last expects 1 argument".to_string());
         }
+        let last = array_arg("last", &args[0])?.borrow().last().cloned();
+        Ok(last.unwrap_or(Value::Null))
     })
 });
 
-builtin!(dll_call_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+// rest(arr): a new array without the first element.
+builtin!(rest_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() < 2 {
-            return Err("dll_call expects at least 2 arguments".to_string());
+        if args.len() != 1 {
+            return Err("rest expects 1 argument".to_string());
+        }
+        let rest = array_arg("rest", &args[0])?.borrow().iter().skip(1).cloned().collect();
+        Ok(Value::Array(Rc::new(ArrayCell::new(rest))))
+    })
+});
+
+/// The array and element count for take/drop.
+fn count_args<'a>(name: &str, args: &'a [Value]) -> Result<(&'a ArrayRef, usize), String> {
+    if args.len() != 2 {
+        return Err(format!("{} expects 2 arguments: array, n", name));
+    }
+    let arr = array_arg(name, &args[0])?;
+    match args[1].as_i64() {
+        Some(n) if n >= 0 => Ok((arr, n as usize)),
+        _ => Err(format!("{}: n must be a non-negative number", name)),
+    }
+}
+
+builtin!(take_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (arr, n) = count_args("take", &args)?;
+        let taken = arr.borrow().iter().take(n).cloned().collect();
+        Ok(Value::Array(Rc::new(ArrayCell::new(taken))))
+    })
+});
+
+builtin!(drop_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (arr, n) = count_args("drop", &args)?;
+        let kept = arr.borrow().iter().skip(n).cloned().collect();
+        Ok(Value::Array(Rc::new(ArrayCell::new(kept))))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Fixed-size pieces of an array (chunk, windows), each a new array
+// -----------------------------------------------------------------------------
+
+fn size_args<'a>(name: &str, args: &'a [Value]) -> Result<(&'a ArrayRef, usize), String> {
+    if args.len() != 2 {
+        return Err(format!("{} expects 2 arguments: array, size", name));
+    }
+    let arr = array_arg(name, &args[0])?;
+    match args[1].as_i64() {
+        Some(n) if n > 0 => Ok((arr, n as usize)),
+        _ => Err(format!("{}: size must be a positive number", name)),
+    }
+}
+
+fn arrays_value<'a>(pieces: impl Iterator<Item = &'a [Value]>) -> Value {
+    let pieces = pieces.map(|p| Value::Array(Rc::new(ArrayCell::new(p.to_vec())))).collect();
+    Value::Array(Rc::new(ArrayCell::new(pieces)))
+}
+
+// chunk(array(1, 2, 3, 4, 5), 2) -> [[1, 2], [3, 4], [5]]
+builtin!(chunk_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (arr, size) = size_args("chunk", &args)?;
+        let items = arr.borrow();
+        Ok(arrays_value(items.chunks(size)))
+    })
+});
+
+// windows(array(1, 2, 3), 2) -> [[1, 2], [2, 3]]; empty if size exceeds the length.
+builtin!(windows_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (arr, size) = size_args("windows", &args)?;
+        let items = arr.borrow();
+        Ok(arrays_value(items.windows(size)))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Maps (string keys; numbers and booleans are stringified)
+// -----------------------------------------------------------------------------
+
+fn map_key(name: &str, key: &Value) -> Result<String, String> {
+    match key {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(_) | Value::Int(_) | Value::Boolean(_) => Ok(key.to_string()),
+        _ => Err(format!("{}: key must be string, number or boolean", name)),
+    }
+}
+
+fn map_arg<'a>(name: &str, value: &'a Value) -> Result<&'a Rc<RefCell<MapValue>>, String> {
+    match value {
+        Value::Map(map) => Ok(map),
+        _ => Err(format!("{}: first argument must be map", name)),
+    }
+}
+
+builtin!(map_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if !args.len().is_multiple_of(2) {
+            return Err("map expects key/value pairs".to_string());
+        }
+        let mut map = MapValue::new();
+        for pair in args.chunks(2) {
+            map.insert(map_key("map", &pair[0])?, pair[1].clone());
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    })
+});
+
+// ordered_map(k1, v1, ...): like map, but keys iterate in insertion order.
+builtin!(ordered_map_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if !args.len().is_multiple_of(2) {
+            return Err("ordered_map expects key/value pairs".to_string());
+        }
+        let mut map = MapValue::ordered();
+        for pair in args.chunks(2) {
+            map.insert(map_key("ordered_map", &pair[0])?, pair[1].clone());
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    })
+});
+
+builtin!(map_get_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 && args.len() != 3 {
+            return Err("map_get expects 2 or 3 arguments: map, key, default".to_string());
+        }
+        let map = map_arg("map_get", &args[0])?;
+        let key = map_key("map_get", &args[1])?;
+        let value = map.borrow().get(&key).cloned();
+        Ok(value.unwrap_or_else(|| args.get(2).cloned().unwrap_or(Value::Null)))
+    })
+});
+
+builtin!(map_set_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 3 {
+            return Err("map_set expects 3 arguments: map, key, value".to_string());
+        }
+        let map = map_arg("map_set", &args[0])?;
+        let key = map_key("map_set", &args[1])?;
+        map.borrow_mut().insert(key, args[2].clone());
+        Ok(Value::Null)
+    })
+});
+
+builtin!(map_has_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("map_has expects 2 arguments: map, key".to_string());
+        }
+        let map = map_arg("map_has", &args[0])?;
+        let key = map_key("map_has", &args[1])?;
+        let has = map.borrow().contains_key(&key);
+        Ok(Value::Boolean(has))
+    })
+});
+
+builtin!(map_remove_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("map_remove expects 2 arguments: map, key".to_string());
+        }
+        let map = map_arg("map_remove", &args[0])?;
+        let key = map_key("map_remove", &args[1])?;
+        let removed = map.borrow_mut().remove(&key);
+        Ok(removed.unwrap_or(Value::Null))
+    })
+});
+
+builtin!(map_keys_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("map_keys expects 1 argument".to_string());
+        }
+        let map = map_arg("map_keys", &args[0])?;
+        let keys: Vec<Value> = map.borrow().keys().into_iter().map(Value::String).collect();
+        Ok(Value::Array(Rc::new(ArrayCell::new(keys))))
+    })
+});
+
+/// Copy of `base` with the entries of `over` added on top. With `deep`, a key
+/// that holds a map on both sides gets the two maps merged instead of replaced.
+fn merge_maps(base: &MapValue, over: &MapValue, deep: bool) -> MapValue {
+    let mut merged = base.clone();
+    for (key, value) in over.iter() {
+        let value = match (deep, merged.get(key), value) {
+            (true, Some(Value::Map(inner)), Value::Map(other)) => {
+                let inner = merge_maps(&inner.borrow(), &other.borrow(), true);
+                Value::Map(Rc::new(RefCell::new(inner)))
+            }
+            _ => value.clone(),
+        };
+        merged.insert(key.clone(), value);
+    }
+    merged
+}
+
+fn merge_args(name: &str, args: &[Value], deep: bool) -> Result<Value, String> {
+    match args {
+        [Value::Map(a), Value::Map(b)] => {
+            let merged = merge_maps(&a.borrow(), &b.borrow(), deep);
+            Ok(Value::Map(Rc::new(RefCell::new(merged))))
+        }
+        [_, _] => Err(format!("{}: both arguments must be maps", name)),
+        _ => Err(format!("{} expects 2 arguments (maps)", name)),
+    }
+}
+
+// merge(a, b): new map with b's entries overriding a's; a and b are unchanged
+builtin!(merge_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { merge_args("merge", &args, false) })
+});
+
+// deep_merge(a, b): like merge, but nested maps present in both are merged too
+builtin!(deep_merge_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { merge_args("deep_merge", &args, true) })
+});
+
+/// New map (sorted or ordered, like the source) with the entries whose key is
+/// (`keep == true`) or is not (`keep == false`) listed in the keys array.
+fn filter_map_keys(name: &str, args: &[Value], keep: bool) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err(format!("{} expects 2 arguments: map, keys array", name));
+    }
+    let map = map_arg(name, &args[0])?;
+    let keys = match &args[1] {
+        Value::Array(arr) => arr
+            .borrow()
+            .iter()
+            .map(|k| map_key(name, k))
+            .collect::<Result<HashSet<String>, String>>()?,
+        _ => return Err(format!("{}: second argument must be array of keys", name)),
+    };
+    let map = map.borrow();
+    let mut result = if map.is_ordered() { MapValue::ordered() } else { MapValue::new() };
+    for (key, value) in map.iter() {
+        if keys.contains(key) == keep {
+            result.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(Value::Map(Rc::new(RefCell::new(result))))
+}
+
+// pick(m, array("a", "b")): only the listed keys; missing ones are skipped
+builtin!(pick_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { filter_map_keys("pick", &args, true) })
+});
+
+// omit(m, array("a")): everything except the listed keys
+builtin!(omit_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { filter_map_keys("omit", &args, false) })
+});
+
+// -----------------------------------------------------------------------------
+// Sets of scalars (numbers, strings, booleans, null)
+// -----------------------------------------------------------------------------
+
+type SetRef = Rc<RefCell<BTreeSet<SetKey>>>;
+
+fn set_value(items: BTreeSet<SetKey>) -> Value {
+    Value::Set(Rc::new(RefCell::new(items)))
+}
+
+// set_of(a, b, ...) or set_of(array): a new set of the given elements.
+builtin!(set_of_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let items = match args.as_slice() {
+            [Value::Array(arr)] => arr.borrow().clone(),
+            _ => args,
+        };
+        let set = items
+            .iter()
+            .map(SetKey::from_value)
+            .collect::<Result<BTreeSet<_>, _>>()
+            .map_err(|e| format!("set_of: {}", e))?;
+        Ok(set_value(set))
+    })
+});
+
+fn set_arg<'a>(name: &str, value: &'a Value) -> Result<&'a SetRef, String> {
+    match value {
+        Value::Set(set) => Ok(set),
+        other => Err(format!("{}: expected set, got {}", name, other.type_name())),
+    }
+}
+
+fn set_elem(name: &str, value: &Value) -> Result<SetKey, String> {
+    SetKey::from_value(value).map_err(|e| format!("{}: {}", name, e))
+}
+
+// set_add(s, x): true if x was not in the set yet.
+builtin!(set_add_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("set_add expects 2 arguments: set, value".to_string());
+        }
+        let set = set_arg("set_add", &args[0])?;
+        let key = set_elem("set_add", &args[1])?;
+        let added = set.borrow_mut().insert(key);
+        Ok(Value::Boolean(added))
+    })
+});
+
+builtin!(set_has_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("set_has expects 2 arguments: set, value".to_string());
+        }
+        let set = set_arg("set_has", &args[0])?;
+        // Несравнимое значение (массив, NaN) просто не может лежать в множестве
+        let has = match SetKey::from_value(&args[1]) {
+            Ok(key) => set.borrow().contains(&key),
+            Err(_) => false,
+        };
+        Ok(Value::Boolean(has))
+    })
+});
+
+// set_remove(s, x): true if x was in the set.
+builtin!(set_remove_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("set_remove expects 2 arguments: set, value".to_string());
+        }
+        let set = set_arg("set_remove", &args[0])?;
+        let removed = match SetKey::from_value(&args[1]) {
+            Ok(key) => set.borrow_mut().remove(&key),
+            Err(_) => false,
+        };
+        Ok(Value::Boolean(removed))
+    })
+});
+
+/// Apply a binary set operation to two set arguments, returning a new set.
+fn set_op(
+    name: &str,
+    args: &[Value],
+    op: fn(&BTreeSet<SetKey>, &BTreeSet<SetKey>) -> BTreeSet<SetKey>,
+) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err(format!("{} expects 2 arguments: set, set", name));
+    }
+    let a = set_arg(name, &args[0])?.borrow();
+    let b = set_arg(name, &args[1])?.borrow();
+    Ok(set_value(op(&a, &b)))
+}
+
+builtin!(set_union_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { set_op("set_union", &args, |a, b| a.union(b).cloned().collect()) })
+});
+
+builtin!(set_intersect_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { set_op("set_intersect", &args, |a, b| a.intersection(b).cloned().collect()) })
+});
+
+builtin!(set_difference_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { set_op("set_difference", &args, |a, b| a.difference(b).cloned().collect()) })
+});
+
+// -----------------------------------------------------------------------------
+// 2D grids (arrays of row arrays)
+// -----------------------------------------------------------------------------
+
+builtin!(grid_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 3 {
+            return Err("grid expects 3 arguments: rows, cols, fill".to_string());
+        }
+        let (rows, cols) = match (args[0].as_i64(), args[1].as_i64()) {
+            (Some(r), Some(c)) if r >= 0 && c >= 0 => (r as usize, c as usize),
+            _ => return Err("grid: rows and cols must be non-negative numbers".to_string()),
+        };
+        // Every row is a separate array so grid_set on one row does not affect the others
+        let grid: Vec<Value> = (0..rows)
+            .map(|_| Value::Array(Rc::new(ArrayCell::new(vec![args[2].clone(); cols]))))
+            .collect();
+        Ok(Value::Array(Rc::new(ArrayCell::new(grid))))
+    })
+});
+
+type GridRow = Rc<ArrayCell>;
+
+/// Resolve `g[r]` for grid_get/grid_set, checking both coordinates.
+fn grid_row(name: &str, grid: &Value, r: &Value, c: &Value) -> Result<(GridRow, usize), String> {
+    let rows = match grid {
+        Value::Array(rows) => rows,
+        _ => return Err(format!("{}: first argument must be a grid", name)),
+    };
+    let (r, c) = match (r.as_i64(), c.as_i64()) {
+        (Some(r), Some(c)) => (r, c),
+        _ => return Err(format!("{}: row and column must be numbers", name)),
+    };
+    let rows = rows.borrow();
+    if r < 0 || r as usize >= rows.len() {
+        return Err(format!("{}: row {} out of range (grid has {} rows)", name, r, rows.len()));
+    }
+    let row = match &rows[r as usize] {
+        Value::Array(row) => Rc::clone(row),
+        _ => return Err(format!("{}: row {} is not an array", name, r)),
+    };
+    let cols = row.borrow().len();
+    if c < 0 || c as usize >= cols {
+        return Err(format!("{}: column {} out of range at row {} (row has {} columns)", name, c, r, cols));
+    }
+    Ok((row, c as usize))
+}
+
+builtin!(grid_get_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 3 {
+            return Err("grid_get expects 3 arguments: grid, row, col".to_string());
+        }
+        let (row, c) = grid_row("grid_get", &args[0], &args[1], &args[2])?;
+        let value = row.borrow()[c].clone();
+        Ok(value)
+    })
+});
+
+builtin!(grid_set_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 4 {
+            return Err("grid_set expects 4 arguments: grid, row, col, value".to_string());
+        }
+        let (row, c) = grid_row("grid_set", &args[0], &args[1], &args[2])?;
+        array_mut("grid_set", &row)?[c] = args[3].clone();
+        Ok(Value::Null)
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Text encodings: strings <-> arrays of byte values (0-255)
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Copy)]
+enum Encoding {
+    Utf8,
+    Latin1,
+    Ascii,
+}
+
+fn encoding_arg(name: &str, value: &Value) -> Result<Encoding, String> {
+    let enc = match value {
+        Value::String(s) => s.to_ascii_lowercase(),
+        _ => return Err(format!("{}: encoding must be string", name)),
+    };
+    match enc.as_str() {
+        "utf8" | "utf-8" => Ok(Encoding::Utf8),
+        "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+        "ascii" | "us-ascii" => Ok(Encoding::Ascii),
+        _ => Err(format!("{}: unknown encoding '{}' (expected utf8, latin1 or ascii)", name, enc)),
+    }
+}
+
+// encode("héllo", "latin1") -> [104, 233, 108, 108, 111]
+builtin!(encode_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("encode expects 2 arguments: string, encoding".to_string());
+        }
+        let s = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("encode: first argument must be string".to_string()),
+        };
+        let bytes: Vec<u8> = match encoding_arg("encode", &args[1])? {
+            Encoding::Utf8 => s.as_bytes().to_vec(),
+            enc => {
+                let (max, label) = match enc {
+                    Encoding::Ascii => (0x7F, "ascii"),
+                    _ => (0xFF, "latin1"),
+                };
+                s.chars()
+                    .map(|c| match c as u32 {
+                        code if code <= max => Ok(code as u8),
+                        _ => Err(format!("encode: character {:?} cannot be encoded as {}", c, label)),
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        let bytes = bytes.into_iter().map(|b| Value::Int(b as i64)).collect();
+        Ok(Value::Array(Rc::new(ArrayCell::new(bytes))))
+    })
+});
+
+builtin!(decode_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("decode expects 2 arguments: byte array, encoding".to_string());
+        }
+        let encoding = encoding_arg("decode", &args[1])?;
+        let bytes = match &args[0] {
+            Value::Array(arr) => arr
+                .borrow()
+                .iter()
+                .map(|v| match v.as_i64() {
+                    Some(b @ 0..=255) => Ok(b as u8),
+                    _ => Err(format!("decode: {} is not a byte (0-255)", v)),
+                })
+                .collect::<Result<Vec<u8>, _>>()?,
+            _ => return Err("decode: first argument must be array of bytes".to_string()),
+        };
+        let s = match encoding {
+            Encoding::Utf8 => String::from_utf8(bytes).map_err(|e| format!("decode: invalid utf8: {}", e))?,
+            // Latin-1 совпадает с первыми 256 кодами Юникода
+            Encoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+            Encoding::Ascii => {
+                if let Some(b) = bytes.iter().find(|b| !b.is_ascii()) {
+                    return Err(format!("decode: byte {} is outside ascii (0-127)", b));
+                }
+                bytes.iter().map(|&b| b as char).collect()
+            }
+        };
+        Ok(Value::String(s))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Toy ciphers for obfuscation examples; not cryptography
+// -----------------------------------------------------------------------------
+
+/// Bytes of a cipher argument: the UTF-8 bytes of a string or an array of byte values.
+fn cipher_bytes(name: &str, value: &Value) -> Result<Vec<u8>, String> {
+    match value {
+        Value::String(s) => Ok(s.as_bytes().to_vec()),
+        Value::Array(arr) => arr
+            .borrow()
+            .iter()
+            .map(|v| match v.as_i64() {
+                Some(b @ 0..=255) => Ok(b as u8),
+                _ => Err(format!("{}: {} is not a byte (0-255)", name, v)),
+            })
+            .collect(),
+        other => Err(format!("{}: expected string or byte array, got {}", name, other.type_name())),
+    }
+}
+
+// xor_cipher("hi", "k") -> [3, 2]; the result is always a byte array, so
+// `decode(xor_cipher(xor_cipher(s, k), k), "utf8") == s`
+builtin!(xor_cipher_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("xor_cipher expects 2 arguments: data, key".to_string());
+        }
+        let data = cipher_bytes("xor_cipher", &args[0])?;
+        let key = cipher_bytes("xor_cipher", &args[1])?;
+        if key.is_empty() {
+            return Err("xor_cipher: key must not be empty".to_string());
+        }
+        let bytes = data
+            .iter()
+            .zip(key.iter().cycle())
+            .map(|(d, k)| Value::Int((d ^ k) as i64))
+            .collect();
+        Ok(Value::Array(Rc::new(ArrayCell::new(bytes))))
+    })
+});
+
+// rot("Hello", 13) -> "Uryyb"; only ASCII letters move, case is kept
+builtin!(rot_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("rot expects 2 arguments: string, shift".to_string());
+        }
+        let s = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("rot: first argument must be string".to_string()),
+        };
+        let shift = args[1].as_i64().ok_or("rot: shift must be a number")?.rem_euclid(26) as u8;
+        let rotated = s
+            .chars()
+            .map(|c| match c {
+                'a'..='z' => ((c as u8 - b'a' + shift) % 26 + b'a') as char,
+                'A'..='Z' => ((c as u8 - b'A' + shift) % 26 + b'A') as char,
+                _ => c,
+            })
+            .collect();
+        Ok(Value::String(rotated))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Hashing (over the UTF-8 bytes of a string, or streamed from a file)
+// -----------------------------------------------------------------------------
+
+/// CRC-32 (IEEE, as used by zip/PNG), bitwise – no table needed for script-sized inputs.
+fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(0xFFFF_FFFF, data)
+}
+
+/// Feed `data` into a running CRC-32 register (start with `0xFFFF_FFFF`, invert at the end).
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    crc
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 (FIPS 180-4).
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// Incremental SHA-256: `update` with any number of pieces, then `finish`.
+struct Sha256 {
+    h: [u32; 8],
+    pending: Vec<u8>, // неполный блок, меньше 64 байт
+    len: u64,
+}
+
+impl Sha256 {
+    fn new() -> Self {
+        Sha256 {
+            h: [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19],
+            pending: Vec::with_capacity(64),
+            len: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        if !self.pending.is_empty() {
+            let take = (64 - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.pending.len() < 64 {
+                return;
+            }
+            let block = std::mem::take(&mut self.pending);
+            self.compress(&block);
+        }
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        self.pending.extend_from_slice(blocks.remainder());
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bit_len = self.len * 8;
+        let mut tail = std::mem::take(&mut self.pending);
+        tail.push(0x80);
+        while tail.len() % 64 != 56 {
+            tail.push(0);
+        }
+        tail.extend_from_slice(&bit_len.to_be_bytes());
+        for block in tail.chunks(64) {
+            self.compress(block);
+        }
+        let mut out = [0u8; 32];
+        for (i, word) in self.h.iter().enumerate() {
+            out[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+
+    fn compress(&mut self, chunk: &[u8]) {
+        let h = &mut self.h;
+        let mut w = [0u32; 64];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(v);
+        }
+    }
+}
+
+builtin!(crc32_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("crc32 expects 1 argument".to_string());
+        }
+        match &args[0] {
+            Value::String(s) => Ok(Value::Int(crc32(s.as_bytes()) as i64)),
+            _ => Err("crc32: argument must be string".to_string()),
+        }
+    })
+});
+
+builtin!(sha256_hex_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("sha256_hex expects 1 argument".to_string());
+        }
+        match &args[0] {
+            Value::String(s) => {
+                let hex: String = sha256(s.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+                Ok(Value::String(hex))
+            }
+            _ => Err("sha256_hex: argument must be string".to_string()),
+        }
+    })
+});
+
+/// Read the file at `args[0]` in fixed-size pieces, passing each one to `consume`.
+fn stream_file(name: &str, args: &[Value], mut consume: impl FnMut(&[u8])) -> Result<(), String> {
+    use std::io::Read;
+    let path = match args {
+        [Value::String(p)] => p,
+        [_] => return Err(format!("{}: path must be string", name)),
+        _ => return Err(format!("{} expects 1 argument", name)),
+    };
+    let mut file = fs::File::open(path).map_err(|e| format!("{}: failed to open '{}': {}", name, path, e))?;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => consume(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(format!("{}: failed to read '{}': {}", name, path, e)),
+        }
+    }
+}
+
+builtin!(file_crc32_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let mut crc = 0xFFFF_FFFF;
+        stream_file("file_crc32", &args, |chunk| crc = crc32_update(crc, chunk))?;
+        Ok(Value::Int(!crc as i64))
+    })
+});
+
+builtin!(file_sha256_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let mut hasher = Sha256::new();
+        stream_file("file_sha256", &args, |chunk| hasher.update(chunk))?;
+        let hex: String = hasher.finish().iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(Value::String(hex))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Higher-order array builtins (take function values as callbacks)
+// -----------------------------------------------------------------------------
+
+builtin!(sort_by_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("sort_by expects 2 arguments: array, key function".to_string());
+        }
+        let items = match &args[0] {
+            Value::Array(arr_rc) => arr_rc.borrow().clone(),
+            _ => return Err("sort_by: first argument must be array".to_string()),
+        };
+        // Schwartzian transform: the key function runs once per element.
+        let mut keyed = Vec::with_capacity(items.len());
+        for item in items {
+            let key = crate::eval::call_value(&args[1], vec![item.clone()], env).await?;
+            keyed.push((key, item));
+        }
+        if keyed.iter().all(|(k, _)| matches!(k, Value::Number(_) | Value::Int(_))) {
+            // NaN has no place in the order; sort_by must get a total order or it panics.
+            if keyed.iter().any(|(k, _)| matches!(k, Value::Number(n) if n.is_nan())) {
+                return Err("sort_by: key function returned NaN".to_string());
+            }
+            keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+                (Value::Int(x), Value::Int(y)) => x.cmp(y),
+                _ => a.as_f64().unwrap_or(0.0).total_cmp(&b.as_f64().unwrap_or(0.0)),
+            });
+        } else if keyed.iter().all(|(k, _)| matches!(k, Value::String(_))) {
+            keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+                (Value::String(x), Value::String(y)) => x.cmp(y),
+                _ => std::cmp::Ordering::Equal,
+            });
+        } else {
+            return Err("sort_by: keys must be all numbers or all strings".to_string());
+        }
+        let sorted: Vec<Value> = keyed.into_iter().map(|(_, item)| item).collect();
+        Ok(Value::Array(Rc::new(ArrayCell::new(sorted))))
+    })
+});
+
+builtin!(unique_by_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("unique_by expects 2 arguments: array, key function".to_string());
+        }
+        let items = match &args[0] {
+            Value::Array(arr_rc) => arr_rc.borrow().clone(),
+            _ => return Err("unique_by: first argument must be array".to_string()),
+        };
+        let mut keys = Vec::with_capacity(items.len());
+        for item in &items {
+            keys.push(crate::eval::call_value(&args[1], vec![item.clone()], env).await?);
+        }
+        Ok(Value::Array(Rc::new(ArrayCell::new(dedup_by_keys(items, &keys)))))
+    })
+});
+
+builtin!(group_by_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("group_by expects 2 arguments: array, key function".to_string());
+        }
+        let items = match &args[0] {
+            Value::Array(arr_rc) => arr_rc.borrow().clone(),
+            _ => return Err("group_by: first argument must be array".to_string()),
+        };
+        let mut groups: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+        for item in items {
+            let key = crate::eval::call_value(&args[1], vec![item.clone()], env).await?;
+            groups.entry(key.to_string()).or_default().push(item);
+        }
+        let map: BTreeMap<String, Value> = groups
+            .into_iter()
+            .map(|(k, v)| (k, Value::Array(Rc::new(ArrayCell::new(v)))))
+            .collect();
+        Ok(Value::Map(Rc::new(RefCell::new(MapValue::from(map)))))
+    })
+});
+
+/// Number of leading elements of `args[0]` for which the predicate `args[1]` is truthy.
+async fn leading_matches(name: &str, args: &[Value], env: &mut Env) -> Result<(Vec<Value>, usize), String> {
+    if args.len() != 2 {
+        return Err(format!("{} expects 2 arguments: array, predicate", name));
+    }
+    let items = array_arg(name, &args[0])?.borrow().clone();
+    let mut count = 0;
+    for item in &items {
+        if !crate::eval::call_value(&args[1], vec![item.clone()], env).await?.as_bool() {
+            break;
+        }
+        count += 1;
+    }
+    Ok((items, count))
+}
+
+builtin!(take_while_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (mut items, count) = leading_matches("take_while", &args, env).await?;
+        items.truncate(count);
+        Ok(Value::Array(Rc::new(ArrayCell::new(items))))
+    })
+});
+
+builtin!(drop_while_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (mut items, count) = leading_matches("drop_while", &args, env).await?;
+        items.drain(..count);
+        Ok(Value::Array(Rc::new(ArrayCell::new(items))))
+    })
+});
+
+/// Elements of `args[0]` for a predicate builtin called as `name(array, predicate)`.
+fn predicate_items(name: &str, args: &[Value]) -> Result<Vec<Value>, String> {
+    if args.len() != 2 {
+        return Err(format!("{} expects 2 arguments: array, predicate", name));
+    }
+    Ok(array_arg(name, &args[0])?.borrow().clone())
+}
+
+builtin!(count_if_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let mut count = 0;
+        for item in predicate_items("count_if", &args)? {
+            if crate::eval::call_value(&args[1], vec![item], env).await?.as_bool() {
+                count += 1;
+            }
+        }
+        Ok(Value::Int(count))
+    })
+});
+
+// any/all stop at the first element that decides the result; any([]) is false, all([]) is true
+builtin!(any_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        for item in predicate_items("any", &args)? {
+            if crate::eval::call_value(&args[1], vec![item], env).await?.as_bool() {
+                return Ok(Value::Boolean(true));
+            }
+        }
+        Ok(Value::Boolean(false))
+    })
+});
+
+builtin!(all_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        for item in predicate_items("all", &args)? {
+            if !crate::eval::call_value(&args[1], vec![item], env).await?.as_bool() {
+                return Ok(Value::Boolean(false));
+            }
+        }
+        Ok(Value::Boolean(true))
+    })
+});
+
+// find_index(arr, value): first index whose element is deep_equal to value, or -1
+builtin!(find_index_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("find_index expects 2 arguments: array, value".to_string());
+        }
+        let arr = array_arg("find_index", &args[0])?.borrow();
+        let index = arr.iter().position(|item| item.deep_eq(&args[1]));
+        Ok(Value::Int(index.map_or(-1, |i| i as i64)))
+    })
+});
+
+// find_index_by(arr, predicate): first index where the predicate is truthy, or -1
+builtin!(find_index_by_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        for (i, item) in predicate_items("find_index_by", &args)?.into_iter().enumerate() {
+            if crate::eval::call_value(&args[1], vec![item], env).await?.as_bool() {
+                return Ok(Value::Int(i as i64));
+            }
+        }
+        Ok(Value::Int(-1))
+    })
+});
+
+/// Elements and initial accumulator for `name(array, fn, init)`.
+fn fold_args(name: &str, args: &[Value]) -> Result<(Vec<Value>, Value), String> {
+    if args.len() != 3 {
+        return Err(format!("{} expects 3 arguments: array, function, initial value", name));
+    }
+    Ok((array_arg(name, &args[0])?.borrow().clone(), args[2].clone()))
+}
+
+// reduce_right(arr, fn, init): fn(acc, item) applied from the last element to the first
+builtin!(reduce_right_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (items, mut acc) = fold_args("reduce_right", &args)?;
+        for item in items.into_iter().rev() {
+            acc = crate::eval::call_value(&args[1], vec![acc, item], env).await?;
+        }
+        Ok(acc)
+    })
+});
+
+// scan(arr, fn, init): every intermediate fn(acc, item), e.g. prefix sums;
+// the initial value itself is not included
+builtin!(scan_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (items, mut acc) = fold_args("scan", &args)?;
+        let mut steps = Vec::with_capacity(items.len());
+        for item in items {
+            acc = crate::eval::call_value(&args[1], vec![acc, item], env).await?;
+            steps.push(acc.clone());
+        }
+        Ok(Value::Array(Rc::new(ArrayCell::new(steps))))
+    })
+});
+
+// Cache key for memoize: only plain data (numbers, strings, booleans, null and
+// arrays/maps of those) is accepted, because it stringifies by value.
+fn memo_key(args: &[Value]) -> Result<String, String> {
+    let mut key = String::new();
+    for arg in args {
+        push_memo_key(&mut key, arg)?;
+    }
+    Ok(key)
+}
+
+// Type tag plus value, recursing into arrays and maps; strings and containers
+// are length-prefixed, so `array("1")` and `array(1)` or `"a,b"` and two
+// arguments never share a key.
+fn push_memo_key(key: &mut String, arg: &Value) -> Result<(), String> {
+    match arg {
+        Value::Null => key.push('n'),
+        Value::Boolean(b) => key.push_str(if *b { "t" } else { "f" }),
+        Value::Int(n) => key.push_str(&format!("i{};", n)),
+        Value::Number(x) => key.push_str(&format!("d{:x};", x.to_bits())),
+        Value::String(s) => key.push_str(&format!("s{}:{}", s.len(), s)),
+        Value::Array(arr) => {
+            let arr = arr.borrow();
+            key.push_str(&format!("a{}:", arr.len()));
+            for item in arr.iter() {
+                push_memo_key(key, item)?;
+            }
+        }
+        Value::Map(map) => {
+            let map = map.borrow();
+            key.push_str(&format!("m{}:", map.len()));
+            for (k, v) in map.iter() {
+                key.push_str(&format!("{}:{}", k.len(), k));
+                push_memo_key(key, v)?;
+            }
+        }
+        _ => return Err(format!("memoize: unsupported argument type {}", arg.type_name())),
+    }
+    Ok(())
+}
+
+builtin!(memoize_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("memoize expects 1 argument (function)".to_string());
+        }
+        let func = args[0].clone();
+        if !matches!(func, Value::Function(_) | Value::Builtin(_)) {
+            return Err("memoize: argument must be a function".to_string());
+        }
+        let cache: Rc<RefCell<HashMap<String, Value>>> = Rc::new(RefCell::new(HashMap::new()));
+        let wrapper: BuiltinFn = Rc::new(move |call_args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+            let func = func.clone();
+            let cache = Rc::clone(&cache);
+            Box::pin(async move {
+                let key = memo_key(&call_args)?;
+                if let Some(val) = cache.borrow().get(&key) {
+                    return Ok(val.clone());
+                }
+                let result = crate::eval::call_value(&func, call_args, env).await?;
+                cache.borrow_mut().insert(key, result.clone());
+                Ok(result)
+            })
+        });
+        Ok(Value::Builtin(wrapper))
+    })
+});
+
+// once(fn): a function that calls fn on its first call and then returns that
+// result on every call, whatever the arguments. A call that fails is not cached.
+builtin!(once_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("once expects 1 argument (function)".to_string());
+        }
+        let func = args[0].clone();
+        if !matches!(func, Value::Function(_) | Value::Builtin(_) | Value::Method(..)) {
+            return Err("once: argument must be a function".to_string());
+        }
+        let result: Rc<RefCell<Option<Value>>> = Rc::new(RefCell::new(None));
+        let wrapper: BuiltinFn = Rc::new(move |call_args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+            let func = func.clone();
+            let result = Rc::clone(&result);
+            Box::pin(async move {
+                if let Some(val) = result.borrow().as_ref() {
+                    return Ok(val.clone());
+                }
+                let val = crate::eval::call_value(&func, call_args, env).await?;
+                *result.borrow_mut() = Some(val.clone());
+                Ok(val)
+            })
+        });
+        Ok(Value::Builtin(wrapper))
+    })
+});
+
+// retry(fn, attempts, delay_ms, backoff = 1): call fn() until it succeeds, at
+// most `attempts` times, sleeping between tries; each delay is multiplied by
+// `backoff` (2 for exponential backoff). The last error is returned as is.
+builtin!(retry_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() < 3 || args.len() > 4 {
+            return Err("retry expects 3 or 4 arguments: function, attempts, delay_ms, backoff".to_string());
+        }
+        let attempts = match args[1].as_i64() {
+            Some(n) if n > 0 => n,
+            _ => return Err("retry: attempts must be a positive number".to_string()),
+        };
+        let mut delay = match args[2].as_f64() {
+            Some(ms) if ms >= 0.0 => ms,
+            _ => return Err("retry: delay_ms must be a non-negative number".to_string()),
+        };
+        let backoff = match args.get(3).map(Value::as_f64) {
+            None => 1.0,
+            Some(Some(b)) if b >= 1.0 => b,
+            Some(_) => return Err("retry: backoff must be a number >= 1".to_string()),
+        };
+        let mut attempt = 1;
+        loop {
+            match crate::eval::call_value(&args[0], Vec::new(), env).await {
+                Ok(val) => return Ok(val),
+                Err(e) if attempt >= attempts => return Err(e),
+                Err(_) => {}
+            }
+            time::sleep(Duration::try_from_secs_f64(delay / 1000.0).unwrap_or(Duration::MAX)).await;
+            delay *= backoff;
+            attempt += 1;
+        }
+    })
+});
+
+// A rate limiter is an ordinary map {interval_ms, last_ms} owned by the script, so
+// it is freed with the last reference to it. last_ms counts from RATE_EPOCH.
+lazy_static! {
+    static ref RATE_EPOCH: std::time::Instant = std::time::Instant::now();
+}
+
+// rate_limiter(interval_ms) -> limiter for rate_wait
+builtin!(rate_limiter_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("rate_limiter expects 1 argument (interval_ms)".to_string());
+        }
+        let interval = match args[0].as_f64() {
+            Some(ms) if ms >= 0.0 && ms.is_finite() => ms,
+            _ => return Err("rate_limiter: interval_ms must be a non-negative number".to_string()),
+        };
+        let mut limiter = MapValue::new();
+        limiter.insert("interval_ms".to_string(), Value::Number(interval));
+        limiter.insert("last_ms".to_string(), Value::Null);
+        Ok(Value::Map(Rc::new(RefCell::new(limiter))))
+    })
+});
+
+// rate_wait(limiter): sleep until at least interval_ms have passed since the
+// previous rate_wait on the same limiter (the first call returns at once)
+builtin!(rate_wait_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let limiter = match args.as_slice() {
+            [Value::Map(map)] => Rc::clone(map),
+            [_] => return Err("rate_wait: argument must be a rate limiter".to_string()),
+            _ => return Err("rate_wait expects 1 argument (rate limiter)".to_string()),
+        };
+        let (interval, last) = {
+            let limiter = limiter.borrow();
+            match (limiter.get("interval_ms").and_then(Value::as_f64), limiter.get("last_ms")) {
+                (Some(interval), Some(last)) => (interval, last.as_f64()),
+                _ => return Err("rate_wait: argument must be a rate limiter".to_string()),
+            }
+        };
+        if let Some(last) = last {
+            let due = Duration::try_from_secs_f64((last + interval) / 1000.0).unwrap_or(Duration::MAX);
+            time::sleep(due.saturating_sub(RATE_EPOCH.elapsed())).await;
+        }
+        let now = RATE_EPOCH.elapsed().as_secs_f64() * 1000.0;
+        limiter.borrow_mut().insert("last_ms".to_string(), Value::Number(now));
+        Ok(Value::Null)
+    })
+});
+
+// benchmark(fn, iterations): call fn() repeatedly and report wall-clock times
+// in milliseconds as a map {total_ms, avg_ms, min_ms, max_ms}.
+builtin!(benchmark_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("benchmark expects 2 arguments: function, iterations".to_string());
+        }
+        let iterations = match args[1].as_i64() {
+            Some(n) if n > 0 => n as u64,
+            _ => return Err("benchmark: iterations must be a positive number".to_string()),
+        };
+        let (mut total, mut min, mut max) = (0.0, f64::INFINITY, 0.0f64);
+        for _ in 0..iterations {
+            let start = std::time::Instant::now();
+            crate::eval::call_value(&args[0], Vec::new(), env).await?;
+            let ms = start.elapsed().as_secs_f64() * 1000.0;
+            total += ms;
+            min = min.min(ms);
+            max = max.max(ms);
+        }
+        let mut stats = MapValue::ordered();
+        stats.insert("total_ms".to_string(), Value::Number(total));
+        stats.insert("avg_ms".to_string(), Value::Number(total / iterations as f64));
+        stats.insert("min_ms".to_string(), Value::Number(min));
+        stats.insert("max_ms".to_string(), Value::Number(max));
+        Ok(Value::Map(Rc::new(RefCell::new(stats))))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Durations: format_duration(3723) -> "1h 2m 3s", parse_duration reverses it
+// -----------------------------------------------------------------------------
+
+const DURATION_UNITS: [(&str, f64); 5] = [("d", 86400.0), ("h", 3600.0), ("m", 60.0), ("s", 1.0), ("ms", 0.001)];
+
+/// Up to three decimals without trailing zeros: 1.5 -> "1.5", 2.0 -> "2".
+fn trim_decimals(x: f64) -> String {
+    let s = format!("{:.3}", x);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+builtin!(format_duration_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("format_duration expects 1 argument (seconds)".to_string());
+        }
+        let total = match args[0].as_f64() {
+            Some(t) if t.is_finite() && t >= 0.0 => t,
+            _ => return Err("format_duration: seconds must be a non-negative number".to_string()),
+        };
+        if total == 0.0 {
+            return Ok(Value::String("0s".to_string()));
+        }
+        // 0.9999996 с округляется до целой секунды
+        if (total * 1e6).round() < 1e6 {
+            return Ok(Value::String(format!("{}ms", trim_decimals(total * 1000.0))));
+        }
+        // сначала округляем до миллисекунд, потом раскладываем по единицам,
+        // чтобы перенос дошёл до минут: 59.9996 -> "1m", а не "60s"
+        let mut rest = (total * 1000.0).round();
+        let mut parts = Vec::new();
+        for (unit, size) in &DURATION_UNITS[..3] {
+            let size = size * 1000.0;
+            let n = (rest / size).floor();
+            rest -= n * size;
+            if n > 0.0 {
+                parts.push(format!("{}{}", n, unit));
+            }
+        }
+        if rest > 0.0 {
+            parts.push(format!("{}s", trim_decimals(rest / 1000.0)));
+        }
+        Ok(Value::String(parts.join(" ")))
+    })
+});
+
+// parse_duration("1h 2m 3s") -> 3723; units d, h, m, s, ms, fractions allowed
+builtin!(parse_duration_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let text = match args.as_slice() {
+            [Value::String(s)] => s,
+            [_] => return Err("parse_duration: argument must be string".to_string()),
+            _ => return Err("parse_duration expects 1 argument (string)".to_string()),
+        };
+        let mut total = 0.0;
+        let mut parts = text.split_whitespace().peekable();
+        if parts.peek().is_none() {
+            return Err("parse_duration: empty duration".to_string());
+        }
+        for part in parts {
+            let split = part.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(part.len());
+            let (number, unit) = part.split_at(split);
+            let size = DURATION_UNITS
+                .iter()
+                .find(|(u, _)| *u == unit)
+                .map(|(_, size)| *size)
+                .ok_or_else(|| format!("parse_duration: unknown unit in '{}' (expected d, h, m, s or ms)", part))?;
+            let n: f64 = number.parse().map_err(|_| format!("parse_duration: invalid number in '{}'", part))?;
+            total += n * size;
+        }
+        if total.fract() == 0.0 && total.abs() < i64::MAX as f64 {
+            Ok(Value::Int(total as i64))
+        } else {
+            Ok(Value::Number(total))
+        }
+    })
+});
+
+builtin!(file_exists_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("file_exists expects 1 argument".to_string());
+        }
+        let path = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("file_exists argument must be string".to_string()),
+        };
+        Ok(Value::Boolean(Path::new(path).exists()))
+    })
+});
+
+/// Modification time of `path` in seconds since the Unix epoch, `None` if it does not exist.
+fn mtime_of(path: &str) -> Result<Option<f64>, String> {
+    let meta = match fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to stat '{}': {}", path, e)),
+    };
+    let modified = meta.modified().map_err(|e| format!("Failed to read mtime of '{}': {}", path, e))?;
+    let secs = match modified.duration_since(std::time::UNIX_EPOCH) {
+        Ok(d) => d.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    };
+    Ok(Some(secs))
+}
+
+builtin!(file_mtime_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let path = match args.as_slice() {
+            [Value::String(s)] => s,
+            [_] => return Err("file_mtime: path must be string".to_string()),
+            _ => return Err("file_mtime expects 1 argument".to_string()),
+        };
+        match mtime_of(path)? {
+            Some(secs) => Ok(Value::Number(secs)),
+            None => Err(format!("file_mtime: '{}' does not exist", path)),
+        }
+    })
+});
+
+// wait_for_change(path, poll_ms): sleep until the mtime of `path` changes or the
+// file appears/disappears; returns the new mtime, or null if the file was deleted.
+builtin!(wait_for_change_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (path, poll_ms) = match args.as_slice() {
+            [Value::String(s), ms] => match ms.as_i64() {
+                Some(ms) if ms > 0 => (s, ms as u64),
+                _ => return Err("wait_for_change: poll_ms must be a positive number".to_string()),
+            },
+            [_, _] => return Err("wait_for_change: path must be string".to_string()),
+            _ => return Err("wait_for_change expects 2 arguments: path, poll_ms".to_string()),
+        };
+        let initial = mtime_of(path)?;
+        loop {
+            time::sleep(Duration::from_millis(poll_ms)).await;
+            let current = mtime_of(path)?;
+            if current != initial {
+                return Ok(current.map(Value::Number).unwrap_or(Value::Null));
+            }
+        }
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Path manipulation (std::path, no filesystem access)
+// -----------------------------------------------------------------------------
+
+fn path_arg<'a>(name: &str, args: &'a [Value]) -> Result<&'a Path, String> {
+    match args {
+        [Value::String(s)] => Ok(Path::new(s)),
+        [_] => Err(format!("{}: path must be string", name)),
+        _ => Err(format!("{} expects 1 argument", name)),
+    }
+}
+
+fn path_string(path: &Path) -> Value {
+    Value::String(path.to_string_lossy().into_owned())
+}
+
+// path_join("a", "b", "c.forge") -> "a/b/c.forge"; empty parts are skipped,
+// an absolute part replaces everything before it (as Path::join does).
+builtin!(path_join_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let mut path = std::path::PathBuf::new();
+        for arg in &args {
+            match arg {
+                Value::String(s) if s.is_empty() => {}
+                Value::String(s) => path.push(s),
+                other => return Err(format!("path_join: parts must be strings, got {}", other.type_name())),
+            }
+        }
+        Ok(path_string(&path))
+    })
+});
+
+// basename("a/b/c.forge") -> "c.forge"; a trailing separator is ignored ("a/b/" -> "b").
+builtin!(basename_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let path = path_arg("basename", &args)?;
+        Ok(Value::String(path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()))
+    })
+});
+
+// dirname("a/b/c.forge") -> "a/b"; "" for a bare file name, the root itself for "/".
+builtin!(dirname_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let path = path_arg("dirname", &args)?;
+        Ok(path_string(path.parent().unwrap_or(path)))
+    })
+});
+
+// extension("a/b/c.forge") -> "forge"; "" when there is none (including ".hidden").
+builtin!(extension_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let path = path_arg("extension", &args)?;
+        Ok(Value::String(path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_default()))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Shell-style globs: `*`, `?`, `[abc]`, `[a-z]`, `[!abc]`
+// -----------------------------------------------------------------------------
+
+enum GlobToken {
+    Char(char),
+    Any,
+    Star,
+    Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+fn is_path_separator(c: char) -> bool {
+    c == '/' || c == std::path::MAIN_SEPARATOR
+}
+
+fn parse_glob(pattern: &str) -> Result<Vec<GlobToken>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '*' => GlobToken::Star,
+            '?' => GlobToken::Any,
+            '[' => {
+                let negated = chars.next_if(|&c| c == '!' || c == '^').is_some();
+                let mut ranges = Vec::new();
+                // ']' сразу после '[' -- обычный символ, как в шелле
+                let mut first = true;
+                loop {
+                    let c = match chars.next() {
+                        Some(']') if !first => break,
+                        Some(c) => c,
+                        None => return Err(format!("Unclosed '[' in glob pattern '{}'", pattern)),
+                    };
+                    first = false;
+                    let end = match chars.peek() {
+                        Some('-') => {
+                            chars.next();
+                            match chars.next() {
+                                Some(']') => {
+                                    ranges.push((c, c));
+                                    ranges.push(('-', '-'));
+                                    break;
+                                }
+                                Some(end) => end,
+                                None => return Err(format!("Unclosed '[' in glob pattern '{}'", pattern)),
+                            }
+                        }
+                        _ => c,
+                    };
+                    ranges.push((c, end));
+                }
+                GlobToken::Class { negated, ranges }
+            }
+            c => GlobToken::Char(c),
+        });
+    }
+    Ok(tokens)
+}
+
+/// Match `text` against parsed glob tokens. `*` and `?` never match a path
+/// separator, so `*.forge` does not match `dir/a.forge`.
+fn glob_matches(tokens: &[GlobToken], text: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let matches_one = |token: &GlobToken, c: char| match token {
+        GlobToken::Char(p) => *p == c,
+        GlobToken::Any => !is_path_separator(c),
+        GlobToken::Class { negated, ranges } => ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated,
+        GlobToken::Star => false,
+    };
+    let (mut p, mut t) = (0, 0);
+    // Последняя встреченная '*' и позиция в тексте, с которой она сейчас сопоставлена
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(GlobToken::Star) => {
+                star = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some(token) if matches_one(token, text[t]) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match star {
+            Some((sp, st)) if !is_path_separator(text[st]) => {
+                star = Some((sp, st + 1));
+                p = sp + 1;
+                t = st + 1;
+            }
+            _ => return false,
+        }
+    }
+    tokens[p..].iter().all(|t| matches!(t, GlobToken::Star))
+}
+
+builtin!(glob_match_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        match args.as_slice() {
+            [Value::String(pattern), Value::String(text)] => Ok(Value::Boolean(glob_matches(&parse_glob(pattern)?, text))),
+            [_, _] => Err("glob_match: pattern and text must be strings".to_string()),
+            _ => Err("glob_match expects 2 arguments: pattern, text".to_string()),
+        }
+    })
+});
+
+// glob_dir(pattern): sorted paths matching `pattern`, component by component.
+// Hidden entries (starting with '.') only match a component that starts with '.'.
+builtin!(glob_dir_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let pattern = match args.as_slice() {
+            [Value::String(p)] => p,
+            [_] => return Err("glob_dir: pattern must be string".to_string()),
+            _ => return Err("glob_dir expects 1 argument".to_string()),
+        };
+        let is_wild = |s: &str| s.contains(['*', '?', '[']);
+        // Пути-кандидаты: (путь для чтения каталога, путь для результата)
+        let mut paths: Vec<(std::path::PathBuf, String)> = if pattern.starts_with('/') {
+            vec![(std::path::PathBuf::from("/"), "/".to_string())]
+        } else {
+            vec![(std::path::PathBuf::from("."), String::new())]
+        };
+        let components: Vec<&str> = pattern.split(is_path_separator).filter(|c| !c.is_empty()).collect();
+        for (i, component) in components.iter().enumerate() {
+            let last = i + 1 == components.len();
+            let mut next = Vec::new();
+            for (dir, shown) in &paths {
+                let join = |name: &str| if shown.is_empty() || shown.ends_with('/') { format!("{}{}", shown, name) } else { format!("{}/{}", shown, name) };
+                if !is_wild(component) {
+                    let path = dir.join(component);
+                    if (last && path.exists()) || path.is_dir() {
+                        next.push((path, join(component)));
+                    }
+                    continue;
+                }
+                let tokens = parse_glob(component)?;
+                let entries = match fs::read_dir(dir) {
+                    Ok(entries) => entries,
+                    Err(_) => continue,
+                };
+                for entry in entries.flatten() {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    if name.starts_with('.') && !component.starts_with('.') {
+                        continue;
+                    }
+                    let path = entry.path();
+                    if glob_matches(&tokens, &name) && (last || path.is_dir()) {
+                        next.push((path, join(&name)));
+                    }
+                }
+            }
+            paths = next;
+        }
+        let mut found: Vec<String> = paths.into_iter().map(|(_, shown)| shown).filter(|s| !s.is_empty()).collect();
+        found.sort();
+        Ok(Value::Array(Rc::new(ArrayCell::new(found.into_iter().map(Value::String).collect()))))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Process environment (env_get, load_env)
+// -----------------------------------------------------------------------------
+
+/// Parse one `KEY=value` line of an env file; `None` for blanks and `#` comments.
+/// Values may be wrapped in single or double quotes, `export KEY=...` is accepted.
+fn parse_env_line(line: &str) -> Option<Result<(String, String), String>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, value) = match line.split_once('=') {
+        Some(kv) => kv,
+        None => return Some(Err(format!("expected KEY=value, got '{}'", line))),
+    };
+    let key = key.trim();
+    if key.is_empty() {
+        return Some(Err(format!("missing key in '{}'", line)));
+    }
+    let value = value.trim();
+    let unquoted = ['"', '\'']
+        .iter()
+        .find_map(|&q| value.strip_prefix(q).and_then(|v| v.strip_suffix(q)))
+        .unwrap_or(value);
+    Some(Ok((key.to_string(), unquoted.to_string())))
+}
+
+builtin!(load_env_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("load_env expects 1 argument".to_string());
+        }
+        let path = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("load_env argument must be string".to_string()),
+        };
+        let content = fs::read_to_string(path).map_err(|e| format!("load_env: cannot read '{}': {}", path, e))?;
+        let mut count = 0;
+        for (i, line) in content.lines().enumerate() {
+            if let Some(entry) = parse_env_line(line) {
+                let (key, value) = entry.map_err(|e| format!("load_env: {}:{}: {}", path, i + 1, e))?;
+                std::env::set_var(key, value);
+                count += 1;
+            }
+        }
+        Ok(Value::Int(count))
+    })
+});
+
+builtin!(env_get_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.is_empty() || args.len() > 2 {
+            return Err("env_get expects 1 or 2 arguments: name, default".to_string());
+        }
+        let name = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("env_get: name must be string".to_string()),
+        };
+        match std::env::var(name) {
+            Ok(value) => Ok(Value::String(value)),
+            Err(_) => Ok(args.get(1).cloned().unwrap_or(Value::Null)),
+        }
+    })
+});
+
+builtin!(mem_read_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("mem_read expects 1 argument".to_string());
+        }
+        let addr = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("mem_read: address must not be negative, got {}", n)),
+            None => return Err("mem_read argument must be number".to_string()),
+        };
+        match env.mem_read(addr) {
+            Ok(byte) => Ok(Value::Int(byte as i64)),
+            Err(e) => Err(e),
+        }
+    })
+});
+
+builtin!(mem_write_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("mem_write expects 2 arguments".to_string());
+        }
+        let addr = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("mem_write: address must not be negative, got {}", n)),
+            None => return Err("mem_write first argument must be number".to_string()),
+        };
+        let value = match args[1].as_i64() {
+            Some(n) => n as u8,
+            None => return Err("mem_write second argument must be number".to_string()),
+        };
+        match env.mem_write(addr, value) {
+            Ok(()) => Ok(Value::Null),
+            Err(e) => Err(e),
+        }
+    })
+});
+
+builtin!(get_reg_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("get_reg expects 1 argument".to_string());
+        }
+        let name = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("get_reg argument must be string".to_string()),
+        };
+        match env.get_reg(name) {
+            Some(val) => Ok(Value::Int(val)),
+            None => Err(format!("Register '{}' not defined", name)),
+        }
+    })
+});
+
+builtin!(set_reg_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("set_reg expects 2 arguments".to_string());
+        }
+        let name = match &args[0] {
+            Value::String(s) => s.clone(),
+            _ => return Err("set_reg first argument must be string".to_string()),
+        };
+        let value = match args[1].as_i64() {
+            Some(n) => n,
+            None => return Err("set_reg second argument must be number".to_string()),
+        };
+        env.set_reg(name, value);
+        Ok(Value::Null)
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Cooperative tasks (spawn, join_task)
+//
+// Tasks run on the interpreter's single thread via `spawn_local` and switch only
+// at await points (`sleep`, `join_task`, `yield_now`), so there are no data races: arrays and maps
+// passed to a task are the same `Rc<RefCell>` and mutations are visible to all.
+// The task gets a snapshot of the caller's environment, like a function call.
+// -----------------------------------------------------------------------------
+
+thread_local! {
+    static TASKS: RefCell<HashMap<i64, tokio::task::JoinHandle<Result<Value, String>>>> = RefCell::new(HashMap::new());
+    static NEXT_TASK: std::cell::Cell<i64> = const { std::cell::Cell::new(1) };
+}
+
+builtin!(spawn_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let mut args = args.into_iter();
+        let callee = args.next().ok_or("spawn expects a function and its arguments")?;
+        if !matches!(callee, Value::Function(_) | Value::Builtin(_) | Value::Method(..) | Value::Class { .. }) {
+            return Err(format!("spawn: {} is not callable", callee.type_name()));
+        }
+        let call_args: Vec<Value> = args.collect();
+        let mut task_env = env.clone();
+        let handle = tokio::task::spawn_local(async move {
+            crate::eval::call_value(&callee, call_args, &mut task_env).await
+        });
+        let id = NEXT_TASK.with(|next| {
+            let id = next.get();
+            next.set(id + 1);
+            id
+        });
+        TASKS.with(|tasks| tasks.borrow_mut().insert(id, handle));
+        Ok(Value::Int(id))
+    })
+});
+
+builtin!(join_task_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("join_task expects 1 argument (task id)".to_string());
+        }
+        let id = args[0].as_i64().ok_or("join_task: argument must be a task id")?;
+        let handle = TASKS
+            .with(|tasks| tasks.borrow_mut().remove(&id))
+            .ok_or_else(|| format!("join_task: unknown or already joined task {}", id))?;
+        handle.await.map_err(|e| format!("join_task: task {} failed: {}", id, e))?
+    })
+});
+
+// Give other tasks a turn without sleeping, for long loops that never await.
+builtin!(yield_now_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if !args.is_empty() {
+            return Err("yield_now expects no arguments".to_string());
+        }
+        tokio::task::yield_now().await;
+        Ok(Value::Null)
+    })
+});
+
+// -----------------------------------------------------------------------------
+// DLL-related builtins (with 64‑bit support)
+// -----------------------------------------------------------------------------
+
+builtin!(dll_load_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("dll_load expects 1 argument".to_string());
+        }
+        let path = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("dll_load argument must be string".to_string()),
+        };
+        unsafe {
+            match Library::new(path) {
+                Ok(lib) => Ok(Value::Dll(Rc::new(lib))),
+                Err(e) => Err(format!("Failed to load DLL: {}", e)),
+            }
+        }
+    })
+});
+
+/// Argument count covered by the hand-written `dll_call` dispatch.
+#[cfg(not(feature = "libffi"))]
+const MAX_DLL_ARGS: usize = 12;
+
+builtin!(dll_call_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() < 2 {
+            return Err("dll_call expects at least 2 arguments".to_string());
         }
         let lib = match &args[0] {
             Value::Dll(lib) => lib,
@@ -492,6 +2775,7 @@ builtin!(dll_call_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result
         for arg in args.iter().skip(2) {
             match arg {
                 Value::Number(n) => c_args.push(*n as i64),   // use i64 for 64‑bit compatibility
+                Value::Int(n) => c_args.push(*n),
                 Value::String(s) => {
                     let mut bytes = s.as_bytes().to_vec();
                     bytes.push(0);
@@ -504,6 +2788,14 @@ builtin!(dll_call_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result
             }
         }
 
+        // With libffi the call frame is built at run time, so any argument count works
+        #[cfg(feature = "libffi")]
+        unsafe {
+            let params = vec![CType::LongLong; c_args.len()];
+            let values: Vec<Value> = c_args.iter().map(|a| Value::Int(*a)).collect();
+            crate::ffi::call(lib, func_name, CType::LongLong, &params, &values)
+        }
+        #[cfg(not(feature = "libffi"))]
         unsafe {
             // Dispatch based on argument count – we support up to 12 arguments.
             // The return type is i64 (to hold pointers or 64‑bit integers).
@@ -511,216 +2803,861 @@ builtin!(dll_call_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result
                 0 => {
                     let func: libloading::Symbol<unsafe extern "C" fn() -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func() as f64))
+                    Ok(Value::Int(func()))
                 }
                 1 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0]) as f64))
+                    Ok(Value::Int(func(c_args[0])))
                 }
                 2 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1])))
                 }
                 3 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1], c_args[2]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1], c_args[2])))
                 }
                 4 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64, i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1], c_args[2], c_args[3]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1], c_args[2], c_args[3])))
                 }
                 5 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64, i64, i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4])))
                 }
                 6 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64, i64, i64, i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5])))
                 }
                 7 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64, i64, i64, i64, i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6])))
                 }
                 8 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64, i64, i64, i64, i64, i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6], c_args[7]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6], c_args[7])))
                 }
                 9 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64, i64, i64, i64, i64, i64, i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6], c_args[7], c_args[8]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6], c_args[7], c_args[8])))
                 }
                 10 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64, i64, i64, i64, i64, i64, i64, i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6], c_args[7], c_args[8], c_args[9]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6], c_args[7], c_args[8], c_args[9])))
                 }
                 11 => {
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64, i64, i64, i64, i64, i64, i64, i64, i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6], c_args[7], c_args[8], c_args[9], c_args[10]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6], c_args[7], c_args[8], c_args[9], c_args[10])))
                 }
                 12 => {
+                    #[allow(clippy::type_complexity)]
                     let func: libloading::Symbol<unsafe extern "C" fn(i64, i64, i64, i64, i64, i64, i64, i64, i64, i64, i64, i64) -> i64> = lib.get(func_name.as_bytes())
                         .map_err(|e| format!("Failed to get function '{}': {}", func_name, e))?;
-                    Ok(Value::Number(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6], c_args[7], c_args[8], c_args[9], c_args[10], c_args[11]) as f64))
+                    Ok(Value::Int(func(c_args[0], c_args[1], c_args[2], c_args[3], c_args[4], c_args[5], c_args[6], c_args[7], c_args[8], c_args[9], c_args[10], c_args[11])))
                 }
-                _ => Err("Too many arguments for DLL call".to_string()),
+                n => Err(format!("dll_call: {} arguments given, at most {} are supported (build with --features libffi for more)", n, MAX_DLL_ARGS)),
+            }
+        }
+    })
+});
+
+builtin!(dll_call_typed_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() < 3 {
+            return Err("dll_call_typed expects at least 3 arguments: dll, name, signature".to_string());
+        }
+        let lib = match &args[0] {
+            Value::Dll(lib) => lib,
+            _ => return Err("dll_call_typed first argument must be a DLL handle".to_string()),
+        };
+        let (func_name, sig) = match (&args[1], &args[2]) {
+            (Value::String(name), Value::String(sig)) => (name, sig),
+            _ => return Err("dll_call_typed: name and signature must be strings".to_string()),
+        };
+        #[cfg(feature = "libffi")]
+        {
+            let (ret, params) = crate::ffi::parse_signature(sig)?;
+            unsafe { crate::ffi::call(lib, func_name, ret, &params, &args[3..]) }
+        }
+        #[cfg(not(feature = "libffi"))]
+        {
+            let _ = (lib, func_name, sig);
+            Err("dll_call_typed requires building with --features libffi".to_string())
+        }
+    })
+});
+
+builtin!(dll_free_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("dll_free expects 1 argument".to_string());
+        }
+        match &args[0] {
+            Value::Dll(_) => Ok(Value::Null),
+            _ => Err("dll_free argument must be a DLL handle".to_string()),
+        }
+    })
+});
+
+// Layout helpers for marshaling C structs into heap blocks.
+builtin!(sizeof_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        match args.as_slice() {
+            [Value::String(name)] => match CType::parse(name) {
+                Ok(ty) => ty.size().map(|n| Value::Int(n as i64)).ok_or_else(|| "sizeof: void has no size".to_string()),
+                Err(e) => Err(format!("sizeof: {}", e)),
+            },
+            _ => Err("sizeof expects 1 argument (type name)".to_string()),
+        }
+    })
+});
+
+// struct_offset(array(4, 8, 2), 1) == 8: each field is aligned to its own size,
+// as C does for primitive members.
+builtin!(struct_offset_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (sizes, index) = match args.as_slice() {
+            [Value::Array(sizes), index] => (sizes.borrow().clone(), index.as_i64().ok_or("struct_offset: index must be number")?),
+            _ => return Err("struct_offset expects 2 arguments: field_sizes_array, index".to_string()),
+        };
+        if index < 0 || index as usize >= sizes.len() {
+            return Err("struct_offset: index out of bounds".to_string());
+        }
+        let mut offset = 0usize;
+        for (i, size) in sizes.iter().enumerate() {
+            let size = match size.as_i64() {
+                Some(n) if n > 0 => n as usize,
+                _ => return Err("struct_offset: field sizes must be positive numbers".to_string()),
+            };
+            offset = offset.next_multiple_of(size);
+            if i == index as usize {
+                break;
+            }
+            offset += size;
+        }
+        Ok(Value::Int(offset as i64))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Memory management builtins (malloc, free, poke, peek, peek32, poke64, peek64)
+// -----------------------------------------------------------------------------
+
+lazy_static! {
+    static ref HEAP: Mutex<HashMap<usize, Vec<u8>>> = Mutex::new(HashMap::new());
+    static ref NEXT_PTR: Mutex<usize> = Mutex::new(1);
+}
+
+builtin!(malloc_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("malloc expects 1 argument (size)".to_string());
+        }
+        let size = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("malloc: size must not be negative, got {}", n)),
+            None => return Err("malloc argument must be number".to_string()),
+        };
+        let mut block = Vec::new();
+        block
+            .try_reserve_exact(size)
+            .map_err(|_| format!("malloc: cannot allocate {} bytes", size))?;
+        block.resize(size, 0);
+        let mut heap = HEAP.lock().unwrap();
+        let mut next = NEXT_PTR.lock().unwrap();
+        let ptr = *next;
+        *next += 1;
+        heap.insert(ptr, block);
+        Ok(Value::Int(ptr as i64))
+    })
+});
+
+builtin!(free_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("free expects 1 argument (ptr)".to_string());
+        }
+        let ptr = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("free: pointer must not be negative, got {}", n)),
+            None => return Err("free argument must be number".to_string()),
+        };
+        let mut heap = HEAP.lock().unwrap();
+        heap.remove(&ptr);
+        Ok(Value::Null)
+    })
+});
+
+builtin!(poke_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 3 {
+            return Err("poke expects 3 arguments: ptr, offset, value".to_string());
+        }
+        let ptr = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("poke: pointer must not be negative, got {}", n)),
+            None => return Err("poke first argument must be number".to_string()),
+        };
+        let offset = match args[1].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("poke: offset must not be negative, got {}", n)),
+            None => return Err("poke second argument must be number".to_string()),
+        };
+        let value = match args[2].as_i64() {
+            Some(n) => n as u8,
+            None => return Err("poke third argument must be number (byte)".to_string()),
+        };
+        let mut heap = HEAP.lock().unwrap();
+        let block = heap.get_mut(&ptr).ok_or("Invalid pointer")?;
+        if offset >= block.len() {
+            return Err("Offset out of bounds".to_string());
+        }
+        block[offset] = value;
+        Ok(Value::Null)
+    })
+});
+
+builtin!(peek_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("peek expects 2 arguments: ptr, offset".to_string());
+        }
+        let ptr = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("peek: pointer must not be negative, got {}", n)),
+            None => return Err("peek first argument must be number".to_string()),
+        };
+        let offset = match args[1].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("peek: offset must not be negative, got {}", n)),
+            None => return Err("peek second argument must be number".to_string()),
+        };
+        let heap = HEAP.lock().unwrap();
+        let block = heap.get(&ptr).ok_or("Invalid pointer")?;
+        if offset >= block.len() {
+            return Err("Offset out of bounds".to_string());
+        }
+        Ok(Value::Int(block[offset] as i64))
+    })
+});
+
+builtin!(peek32_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("peek32 expects 2 arguments: ptr, offset".to_string());
+        }
+        let ptr = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("peek32: pointer must not be negative, got {}", n)),
+            None => return Err("peek32 first argument must be number".to_string()),
+        };
+        let offset = match args[1].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("peek32: offset must not be negative, got {}", n)),
+            None => return Err("peek32 second argument must be number".to_string()),
+        };
+        let heap = HEAP.lock().unwrap();
+        let block = heap.get(&ptr).ok_or("Invalid pointer")?;
+        if offset + 3 >= block.len() {
+            return Err("Offset out of bounds for 4-byte read".to_string());
+        }
+        let val = (block[offset] as u32) |
+                 ((block[offset+1] as u32) << 8) |
+                 ((block[offset+2] as u32) << 16) |
+                 ((block[offset+3] as u32) << 24);
+        Ok(Value::Int(val as i64))
+    })
+});
+
+// 64-bit little-endian access, for storing pointers and addresses bit-exactly.
+builtin!(poke64_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 3 {
+            return Err("poke64 expects 3 arguments: ptr, offset, value".to_string());
+        }
+        let ptr = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("poke64: pointer must not be negative, got {}", n)),
+            None => return Err("poke64 first argument must be number".to_string()),
+        };
+        let offset = match args[1].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("poke64: offset must not be negative, got {}", n)),
+            None => return Err("poke64 second argument must be number".to_string()),
+        };
+        let value = match args[2].as_i64() {
+            Some(n) => n,
+            None => return Err("poke64 third argument must be number".to_string()),
+        };
+        let mut heap = HEAP.lock().unwrap();
+        let block = heap.get_mut(&ptr).ok_or("Invalid pointer")?;
+        if offset + 8 > block.len() {
+            return Err("Offset out of bounds for 8-byte write".to_string());
+        }
+        block[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        Ok(Value::Null)
+    })
+});
+
+builtin!(peek64_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("peek64 expects 2 arguments: ptr, offset".to_string());
+        }
+        let ptr = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("peek64: pointer must not be negative, got {}", n)),
+            None => return Err("peek64 first argument must be number".to_string()),
+        };
+        let offset = match args[1].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("peek64: offset must not be negative, got {}", n)),
+            None => return Err("peek64 second argument must be number".to_string()),
+        };
+        let heap = HEAP.lock().unwrap();
+        let block = heap.get(&ptr).ok_or("Invalid pointer")?;
+        if offset + 8 > block.len() {
+            return Err("Offset out of bounds for 8-byte read".to_string());
+        }
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&block[offset..offset + 8]);
+        Ok(Value::Int(i64::from_le_bytes(bytes)))
+    })
+});
+
+// Real address of a heap block, for passing it to DLL functions as an out-parameter:
+// `dll_call(lib, "GetWindowRect", hwnd, heap_ptr(rect))`, then `peek32(rect, 0)`.
+// The block never reallocates, so the address stays valid until `free`.
+builtin!(heap_ptr_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.is_empty() || args.len() > 2 {
+            return Err("heap_ptr expects 1 or 2 arguments: ptr[, offset]".to_string());
+        }
+        let ptr = match args[0].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            Some(n) => return Err(format!("heap_ptr: pointer must not be negative, got {}", n)),
+            None => return Err("heap_ptr first argument must be number".to_string()),
+        };
+        let offset = match args.get(1).map(|v| v.as_i64()) {
+            None => 0,
+            Some(Some(n)) if n >= 0 => n as usize,
+            Some(Some(n)) => return Err(format!("heap_ptr: offset must not be negative, got {}", n)),
+            Some(None) => return Err("heap_ptr second argument must be number".to_string()),
+        };
+        let mut heap = HEAP.lock().unwrap();
+        let block = heap.get_mut(&ptr).ok_or("Invalid pointer")?;
+        if offset >= block.len() {
+            return Err("Offset out of bounds".to_string());
+        }
+        Ok(Value::Int(block[offset..].as_mut_ptr() as usize as i64))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Window class registration (experimental, not fully implemented)
+// -----------------------------------------------------------------------------
+
+// This is a stub – full implementation requires async callback handling.
+builtin!(register_window_class_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("register_window_class expects 2 arguments: class_name, callback_function_name".to_string());
+        }
+        // Just a placeholder – real implementation would register a window class
+        // with a thunk that calls back into Forge.
+        Err("Window class registration is not yet implemented in this build".to_string())
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Numeric predicates (IEEE semantics: NaN != NaN, NaN comparisons are false)
+// -----------------------------------------------------------------------------
+
+fn float_arg(name: &str, args: &[Value]) -> Result<f64, String> {
+    if args.len() != 1 {
+        return Err(format!("{} expects 1 argument", name));
+    }
+    args[0].as_f64().ok_or_else(|| format!("{}: argument must be number", name))
+}
+
+builtin!(is_nan_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Boolean(float_arg("is_nan", &args)?.is_nan())) })
+});
+
+builtin!(is_finite_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Boolean(float_arg("is_finite", &args)?.is_finite())) })
+});
+
+builtin!(is_infinite_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Boolean(float_arg("is_infinite", &args)?.is_infinite())) })
+});
+
+// -----------------------------------------------------------------------------
+// Trigonometry (radians) and angle conversion
+// -----------------------------------------------------------------------------
+
+builtin!(sin_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Number(float_arg("sin", &args)?.sin())) })
+});
+
+builtin!(cos_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Number(float_arg("cos", &args)?.cos())) })
+});
+
+builtin!(tan_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Number(float_arg("tan", &args)?.tan())) })
+});
+
+builtin!(asin_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Number(float_arg("asin", &args)?.asin())) })
+});
+
+builtin!(acos_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Number(float_arg("acos", &args)?.acos())) })
+});
+
+builtin!(atan_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Number(float_arg("atan", &args)?.atan())) })
+});
+
+builtin!(to_radians_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Number(float_arg("to_radians", &args)?.to_radians())) })
+});
+
+builtin!(to_degrees_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { Ok(Value::Number(float_arg("to_degrees", &args)?.to_degrees())) })
+});
+
+// atan2(y, x): angle of the point (x, y) in (-pi, pi], correct in every quadrant.
+builtin!(atan2_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        match args.as_slice() {
+            [y, x] => match (y.as_f64(), x.as_f64()) {
+                (Some(y), Some(x)) => Ok(Value::Number(y.atan2(x))),
+                _ => Err("atan2: arguments must be numbers".to_string()),
+            },
+            _ => Err("atan2 expects 2 arguments: y, x".to_string()),
+        }
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Integer math (gcd, lcm, factorial, is_prime) on non-negative whole numbers
+// -----------------------------------------------------------------------------
+
+/// A non-negative integer argument; `4.0` is accepted, `4.5` and `-1` are not.
+fn natural_arg(name: &str, v: &Value) -> Result<u64, String> {
+    let n = match v {
+        Value::Int(n) => *n,
+        Value::Number(n) if n.fract() == 0.0 && n.abs() < 9.2e18 => *n as i64,
+        _ => return Err(format!("{}: expected a non-negative integer, got {}", name, v)),
+    };
+    u64::try_from(n).map_err(|_| format!("{}: expected a non-negative integer, got {}", name, v))
+}
+
+fn gcd_u64(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn int_result(name: &str, n: u64) -> Result<Value, String> {
+    i64::try_from(n).map(Value::Int).map_err(|_| format!("{}: result does not fit in a 64-bit integer", name))
+}
+
+builtin!(gcd_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        match args.as_slice() {
+            [a, b] => int_result("gcd", gcd_u64(natural_arg("gcd", a)?, natural_arg("gcd", b)?)),
+            _ => Err("gcd expects 2 arguments".to_string()),
+        }
+    })
+});
+
+builtin!(lcm_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (a, b) = match args.as_slice() {
+            [a, b] => (natural_arg("lcm", a)?, natural_arg("lcm", b)?),
+            _ => return Err("lcm expects 2 arguments".to_string()),
+        };
+        if a == 0 || b == 0 {
+            return Ok(Value::Int(0));
+        }
+        let lcm = (a / gcd_u64(a, b)).checked_mul(b).ok_or("lcm: result does not fit in a 64-bit integer")?;
+        int_result("lcm", lcm)
+    })
+});
+
+// Exact integer factorial: 20! is the largest that fits in i64, larger n is an error
+// rather than a silently rounded float.
+builtin!(factorial_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let n = match args.as_slice() {
+            [n] => natural_arg("factorial", n)?,
+            _ => return Err("factorial expects 1 argument".to_string()),
+        };
+        (1..=n as i64)
+            .try_fold(1i64, i64::checked_mul)
+            .map(Value::Int)
+            .ok_or_else(|| format!("factorial: {}! does not fit in a 64-bit integer (n <= 20)", n))
+    })
+});
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 * b as u128) % m as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Deterministic Miller–Rabin: these bases are exact for every 64-bit n.
+fn is_prime_u64(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    for p in BASES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    let (mut d, mut r) = (n - 1, 0);
+    while d.is_multiple_of(2) {
+        d /= 2;
+        r += 1;
+    }
+    'witness: for a in BASES {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..r {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+builtin!(is_prime_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        match args.as_slice() {
+            [n] => Ok(Value::Boolean(is_prime_u64(natural_arg("is_prime", n)?))),
+            _ => Err("is_prime expects 1 argument".to_string()),
+        }
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Aggregates over arrays of numbers (sum, product, avg; min and max also take strings)
+// -----------------------------------------------------------------------------
+
+/// Numeric elements of an array argument; any non-number is an error.
+fn numbers_arg(name: &str, args: &[Value]) -> Result<Vec<Value>, String> {
+    if args.len() != 1 {
+        return Err(format!("{} expects 1 argument (array)", name));
+    }
+    let items = match &args[0] {
+        Value::Array(arr_rc) => arr_rc.borrow().clone(),
+        _ => return Err(format!("{}: argument must be array", name)),
+    };
+    for (i, item) in items.iter().enumerate() {
+        if item.as_f64().is_none() {
+            return Err(format!("{}: element {} is {}, expected number", name, i, item.type_name()));
+        }
+    }
+    Ok(items)
+}
+
+/// Fold with integer arithmetic while every value is an Int and nothing overflows, float otherwise.
+fn fold_numbers(items: &[Value], init: i64, int_op: fn(i64, i64) -> Option<i64>, float_op: fn(f64, f64) -> f64) -> Value {
+    let mut acc = Value::Int(init);
+    for item in items {
+        acc = match (&acc, item) {
+            (Value::Int(a), Value::Int(b)) => match int_op(*a, *b) {
+                Some(n) => Value::Int(n),
+                None => Value::Number(float_op(*a as f64, *b as f64)),
+            },
+            _ => Value::Number(float_op(acc.as_f64().unwrap_or(0.0), item.as_f64().unwrap_or(0.0))),
+        };
+    }
+    acc
+}
+
+/// Smallest (`want == Less`) or largest (`Greater`) value; ints compare exactly,
+/// strings lexicographically. All items must be numbers or all strings.
+/// A NaN anywhere makes the result NaN, whatever its position.
+fn extreme(name: &str, items: Vec<Value>, want: std::cmp::Ordering) -> Result<Value, String> {
+    let is_nan = |v: &Value| matches!(v, Value::Number(n) if n.is_nan());
+    let mut iter = items.into_iter();
+    let mut best = iter.next().ok_or_else(|| format!("{}: empty array", name))?;
+    let mut saw_nan = is_nan(&best);
+    for item in iter {
+        saw_nan |= is_nan(&item);
+        let ord = match (&item, &best) {
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::String(_), _) | (_, Value::String(_)) => {
+                return Err(format!("{}: cannot compare numbers and strings", name));
             }
+            _ => item.as_f64().partial_cmp(&best.as_f64()),
+        };
+        if ord == Some(want) {
+            best = item;
+        }
+    }
+    if saw_nan {
+        return Ok(Value::Number(f64::NAN));
+    }
+    Ok(best)
+}
+
+/// min/max take either one array or two or more values: `max(xs)`, `max(a, b)`.
+fn extreme_args(name: &str, args: Vec<Value>, want: std::cmp::Ordering) -> Result<Value, String> {
+    let items = match args.as_slice() {
+        [Value::Array(arr_rc)] => arr_rc.borrow().clone(),
+        [_] => return Err(format!("{}: argument must be array", name)),
+        _ => args,
+    };
+    for (i, item) in items.iter().enumerate() {
+        if item.as_f64().is_none() && !matches!(item, Value::String(_)) {
+            return Err(format!("{}: element {} is {}, expected number or string", name, i, item.type_name()));
         }
+    }
+    extreme(name, items, want)
+}
+
+builtin!(sum_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let items = numbers_arg("sum", &args)?;
+        Ok(fold_numbers(&items, 0, i64::checked_add, |a, b| a + b))
     })
 });
 
-builtin!(dll_free_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+builtin!(product_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 1 {
-            return Err("dll_free expects 1 argument".to_string());
-        }
-        match &args[0] {
-            Value::Dll(_) => Ok(Value::Null),
-            _ => Err("dll_free argument must be a DLL handle".to_string()),
+        let items = numbers_arg("product", &args)?;
+        Ok(fold_numbers(&items, 1, i64::checked_mul, |a, b| a * b))
+    })
+});
+
+builtin!(avg_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let items = numbers_arg("avg", &args)?;
+        if items.is_empty() {
+            return Err("avg: empty array".to_string());
         }
+        let total: f64 = items.iter().filter_map(Value::as_f64).sum();
+        Ok(Value::Number(total / items.len() as f64))
     })
 });
 
+builtin!(min_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { extreme_args("min", args, std::cmp::Ordering::Less) })
+});
+
+builtin!(max_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move { extreme_args("max", args, std::cmp::Ordering::Greater) })
+});
+
 // -----------------------------------------------------------------------------
-// Memory management builtins (malloc, free, poke, peek, peek32)
+// Random numbers. All draws come from the generator stored in `Env`, so a
+// script that calls `seed(n)` first is fully reproducible.
 // -----------------------------------------------------------------------------
 
-lazy_static! {
-    static ref HEAP: Mutex<HashMap<usize, Vec<u8>>> = Mutex::new(HashMap::new());
-    static ref NEXT_PTR: Mutex<usize> = Mutex::new(1);
-}
-
-builtin!(malloc_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+builtin!(seed_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 1 {
-            return Err("malloc expects 1 argument (size)".to_string());
+        match args.as_slice() {
+            [Value::Int(n)] => env.seed_rng(*n as u64),
+            [Value::Number(n)] => env.seed_rng(n.to_bits()),
+            _ => return Err("seed expects 1 argument (number)".to_string()),
         }
-        let size = match &args[0] {
-            Value::Number(n) => *n as usize,
-            _ => return Err("malloc argument must be number".to_string()),
-        };
-        let mut heap = HEAP.lock().unwrap();
-        let mut next = NEXT_PTR.lock().unwrap();
-        let ptr = *next;
-        *next += 1;
-        heap.insert(ptr, vec![0; size]);
-        Ok(Value::Number(ptr as f64))
+        Ok(Value::Null)
     })
 });
 
-builtin!(free_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+builtin!(random_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 1 {
-            return Err("free expects 1 argument (ptr)".to_string());
+        if !args.is_empty() {
+            return Err("random expects no arguments".to_string());
         }
-        let ptr = match &args[0] {
-            Value::Number(n) => *n as usize,
-            _ => return Err("free argument must be number".to_string()),
-        };
-        let mut heap = HEAP.lock().unwrap();
-        heap.remove(&ptr);
-        Ok(Value::Null)
+        Ok(Value::Number(env.random_f64()))
     })
 });
 
-builtin!(poke_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+// uuid(): random (version 4) UUID like "1b4e28ba-2fa1-4d2e-8b2c-0e5c8f3a9d71"
+builtin!(uuid_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 3 {
-            return Err("poke expects 3 arguments: ptr, offset, value".to_string());
+        if !args.is_empty() {
+            return Err("uuid expects no arguments".to_string());
         }
-        let ptr = match &args[0] {
-            Value::Number(n) => *n as usize,
-            _ => return Err("poke first argument must be number".to_string()),
-        };
-        let offset = match &args[1] {
-            Value::Number(n) => *n as usize,
-            _ => return Err("poke second argument must be number".to_string()),
-        };
-        let value = match &args[2] {
-            Value::Number(n) => *n as u8,
-            _ => return Err("poke third argument must be number (byte)".to_string()),
+        let mut bits = ((env.next_random() as u128) << 64) | env.next_random() as u128;
+        bits = (bits & !(0xF << 76)) | (0x4 << 76); // версия 4
+        bits = (bits & !(0x3 << 62)) | (0x2 << 62); // вариант RFC 4122
+        let hex = format!("{:032x}", bits);
+        Ok(Value::String(format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])))
+    })
+});
+
+// random_int(lo, hi): uniform integer in [lo, hi], both ends included.
+builtin!(random_int_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (lo, hi) = match args.as_slice() {
+            [lo, hi] => match (lo.as_i64(), hi.as_i64()) {
+                (Some(lo), Some(hi)) => (lo, hi),
+                _ => return Err("random_int: bounds must be numbers".to_string()),
+            },
+            _ => return Err("random_int expects 2 arguments: lo, hi".to_string()),
         };
-        let mut heap = HEAP.lock().unwrap();
-        let block = heap.get_mut(&ptr).ok_or("Invalid pointer")?;
-        if offset >= block.len() {
-            return Err("Offset out of bounds".to_string());
+        if lo > hi {
+            return Err(format!("random_int: lo ({}) is greater than hi ({})", lo, hi));
         }
-        block[offset] = value;
-        Ok(Value::Null)
+        let span = hi.wrapping_sub(lo) as u64;
+        let offset = if span == u64::MAX { env.next_random() } else { env.random_below(span + 1) };
+        Ok(Value::Int(lo.wrapping_add(offset as i64)))
     })
 });
 
-builtin!(peek_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+// Fisher–Yates, in place; returns the same array.
+builtin!(shuffle_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 2 {
-            return Err("peek expects 2 arguments: ptr, offset".to_string());
-        }
-        let ptr = match &args[0] {
-            Value::Number(n) => *n as usize,
-            _ => return Err("peek first argument must be number".to_string()),
+        let arr_rc = match args.as_slice() {
+            [Value::Array(arr_rc)] => Rc::clone(arr_rc),
+            _ => return Err("shuffle expects 1 argument (array)".to_string()),
         };
-        let offset = match &args[1] {
-            Value::Number(n) => *n as usize,
-            _ => return Err("peek second argument must be number".to_string()),
-        };
-        let heap = HEAP.lock().unwrap();
-        let block = heap.get(&ptr).ok_or("Invalid pointer")?;
-        if offset >= block.len() {
-            return Err("Offset out of bounds".to_string());
+        {
+            let mut arr = array_mut("shuffle", &arr_rc)?;
+            for i in (1..arr.len()).rev() {
+                let j = env.random_below(i as u64 + 1) as usize;
+                arr.swap(i, j);
+            }
         }
-        Ok(Value::Number(block[offset] as f64))
+        Ok(Value::Array(arr_rc))
     })
 });
 
-builtin!(peek32_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+// sample(arr, k): k elements at distinct positions, in random order; the input is not changed.
+builtin!(sample_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 2 {
-            return Err("peek32 expects 2 arguments: ptr, offset".to_string());
-        }
-        let ptr = match &args[0] {
-            Value::Number(n) => *n as usize,
-            _ => return Err("peek32 first argument must be number".to_string()),
+        let (mut items, k) = match args.as_slice() {
+            [Value::Array(arr_rc), k] => (arr_rc.borrow().clone(), k.as_i64().ok_or("sample: k must be number")?),
+            _ => return Err("sample expects 2 arguments: array, k".to_string()),
         };
-        let offset = match &args[1] {
-            Value::Number(n) => *n as usize,
-            _ => return Err("peek32 second argument must be number".to_string()),
+        if k < 0 || k as usize > items.len() {
+            return Err(format!("sample: k ({}) must be between 0 and the array length ({})", k, items.len()));
+        }
+        // Частичный Fisher–Yates: первые k позиций получают случайные элементы
+        let k = k as usize;
+        for i in 0..k {
+            let j = i + env.random_below((items.len() - i) as u64) as usize;
+            items.swap(i, j);
+        }
+        items.truncate(k);
+        Ok(Value::Array(Rc::new(ArrayCell::new(items))))
+    })
+});
+
+// weighted_choice(values, weights): one value, picked with probability proportional to its weight.
+builtin!(weighted_choice_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (values, weights) = match args.as_slice() {
+            [Value::Array(values), Value::Array(weights)] => (values.borrow().clone(), weights.borrow().clone()),
+            _ => return Err("weighted_choice expects 2 arguments: values_array, weights_array".to_string()),
         };
-        let heap = HEAP.lock().unwrap();
-        let block = heap.get(&ptr).ok_or("Invalid pointer")?;
-        if offset + 3 >= block.len() {
-            return Err("Offset out of bounds for 4-byte read".to_string());
+        if values.len() != weights.len() {
+            return Err(format!("weighted_choice: {} values but {} weights", values.len(), weights.len()));
         }
-        let val = (block[offset] as u32) |
-                 ((block[offset+1] as u32) << 8) |
-                 ((block[offset+2] as u32) << 16) |
-                 ((block[offset+3] as u32) << 24);
-        Ok(Value::Number(val as f64))
+        let mut weights_f = Vec::with_capacity(weights.len());
+        for (i, w) in weights.iter().enumerate() {
+            match w.as_f64() {
+                Some(w) if w >= 0.0 && w.is_finite() => weights_f.push(w),
+                _ => return Err(format!("weighted_choice: weight {} must be a non-negative number, got {}", i, w)),
+            }
+        }
+        let total: f64 = weights_f.iter().sum();
+        if total <= 0.0 {
+            return Err("weighted_choice: weights must have a positive sum".to_string());
+        }
+        let mut target = env.random_f64() * total;
+        for (value, w) in values.iter().zip(&weights_f) {
+            if target < *w {
+                return Ok(value.clone());
+            }
+            target -= w;
+        }
+        // Ошибка округления на последнем шаге: берём последний элемент с ненулевым весом
+        let last = weights_f.iter().rposition(|w| *w > 0.0).unwrap_or(0);
+        Ok(values[last].clone())
     })
 });
 
 // -----------------------------------------------------------------------------
-// Window class registration (experimental, not fully implemented)
+// Clamping and interpolation
 // -----------------------------------------------------------------------------
 
-// This is a stub – full implementation requires async callback handling.
-builtin!(register_window_class_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+/// Exact for two ints, IEEE otherwise (`None` if either is NaN).
+fn compare_numbers(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+    match (a, b) {
+        (Value::Int(x), Value::Int(y)) => Some(x.cmp(y)),
+        _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+    }
+}
+
+builtin!(clamp_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
     Box::pin(async move {
-        if args.len() != 2 {
-            return Err("register_window_class expects 2 arguments: class_name, callback_function_name".to_string());
+        use std::cmp::Ordering;
+        if args.len() != 3 {
+            return Err("clamp expects 3 arguments: value, lo, hi".to_string());
+        }
+        if args.iter().any(|v| v.as_f64().is_none()) {
+            return Err("clamp: arguments must be numbers".to_string());
+        }
+        let (value, lo, hi) = (&args[0], &args[1], &args[2]);
+        if compare_numbers(lo, hi) == Some(Ordering::Greater) {
+            return Err(format!("clamp: lo ({}) is greater than hi ({})", lo, hi));
+        }
+        if compare_numbers(value, lo) == Some(Ordering::Less) {
+            Ok(lo.clone())
+        } else if compare_numbers(value, hi) == Some(Ordering::Greater) {
+            Ok(hi.clone())
+        } else {
+            Ok(value.clone())
+        }
+    })
+});
+
+builtin!(lerp_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        match args.iter().map(Value::as_f64).collect::<Option<Vec<f64>>>().as_deref() {
+            Some(&[a, b, t]) => Ok(Value::Number(a + (b - a) * t)),
+            Some(_) => Err("lerp expects 3 arguments: a, b, t".to_string()),
+            None => Err("lerp: arguments must be numbers".to_string()),
         }
-        // Just a placeholder – real implementation would register a window class
-        // with a thunk that calls back into Forge.
-        Err("Window class registration is not yet implemented in this build".to_string())
     })
 });
 
@@ -735,17 +3672,32 @@ builtin!(tonumber_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result
         }
         match &args[0] {
             Value::String(s) => {
+                if let Ok(n) = s.parse::<i64>() {
+                    return Ok(Value::Int(n));
+                }
                 match s.parse::<f64>() {
                     Ok(n) => Ok(Value::Number(n)),
-                    Err(_) => Ok(Value::Number(0.0)),
+                    Err(_) => Ok(Value::Int(0)),
                 }
             }
             Value::Number(n) => Ok(Value::Number(*n)),
-            Value::Boolean(b) => Ok(Value::Number(if *b { 1.0 } else { 0.0 })),
-            Value::Null => Ok(Value::Number(0.0)),
-            Value::Array(_) => Ok(Value::Number(0.0)),
-            Value::Class { .. } | Value::Instance { .. } | Value::Method(_, _) | Value::Dll(_) => Ok(Value::Number(0.0)),
+            Value::Int(n) => Ok(Value::Int(*n)),
+            Value::Boolean(b) => Ok(Value::Int(if *b { 1 } else { 0 })),
+            Value::Null => Ok(Value::Int(0)),
+            Value::Array(_) | Value::Map(_) | Value::Set(_) => Ok(Value::Int(0)),
+            Value::Class { .. } | Value::Instance { .. } | Value::Method(_, _) | Value::Dll(_) => Ok(Value::Int(0)),
+            Value::Function(_) | Value::Builtin(_) => Ok(Value::Int(0)),
+        }
+    })
+});
+
+// tostring(v): the text print would show, including a class's __str__.
+builtin!(tostring_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("tostring expects 1 argument".to_string());
         }
+        Ok(Value::String(crate::eval::display_string(&args[0], env).await?))
     })
 });
 
@@ -757,22 +3709,363 @@ builtin!(type_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Val
         let type_str = match &args[0] {
             Value::Null => "null",
             Value::Boolean(_) => "boolean",
-            Value::Number(_) => "number",
+            Value::Number(_) | Value::Int(_) => "number",
             Value::String(_) => "string",
             Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Set(_) => "set",
             Value::Class { .. } => "class",
             Value::Instance { .. } => "instance",
             Value::Method(_, _) => "method",
+            Value::Function(_) | Value::Builtin(_) => "function",
             Value::Dll(_) => "dll",
         };
         Ok(Value::String(type_str.to_string()))
     })
 });
 
+/// Every name `type` can return.
+const TYPE_NAMES: &[&str] = &[
+    "null", "boolean", "number", "string", "array", "map", "set", "class", "instance", "method", "function", "dll",
+];
+
+// expect_type(value, "number") or expect_type(value, array("number", "null")):
+// the value itself if `type(value)` is one of the names, otherwise an error.
+builtin!(expect_type_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("expect_type expects 2 arguments: value, type name or array of names".to_string());
+        }
+        let names: Vec<Value> = match &args[1] {
+            Value::Array(arr) => arr.borrow().clone(),
+            other => vec![other.clone()],
+        };
+        let mut expected = Vec::with_capacity(names.len());
+        for name in names {
+            match name {
+                Value::String(s) if TYPE_NAMES.contains(&s.as_str()) => expected.push(s),
+                Value::String(s) => return Err(format!("expect_type: unknown type name '{}'", s)),
+                other => return Err(format!("expect_type: type names must be strings, got {}", other.type_name())),
+            }
+        }
+        if expected.is_empty() {
+            return Err("expect_type: no type names given".to_string());
+        }
+        let actual = args[0].type_name();
+        if expected.iter().any(|t| t == actual) {
+            Ok(args[0].clone())
+        } else {
+            Err(format!("expect_type: expected {}, got {}", expected.join(" or "), actual))
+        }
+    })
+});
+
+builtin!(deep_equal_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("deep_equal expects 2 arguments".to_string());
+        }
+        Ok(Value::Boolean(args[0].deep_eq(&args[1])))
+    })
+});
+
+builtin!(dump_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("dump expects 1 argument".to_string());
+        }
+        env.print_line(&args[0].pretty())?;
+        Ok(Value::Null)
+    })
+});
+
+// eprint(a, b, ...): like print, but to stderr.
+builtin!(eprint_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let mut parts = Vec::with_capacity(args.len());
+        for v in &args {
+            parts.push(crate::eval::display_string(v, env).await?);
+        }
+        env.write_err_line(&parts.join(" "))?;
+        Ok(Value::Null)
+    })
+});
+
+// write_out(a, b, ...): values written back to back, without a newline.
+builtin!(write_out_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let mut text = String::new();
+        for v in &args {
+            text.push_str(&crate::eval::display_string(v, env).await?);
+        }
+        env.write_out(&text)?;
+        Ok(Value::Null)
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Progress indicators: redrawn in place with `\r`, never end the line
+// -----------------------------------------------------------------------------
+
+const PROGRESS_WIDTH: usize = 30;
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+
+thread_local! {
+    static SPINNER_FRAME: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// progress(50, 100) -> "\r[###############...............]  50%"
+builtin!(progress_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 2 {
+            return Err("progress expects 2 arguments: current, total".to_string());
+        }
+        let current = args[0].as_f64().ok_or("progress: current must be a number")?;
+        let total = match args[1].as_f64() {
+            Some(t) if t > 0.0 => t,
+            _ => return Err("progress: total must be a positive number".to_string()),
+        };
+        let ratio = (current / total).clamp(0.0, 1.0);
+        let filled = (ratio * PROGRESS_WIDTH as f64).round() as usize;
+        env.write_out(&format!(
+            "\r[{}{}] {:>3}%",
+            "#".repeat(filled),
+            ".".repeat(PROGRESS_WIDTH - filled),
+            (ratio * 100.0).round() as i64
+        ))?;
+        Ok(Value::Null)
+    })
+});
+
+builtin!(spinner_tick_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if !args.is_empty() {
+            return Err("spinner_tick expects no arguments".to_string());
+        }
+        let frame = SPINNER_FRAME.with(|f| {
+            let i = f.get();
+            f.set((i + 1) % SPINNER_FRAMES.len());
+            SPINNER_FRAMES[i]
+        });
+        env.write_out(&format!("\r{}", frame))?;
+        Ok(Value::Null)
+    })
+});
+
+builtin!(pretty_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() != 1 {
+            return Err("pretty expects 1 argument".to_string());
+        }
+        Ok(Value::String(args[0].pretty()))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Objects: introspection of instances and classes
+// -----------------------------------------------------------------------------
+
+// fields(obj): sorted names of an instance's fields or of a class's static fields.
+builtin!(fields_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        match args.as_slice() {
+            [Value::Instance { fields, .. }] | [Value::Class { fields, .. }] => {
+                let mut names: Vec<String> = fields.borrow().keys().cloned().collect();
+                names.sort();
+                Ok(Value::Array(Rc::new(ArrayCell::new(names.into_iter().map(Value::String).collect()))))
+            }
+            [other] => Err(format!("fields: expected instance or class, got {}", other.type_name())),
+            _ => Err("fields expects 1 argument".to_string()),
+        }
+    })
+});
+
+// has_method(obj, name): whether the class of an instance (or the class itself) defines `name`.
+builtin!(has_method_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let (obj, name) = match args.as_slice() {
+            [obj, Value::String(name)] => (obj, name),
+            [_, _] => return Err("has_method: method name must be string".to_string()),
+            _ => return Err("has_method expects 2 arguments: object, name".to_string()),
+        };
+        let class = match obj {
+            Value::Instance { class, .. } => &**class,
+            Value::Class { .. } => obj,
+            other => return Err(format!("has_method: expected instance or class, got {}", other.type_name())),
+        };
+        match class {
+            Value::Class { methods, .. } => Ok(Value::Boolean(methods.contains_key(name))),
+            _ => Ok(Value::Boolean(false)),
+        }
+    })
+});
+
+// seal(Class): forbid changing the class's static fields and redefining it.
+// Instances keep working as before. Returns the class.
+builtin!(seal_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        match args.as_slice() {
+            [Value::Class { sealed, .. }] => {
+                sealed.set(true);
+                Ok(args[0].clone())
+            }
+            [other] => Err(format!("seal: expected class, got {}", other.type_name())),
+            _ => Err("seal expects 1 argument".to_string()),
+        }
+    })
+});
+
+builtin!(is_sealed_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        match args.as_slice() {
+            [Value::Class { sealed, .. }] => Ok(Value::Boolean(sealed.get())),
+            [_] => Ok(Value::Boolean(false)),
+            _ => Err("is_sealed expects 1 argument".to_string()),
+        }
+    })
+});
+
+// entries(obj): `[key, value]` pairs of a map (in its iteration order, so
+// sorted unless it is an ordered_map) or of an instance's fields (sorted).
+builtin!(entries_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        let pairs: Vec<(String, Value)> = match args.as_slice() {
+            [Value::Map(map)] => map.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            [Value::Instance { fields, .. }] => {
+                let mut pairs: Vec<(String, Value)> =
+                    fields.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                pairs.sort_by(|a, b| a.0.cmp(&b.0));
+                pairs
+            }
+            [other] => return Err(format!("entries: expected map or instance, got {}", other.type_name())),
+            _ => return Err("entries expects 1 argument".to_string()),
+        };
+        let items = pairs
+            .into_iter()
+            .map(|(k, v)| Value::Array(Rc::new(ArrayCell::new(vec![Value::String(k), v]))))
+            .collect();
+        Ok(Value::Array(Rc::new(ArrayCell::new(items))))
+    })
+});
+
+// -----------------------------------------------------------------------------
+// Assertions for test scripts
+// -----------------------------------------------------------------------------
+
+/// Tolerance of `assert_approx` when no epsilon is given.
+const DEFAULT_APPROX_EPSILON: f64 = 1e-9;
+
+builtin!(assert_approx_fn, |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.len() < 2 || args.len() > 3 {
+            return Err("assert_approx expects 2 or 3 arguments: actual, expected, epsilon".to_string());
+        }
+        let actual = args[0].as_f64().ok_or("assert_approx: actual value must be a number")?;
+        let expected = args[1].as_f64().ok_or("assert_approx: expected value must be a number")?;
+        let epsilon = match args.get(2) {
+            None => DEFAULT_APPROX_EPSILON,
+            Some(v) => match v.as_f64() {
+                Some(e) if e >= 0.0 => e,
+                _ => return Err("assert_approx: epsilon must be a non-negative number".to_string()),
+            },
+        };
+        if (actual - expected).abs() <= epsilon {
+            Ok(Value::Null)
+        } else {
+            Err(format!("assert_approx: {} is not within {} of {}", actual, epsilon, expected))
+        }
+    })
+});
+
+// A function argument is called only when checks are on, so an expensive
+// invariant costs nothing in a built exe: `debug_assert(check_heap)`.
+builtin!(debug_assert_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.is_empty() || args.len() > 2 {
+            return Err("debug_assert expects 1 or 2 arguments: condition or function, message".to_string());
+        }
+        if !env.debug_asserts() {
+            return Ok(Value::Null);
+        }
+        let ok = match &args[0] {
+            callee @ (Value::Function(_) | Value::Builtin(_) | Value::Method(..)) => {
+                crate::eval::call_value(callee, Vec::new(), env).await?.as_bool()
+            }
+            cond => cond.as_bool(),
+        };
+        if ok {
+            return Ok(Value::Null);
+        }
+        match args.get(1) {
+            Some(msg) => Err(format!("debug_assert failed: {}", msg)),
+            None => Err("debug_assert failed".to_string()),
+        }
+    })
+});
+
+builtin!(assert_throws_fn, |args: Vec<Value>, env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+    Box::pin(async move {
+        if args.is_empty() || args.len() > 2 {
+            return Err("assert_throws expects 1 or 2 arguments: function, expected substring".to_string());
+        }
+        let expected = match args.get(1) {
+            None => None,
+            Some(Value::String(s)) => Some(s.clone()),
+            Some(_) => return Err("assert_throws: expected substring must be string".to_string()),
+        };
+        // иначе ошибка "not callable" сошла бы за ожидаемое исключение
+        if !matches!(args[0], Value::Function(_) | Value::Builtin(_) | Value::Method(..)) {
+            return Err(format!("assert_throws: expected a function, got {}", args[0].type_name()));
+        }
+        match crate::eval::call_value(&args[0], Vec::new(), env).await {
+            Ok(_) => Err("assert_throws: function returned normally".to_string()),
+            Err(e) => match expected {
+                Some(sub) if !e.contains(&sub) => Err(format!(
+                    "assert_throws: error '{}' does not contain '{}'", e, sub
+                )),
+                _ => Ok(Value::Null),
+            },
+        }
+    })
+});
+
 // -----------------------------------------------------------------------------
 // Install all builtins into the environment
 // -----------------------------------------------------------------------------
 
+/// Builtin names grouped by category, for `--help`. Keep in sync with `install`.
+pub const BUILTIN_CATEGORIES: &[(&str, &[&str])] = &[
+    ("core", &["sleep", "exit", "input", "input_timeout", "input_password", "type", "expect_type", "tonumber", "tostring", "dump", "pretty", "eprint", "write_out"]),
+    ("numbers", &["is_nan", "is_finite", "is_infinite", "sum", "product", "avg", "min", "max", "clamp", "lerp"]),
+    ("math", &["sin", "cos", "tan", "asin", "acos", "atan", "atan2", "to_radians", "to_degrees", "gcd", "lcm", "factorial", "is_prime"]),
+    ("arrays", &["array", "push", "pop", "length", "is_empty", "slice", "get", "set", "set_grow", "fill", "resize", "freeze", "deep_freeze", "is_frozen", "unique", "first", "last", "rest", "take", "drop", "chunk", "windows", "sort_by", "unique_by", "group_by", "take_while", "drop_while", "count_if", "any", "all", "find_index", "find_index_by", "reduce_right", "scan"]),
+    ("maps", &["map", "ordered_map", "map_get", "map_set", "map_has", "map_remove", "map_keys", "merge", "deep_merge", "pick", "omit"]),
+    ("sets", &["set_of", "set_add", "set_has", "set_remove", "set_union", "set_intersect", "set_difference"]),
+    ("random", &["seed", "random", "random_int", "shuffle", "sample", "weighted_choice", "uuid"]),
+    ("grids", &["grid", "grid_get", "grid_set"]),
+    ("strings", &[
+        "upper", "lower", "title_case", "capitalize", "swap_case", "split", "splitn", "join", "replace",
+        "contains", "count_occurrences", "format", "regex_split", "regex_captures",
+        "is_digit", "is_alpha", "is_alnum", "is_whitespace", "is_upper", "is_lower",
+    ]),
+    ("encodings", &["encode", "decode"]),
+    ("ciphers", &["xor_cipher", "rot"]),
+    ("hashing", &["crc32", "sha256_hex", "file_crc32", "file_sha256"]),
+    ("functions", &["memoize", "once", "retry", "benchmark"]),
+    ("durations", &["format_duration", "parse_duration"]),
+    ("throttling", &["rate_limiter", "rate_wait"]),
+    ("files", &["write", "append", "read", "fprintf", "fprintln", "file_exists", "file_mtime", "wait_for_change", "glob_match", "glob_dir"]),
+    ("paths", &["path_join", "basename", "dirname", "extension"]),
+    ("environment", &["env_get", "load_env"]),
+    ("progress", &["progress", "spinner_tick"]),
+    ("objects", &["fields", "has_method", "entries", "seal", "is_sealed"]),
+    ("testing", &["deep_equal", "assert_throws", "assert_approx", "debug_assert"]),
+    ("memory", &["mem_read", "mem_write", "get_reg", "set_reg", "malloc", "free", "poke", "peek", "peek32", "poke64", "peek64", "heap_ptr"]),
+    ("tasks", &["spawn", "join_task", "yield_now"]),
+    ("dll", &["dll_load", "dll_call", "dll_call_typed", "dll_free", "sizeof", "struct_offset"]),
+    ("windows", &["register_window_class"]),
+];
+
 pub fn install(env: &mut Env) {
     env.add_builtin("sleep", sleep_fn());
     env.add_builtin("array", array_fn());
@@ -780,33 +4073,181 @@ pub fn install(env: &mut Env) {
     env.add_builtin("pop", pop_fn());
     env.add_builtin("exit", exit_fn());
     env.add_builtin("length", length_fn());
+    env.add_builtin("is_empty", is_empty_fn());
     env.add_builtin("slice", slice_fn());
     env.add_builtin("input", input_fn());
+    env.add_builtin("input_timeout", input_timeout_fn());
+    env.add_builtin("input_password", input_password_fn());
     env.add_builtin("write", write_fn());
     env.add_builtin("append", append_fn());
     env.add_builtin("read", read_fn());
+    env.add_builtin("fprintf", fprintf_fn());
+    env.add_builtin("fprintln", fprintln_fn());
     env.add_builtin("upper", upper_fn());
     env.add_builtin("lower", lower_fn());
+    env.add_builtin("title_case", title_case_fn());
+    env.add_builtin("capitalize", capitalize_fn());
+    env.add_builtin("swap_case", swap_case_fn());
+    env.add_builtin("is_digit", is_digit_fn());
+    env.add_builtin("is_alpha", is_alpha_fn());
+    env.add_builtin("is_alnum", is_alnum_fn());
+    env.add_builtin("is_whitespace", is_whitespace_fn());
+    env.add_builtin("is_upper", is_upper_fn());
+    env.add_builtin("is_lower", is_lower_fn());
     env.add_builtin("split", split_fn());
+    env.add_builtin("splitn", splitn_fn());
     env.add_builtin("join", join_fn());
     env.add_builtin("replace", replace_fn());
     env.add_builtin("contains", contains_fn());
+    env.add_builtin("count_occurrences", count_occurrences_fn());
+    env.add_builtin("format", format_fn());
+    env.add_builtin("regex_split", regex_split_fn());
+    env.add_builtin("regex_captures", regex_captures_fn());
+    env.add_builtin("encode", encode_fn());
+    env.add_builtin("decode", decode_fn());
+    env.add_builtin("xor_cipher", xor_cipher_fn());
+    env.add_builtin("rot", rot_fn());
+    env.add_builtin("crc32", crc32_fn());
+    env.add_builtin("file_crc32", file_crc32_fn());
+    env.add_builtin("file_sha256", file_sha256_fn());
+    env.add_builtin("sha256_hex", sha256_hex_fn());
     env.add_builtin("get", get_fn());
     env.add_builtin("set", set_fn());
+    env.add_builtin("set_grow", set_grow_fn());
+    env.add_builtin("fill", fill_fn());
+    env.add_builtin("resize", resize_fn());
+    env.add_builtin("freeze", freeze_fn());
+    env.add_builtin("deep_freeze", deep_freeze_fn());
+    env.add_builtin("is_frozen", is_frozen_fn());
+    env.add_builtin("grid", grid_fn());
+    env.add_builtin("grid_get", grid_get_fn());
+    env.add_builtin("grid_set", grid_set_fn());
+    env.add_builtin("unique", unique_fn());
+    env.add_builtin("first", first_fn());
+    env.add_builtin("last", last_fn());
+    env.add_builtin("rest", rest_fn());
+    env.add_builtin("take", take_fn());
+    env.add_builtin("drop", drop_fn());
+    env.add_builtin("chunk", chunk_fn());
+    env.add_builtin("windows", windows_fn());
+    env.add_builtin("map", map_fn());
+    env.add_builtin("ordered_map", ordered_map_fn());
+    env.add_builtin("set_of", set_of_fn());
+    env.add_builtin("set_add", set_add_fn());
+    env.add_builtin("set_has", set_has_fn());
+    env.add_builtin("set_remove", set_remove_fn());
+    env.add_builtin("set_union", set_union_fn());
+    env.add_builtin("set_intersect", set_intersect_fn());
+    env.add_builtin("set_difference", set_difference_fn());
+    env.add_builtin("map_get", map_get_fn());
+    env.add_builtin("map_set", map_set_fn());
+    env.add_builtin("map_has", map_has_fn());
+    env.add_builtin("map_remove", map_remove_fn());
+    env.add_builtin("map_keys", map_keys_fn());
+    env.add_builtin("merge", merge_fn());
+    env.add_builtin("deep_merge", deep_merge_fn());
+    env.add_builtin("pick", pick_fn());
+    env.add_builtin("omit", omit_fn());
+    env.add_builtin("sort_by", sort_by_fn());
+    env.add_builtin("unique_by", unique_by_fn());
+    env.add_builtin("group_by", group_by_fn());
+    env.add_builtin("take_while", take_while_fn());
+    env.add_builtin("drop_while", drop_while_fn());
+    env.add_builtin("count_if", count_if_fn());
+    env.add_builtin("any", any_fn());
+    env.add_builtin("all", all_fn());
+    env.add_builtin("find_index", find_index_fn());
+    env.add_builtin("find_index_by", find_index_by_fn());
+    env.add_builtin("reduce_right", reduce_right_fn());
+    env.add_builtin("scan", scan_fn());
+    env.add_builtin("memoize", memoize_fn());
+    env.add_builtin("once", once_fn());
+    env.add_builtin("retry", retry_fn());
+    env.add_builtin("rate_limiter", rate_limiter_fn());
+    env.add_builtin("rate_wait", rate_wait_fn());
+    env.add_builtin("format_duration", format_duration_fn());
+    env.add_builtin("parse_duration", parse_duration_fn());
+    env.add_builtin("benchmark", benchmark_fn());
     env.add_builtin("file_exists", file_exists_fn());
+    env.add_builtin("file_mtime", file_mtime_fn());
+    env.add_builtin("wait_for_change", wait_for_change_fn());
+    env.add_builtin("path_join", path_join_fn());
+    env.add_builtin("basename", basename_fn());
+    env.add_builtin("dirname", dirname_fn());
+    env.add_builtin("extension", extension_fn());
+    env.add_builtin("glob_match", glob_match_fn());
+    env.add_builtin("glob_dir", glob_dir_fn());
+    env.add_builtin("env_get", env_get_fn());
+    env.add_builtin("load_env", load_env_fn());
     env.add_builtin("mem_read", mem_read_fn());
     env.add_builtin("mem_write", mem_write_fn());
     env.add_builtin("get_reg", get_reg_fn());
     env.add_builtin("set_reg", set_reg_fn());
+    env.add_builtin("is_nan", is_nan_fn());
+    env.add_builtin("is_finite", is_finite_fn());
+    env.add_builtin("is_infinite", is_infinite_fn());
+    env.add_builtin("sum", sum_fn());
+    env.add_builtin("product", product_fn());
+    env.add_builtin("avg", avg_fn());
+    env.add_builtin("min", min_fn());
+    env.add_builtin("max", max_fn());
+    env.add_builtin("clamp", clamp_fn());
+    env.add_builtin("lerp", lerp_fn());
+    env.add_builtin("seed", seed_fn());
+    env.add_builtin("random", random_fn());
+    env.add_builtin("uuid", uuid_fn());
+    env.add_builtin("random_int", random_int_fn());
+    env.add_builtin("shuffle", shuffle_fn());
+    env.add_builtin("sample", sample_fn());
+    env.add_builtin("weighted_choice", weighted_choice_fn());
+    env.add_builtin("sin", sin_fn());
+    env.add_builtin("cos", cos_fn());
+    env.add_builtin("tan", tan_fn());
+    env.add_builtin("asin", asin_fn());
+    env.add_builtin("acos", acos_fn());
+    env.add_builtin("atan", atan_fn());
+    env.add_builtin("atan2", atan2_fn());
+    env.add_builtin("to_radians", to_radians_fn());
+    env.add_builtin("to_degrees", to_degrees_fn());
+    env.add_builtin("gcd", gcd_fn());
+    env.add_builtin("lcm", lcm_fn());
+    env.add_builtin("factorial", factorial_fn());
+    env.add_builtin("is_prime", is_prime_fn());
     env.add_builtin("tonumber", tonumber_fn());
+    env.add_builtin("tostring", tostring_fn());
     env.add_builtin("type", type_fn());
+    env.add_builtin("expect_type", expect_type_fn());
+    env.add_builtin("dump", dump_fn());
+    env.add_builtin("eprint", eprint_fn());
+    env.add_builtin("write_out", write_out_fn());
+    env.add_builtin("progress", progress_fn());
+    env.add_builtin("spinner_tick", spinner_tick_fn());
+    env.add_builtin("pretty", pretty_fn());
+    env.add_builtin("fields", fields_fn());
+    env.add_builtin("has_method", has_method_fn());
+    env.add_builtin("entries", entries_fn());
+    env.add_builtin("seal", seal_fn());
+    env.add_builtin("is_sealed", is_sealed_fn());
+    env.add_builtin("deep_equal", deep_equal_fn());
+    env.add_builtin("assert_throws", assert_throws_fn());
+    env.add_builtin("assert_approx", assert_approx_fn());
+    env.add_builtin("debug_assert", debug_assert_fn());
+    env.add_builtin("spawn", spawn_fn());
+    env.add_builtin("join_task", join_task_fn());
+    env.add_builtin("yield_now", yield_now_fn());
     env.add_builtin("dll_load", dll_load_fn());
     env.add_builtin("dll_call", dll_call_fn());
+    env.add_builtin("dll_call_typed", dll_call_typed_fn());
     env.add_builtin("dll_free", dll_free_fn());
+    env.add_builtin("sizeof", sizeof_fn());
+    env.add_builtin("struct_offset", struct_offset_fn());
     env.add_builtin("malloc", malloc_fn());
     env.add_builtin("free", free_fn());
     env.add_builtin("poke", poke_fn());
     env.add_builtin("peek", peek_fn());
     env.add_builtin("peek32", peek32_fn());
+    env.add_builtin("poke64", poke64_fn());
+    env.add_builtin("peek64", peek64_fn());
+    env.add_builtin("heap_ptr", heap_ptr_fn());
     env.add_builtin("register_window_class", register_window_class_fn());
 }
\ No newline at end of file
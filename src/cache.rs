@@ -0,0 +1,56 @@
+// cache.rs – on-disk cache of parsed scripts (`script.forge` -> `script.forgec`).
+//
+// The cache stores the AST as JSON together with the SHA-256 of the source and
+// the interpreter version; a mismatch in either means the cache is stale and
+// the script is parsed again. Failing to read or write the cache is never an
+// error – it only costs a re-parse.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::ast::Stmt;
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: String,
+    source_hash: String,
+    stmts: Vec<Stmt>,
+}
+
+/// Path of the cache file for a script: the same name with a `.forgec` extension.
+pub fn cache_path(script: &Path) -> PathBuf {
+    script.with_extension("forgec")
+}
+
+fn source_hash(source: &str) -> String {
+    crate::builtins::sha256(source.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parse `source`, reusing the cache next to `script` when it matches the source.
+/// Returns the statements and whether they came from the cache.
+pub fn load_or_parse(script: &Path, source: &str) -> Result<(Vec<Stmt>, bool), String> {
+    let path = cache_path(script);
+    let hash = source_hash(source);
+    if let Some(stmts) = read_cache(&path, &hash) {
+        return Ok((stmts, true));
+    }
+    let stmts = crate::try_parse(source)?;
+    let cache = CacheFile {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        source_hash: hash,
+        stmts,
+    };
+    if let Ok(json) = serde_json::to_string(&cache) {
+        let _ = fs::write(&path, json);
+    }
+    Ok((cache.stmts, false))
+}
+
+fn read_cache(path: &Path, hash: &str) -> Option<Vec<Stmt>> {
+    let content = fs::read_to_string(path).ok()?;
+    let cache: CacheFile = serde_json::from_str(&content).ok()?;
+    if cache.version != env!("CARGO_PKG_VERSION") || cache.source_hash != hash {
+        return None;
+    }
+    Some(cache.stmts)
+}
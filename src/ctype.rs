@@ -0,0 +1,77 @@
+// ctype.rs – names of primitive C types.
+//
+// `sizeof` and the struct layout helpers, as well as the signatures given to
+// `dll_call_typed` (ffi.rs, `--features libffi`), look type names up in the
+// same table, so a name accepted by one is accepted by the other.
+
+use std::os::raw::{c_char, c_int, c_long, c_longlong, c_short};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CType {
+    Void,
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Long,
+    LongLong,
+    ULongLong,
+    SizeT,
+    Float,
+    Double,
+    Pointer,
+}
+
+const NAMES: &[(&str, CType)] = &[
+    ("void", CType::Void),
+    ("char", CType::Char),
+    ("int8", CType::Char),
+    ("bool", CType::UChar),
+    ("uint8", CType::UChar),
+    ("short", CType::Short),
+    ("int16", CType::Short),
+    ("uint16", CType::UShort),
+    ("int", CType::Int),
+    ("int32", CType::Int),
+    ("uint32", CType::UInt),
+    ("long", CType::Long),
+    ("long long", CType::LongLong),
+    ("int64", CType::LongLong),
+    ("uint64", CType::ULongLong),
+    ("size_t", CType::SizeT),
+    ("float", CType::Float),
+    ("double", CType::Double),
+    ("pointer", CType::Pointer),
+    ("ptr", CType::Pointer),
+    ("char*", CType::Pointer),
+    ("void*", CType::Pointer),
+];
+
+impl CType {
+    pub fn parse(name: &str) -> Result<CType, String> {
+        let name = name.trim();
+        NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, ty)| *ty)
+            .ok_or_else(|| format!("Unknown C type '{}'", name))
+    }
+
+    /// Size in bytes on this platform; `None` for `void`.
+    pub fn size(self) -> Option<usize> {
+        use std::mem::size_of;
+        Some(match self {
+            CType::Void => return None,
+            CType::Char | CType::UChar => size_of::<c_char>(),
+            CType::Short | CType::UShort => size_of::<c_short>(),
+            CType::Int | CType::UInt => size_of::<c_int>(),
+            CType::Long => size_of::<c_long>(),
+            CType::LongLong | CType::ULongLong => size_of::<c_longlong>(),
+            CType::SizeT | CType::Pointer => size_of::<usize>(),
+            CType::Float => size_of::<f32>(),
+            CType::Double => size_of::<f64>(),
+        })
+    }
+}
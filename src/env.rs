@@ -1,96 +1,192 @@
 use std::collections::HashMap;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use crate::ast::Stmt;
 use crate::eval::BoxFuture;
+use crate::symbol::Symbol;
 use crate::value::Value;
 use libloading::Library;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserFunction {
     pub name: String,
-    pub params: Vec<String>,
+    pub params: Vec<Symbol>,
     pub body: Vec<Stmt>,
     pub is_async: bool,
+    /// Static method: `Class.f(args)` and `obj.f(args)` call it without a receiver.
+    #[serde(default)]
+    pub is_static: bool,
+    /// The body uses `yield`: a call runs it to the end and returns the yielded values as an array.
+    #[serde(default)]
+    pub is_generator: bool,
 }
 
+/// Default limit for nested user function/method calls.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// Stack assumed for the thread running the interpreter until
+/// [`Env::set_stack_size`] says otherwise: the 2 MB Rust gives spawned threads.
+pub const DEFAULT_STACK_SIZE: usize = 2 * 1024 * 1024;
+
+/// Native function callable from Forge. Receives the evaluated arguments and the
+/// caller's environment and returns a boxed future, so builtins may `.await`
+/// (e.g. `sleep`). Synchronous closures can be registered with
+/// [`crate::Interpreter::register_fn`].
 pub type BuiltinFn = Rc<dyn Fn(Vec<Value>, &mut Env) -> BoxFuture<'_, Result<Value, String>>>;
 
+/// Destination for script output instead of the process stdout, see [`Env::set_output`].
+pub type OutputSink = Rc<RefCell<dyn std::io::Write>>;
+
 #[derive(Clone)]
 pub struct Env {
-    vars: HashMap<String, Value>,
-    funcs: HashMap<String, UserFunction>,
+    vars: HashMap<Symbol, Value>,
+    declared: Vec<Symbol>,       // локальные переменные в порядке объявления, для блочных областей
+    funcs: HashMap<Symbol, UserFunction>,
     builtins: HashMap<String, BuiltinFn>,
     classes: HashMap<String, Value>,
     dll_cache: HashMap<String, Rc<Library>>,
     parent: Option<Rc<RefCell<Env>>>,
-    memory: Vec<u8>,
-    registers: HashMap<String, i64>,
+    memory: Rc<RefCell<Vec<u8>>>,                  // общие для всех дочерних сред:
+    registers: Rc<RefCell<HashMap<String, i64>>>,  // запись в функции видна вызывающему
+    trace: bool,
+    debug_asserts: bool,         // выключаются в exe, собранных builder'ом
+    steps: Rc<Cell<u64>>,        // общий для всех дочерних сред счётчик шагов
+    rng: Rc<Cell<u64>>,          // состояние генератора случайных чисел, тоже общее
+    max_steps: Option<u64>,
+    call_depth: usize,
+    max_call_depth: usize,
+    stack_base: usize,           // адрес стека в начале `run`, 0 -- ещё не запускали
+    stack_size: usize,
+    output: Option<OutputSink>,  // None -- настоящий stdout/stderr
+    yielded: Option<Vec<Value>>, // значения `yield` текущего вызова генератора
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Env {
     pub fn new() -> Self {
         Self {
             vars: HashMap::new(),
+            declared: Vec::new(),
             funcs: HashMap::new(),
             builtins: HashMap::new(),
             classes: HashMap::new(),
             dll_cache: HashMap::new(),
             parent: None,
-            memory: vec![0; 65536],
-            registers: HashMap::new(),
+            memory: Rc::new(RefCell::new(vec![0; 65536])),
+            registers: Rc::new(RefCell::new(HashMap::new())),
+            trace: false,
+            debug_asserts: true,
+            steps: Rc::new(Cell::new(0)),
+            rng: Rc::new(Cell::new(time_seed())),
+            max_steps: None,
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            stack_base: 0,
+            stack_size: DEFAULT_STACK_SIZE,
+            output: None,
+            yielded: None,
         }
     }
 
     pub fn child(&self) -> Self {
         Self {
             vars: HashMap::new(),
+            declared: Vec::new(),
             funcs: self.funcs.clone(),
             builtins: self.builtins.clone(),
             classes: self.classes.clone(),
             dll_cache: self.dll_cache.clone(),
             parent: Some(Rc::new(RefCell::new(self.clone()))),
-            memory: self.memory.clone(),
-            registers: self.registers.clone(),
+            memory: Rc::clone(&self.memory),
+            registers: Rc::clone(&self.registers),
+            trace: self.trace,
+            debug_asserts: self.debug_asserts,
+            steps: Rc::clone(&self.steps),
+            rng: Rc::clone(&self.rng),
+            max_steps: self.max_steps,
+            call_depth: self.call_depth,
+            max_call_depth: self.max_call_depth,
+            stack_base: self.stack_base,
+            stack_size: self.stack_size,
+            output: self.output.clone(),
+            yielded: None,
         }
     }
 
+    /// Look up a variable by name; see [`Env::get_sym`] for the interned fast path.
     pub fn get_var(&self, name: &str) -> Option<Value> {
-        if let Some(val) = self.vars.get(name).cloned() {
+        Symbol::lookup(name).and_then(|sym| self.get_sym(sym))
+    }
+
+    pub fn get_sym(&self, name: Symbol) -> Option<Value> {
+        if let Some(val) = self.vars.get(&name).cloned() {
             return Some(val);
         }
         if let Some(parent) = &self.parent {
-            parent.borrow().get_var(name)
+            parent.borrow().get_sym(name)
         } else {
             None
         }
     }
 
-    pub fn set_var(&mut self, name: String, value: Value) {
-        self.vars.insert(name, value);
+    pub fn set_var(&mut self, name: impl Into<Symbol>, value: Value) {
+        let name = name.into();
+        if self.vars.insert(name, value).is_none() {
+            self.declared.push(name);
+        }
+    }
+
+    /// Start a block scope; pass the result to [`Env::exit_scope`] when the block ends.
+    pub fn enter_scope(&self) -> usize {
+        self.declared.len()
+    }
+
+    /// Drop every local variable first assigned since `mark`. Variables that
+    /// already existed keep the values assigned inside the block.
+    pub fn exit_scope(&mut self, mark: usize) {
+        for name in self.declared.drain(mark..) {
+            self.vars.remove(&name);
+        }
     }
 
     pub fn has_var(&self, name: &str) -> bool {
-        if self.vars.contains_key(name) {
+        match Symbol::lookup(name) {
+            Some(sym) => self.has_sym(sym),
+            None => false,
+        }
+    }
+
+    fn has_sym(&self, name: Symbol) -> bool {
+        if self.vars.contains_key(&name) {
             return true;
         }
         if let Some(parent) = &self.parent {
-            parent.borrow().has_var(name)
+            parent.borrow().has_sym(name)
         } else {
             false
         }
     }
 
-    pub fn define_func(&mut self, name: String, func: UserFunction) {
-        self.funcs.insert(name, func);
+    pub fn define_func(&mut self, name: impl Into<Symbol>, func: UserFunction) {
+        self.funcs.insert(name.into(), func);
     }
 
     pub fn get_func(&self, name: &str) -> Option<UserFunction> {
-        if let Some(func) = self.funcs.get(name).cloned() {
+        Symbol::lookup(name).and_then(|sym| self.get_func_sym(sym))
+    }
+
+    pub fn get_func_sym(&self, name: Symbol) -> Option<UserFunction> {
+        if let Some(func) = self.funcs.get(&name).cloned() {
             return Some(func);
         }
         if let Some(parent) = &self.parent {
-            parent.borrow().get_func(name)
+            parent.borrow().get_func_sym(name)
         } else {
             None
         }
@@ -143,23 +239,196 @@ impl Env {
     }
 
     pub fn mem_read(&self, addr: usize) -> Result<u8, String> {
-        self.memory.get(addr).copied().ok_or_else(|| "Memory access out of bounds".to_string())
+        self.memory.borrow().get(addr).copied().ok_or_else(|| "Memory access out of bounds".to_string())
     }
 
     pub fn mem_write(&mut self, addr: usize, value: u8) -> Result<(), String> {
-        if addr < self.memory.len() {
-            self.memory[addr] = value;
-            Ok(())
-        } else {
-            Err("Memory access out of bounds".to_string())
+        match self.memory.borrow_mut().get_mut(addr) {
+            Some(byte) => {
+                *byte = value;
+                Ok(())
+            }
+            None => Err("Memory access out of bounds".to_string()),
         }
     }
 
     pub fn get_reg(&self, name: &str) -> Option<i64> {
-        self.registers.get(name).copied()
+        self.registers.borrow().get(name).copied()
     }
 
     pub fn set_reg(&mut self, name: String, value: i64) {
-        self.registers.insert(name, value);
+        self.registers.borrow_mut().insert(name, value);
+    }
+
+    pub fn trace(&self) -> bool {
+        self.trace
+    }
+
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+
+    /// Whether `debug_assert` checks run (on by default).
+    pub fn debug_asserts(&self) -> bool {
+        self.debug_asserts
+    }
+
+    pub fn set_debug_asserts(&mut self, enabled: bool) {
+        self.debug_asserts = enabled;
+    }
+
+    pub fn set_max_steps(&mut self, limit: Option<u64>) {
+        self.max_steps = limit;
+    }
+
+    /// Count one evaluation step and fail once the configured budget is exhausted.
+    pub fn count_step(&self) -> Result<(), String> {
+        if let Some(limit) = self.max_steps {
+            let steps = self.steps.get() + 1;
+            self.steps.set(steps);
+            if steps > limit {
+                return Err("execution step limit exceeded".to_string());
+            }
+        }
+        Ok(())
     }
+
+    /// Steps counted so far in the current run (only while a step limit is set).
+    pub fn steps_taken(&self) -> u64 {
+        self.steps.get()
+    }
+
+    /// Undo the steps counted since `steps_taken` returned `steps`.
+    pub(crate) fn rewind_steps(&self, steps: u64) {
+        self.steps.set(steps);
+    }
+
+    /// Reseed the random generator; the same seed gives the same sequence.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng.set(seed);
+    }
+
+    /// Next 64 random bits (splitmix64).
+    pub fn next_random(&self) -> u64 {
+        let state = self.rng.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.rng.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform random integer in `0..n` (`n > 0`).
+    pub fn random_below(&self, n: u64) -> u64 {
+        ((self.next_random() as u128 * n as u128) >> 64) as u64
+    }
+
+    /// Uniform random float in `[0, 1)`.
+    pub fn random_f64(&self) -> f64 {
+        (self.next_random() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Send `print`, `write_out` and `eprint` output to `sink` instead of
+    /// stdout/stderr; `None` restores the default. Function calls inherit the
+    /// sink of their caller.
+    pub fn set_output(&mut self, sink: Option<OutputSink>) {
+        self.output = sink;
+    }
+
+    /// Write `text` to the output sink or stdout and flush it right away, so it
+    /// cannot be overtaken by an `input` prompt written through tokio's stdout.
+    pub fn write_out(&self, text: &str) -> Result<(), String> {
+        use std::io::Write;
+        let result = match &self.output {
+            Some(sink) => {
+                let mut sink = sink.borrow_mut();
+                sink.write_all(text.as_bytes()).and_then(|_| sink.flush())
+            }
+            None => {
+                let mut out = std::io::stdout().lock();
+                out.write_all(text.as_bytes()).and_then(|_| out.flush())
+            }
+        };
+        result.map_err(|e| format!("failed to write output: {}", e))
+    }
+
+    /// Write one line of `print` output, see [`Env::write_out`].
+    pub fn print_line(&self, line: &str) -> Result<(), String> {
+        self.write_out(&format!("{}\n", line))
+    }
+
+    /// Write a line of diagnostics to the output sink, or to stderr by default.
+    pub fn write_err_line(&self, line: &str) -> Result<(), String> {
+        if self.output.is_some() {
+            return self.print_line(line);
+        }
+        use std::io::Write;
+        writeln!(std::io::stderr(), "{}", line).map_err(|e| format!("failed to write to stderr: {}", e))
+    }
+
+    /// Limit nested calls to `limit`. The limit is clamped to what the stack
+    /// holds: a call that would use more than half of the stack size (see
+    /// [`Env::set_stack_size`]) fails with the same error even below `limit`.
+    pub fn set_max_call_depth(&mut self, limit: usize) {
+        self.max_call_depth = limit;
+    }
+
+    /// Size in bytes of the stack of the thread that runs the script.
+    pub fn set_stack_size(&mut self, bytes: usize) {
+        self.stack_size = bytes;
+    }
+
+    /// Remember the current stack position as the start of the script, for
+    /// the stack check in [`Env::enter_call`].
+    pub(crate) fn mark_stack_base(&mut self) {
+        self.stack_base = stack_address();
+    }
+
+    /// Start collecting `yield` values in this (freshly created call) environment.
+    pub fn start_generator(&mut self) {
+        self.yielded = Some(Vec::new());
+    }
+
+    /// Record a `yield` value; errors outside a generator call.
+    pub fn push_yield(&mut self, value: Value) -> Result<(), String> {
+        match &mut self.yielded {
+            Some(values) => {
+                values.push(value);
+                Ok(())
+            }
+            None => Err("yield outside of a function".to_string()),
+        }
+    }
+
+    /// Values yielded so far by the generator call, see [`Env::start_generator`].
+    pub fn take_yielded(&mut self) -> Option<Vec<Value>> {
+        self.yielded.take()
+    }
+
+    /// Mark this (freshly created child) environment as one call deeper than its parent.
+    pub fn enter_call(&mut self) -> Result<(), String> {
+        self.call_depth += 1;
+        // Стек растёт вниз; каждый вызов Forge --- несколько вложенных future
+        // (десятки КБ стека в отладочной сборке)
+        let stack_used = self.stack_base.saturating_sub(stack_address());
+        if self.call_depth > self.max_call_depth || stack_used > self.stack_size / 2 {
+            return Err("maximum recursion depth exceeded".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Approximate current stack pointer: the address of a local.
+#[inline(never)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+/// Initial seed when the script never calls `seed`.
+fn time_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
 }
\ No newline at end of file
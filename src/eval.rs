@@ -1,117 +1,279 @@
+use std::cmp::Ordering;
 use std::future::Future;
 use std::pin::Pin;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use crate::ast::*;
 use crate::env::{Env, UserFunction, BuiltinFn};
-use crate::value::Value;
+use crate::symbol::Symbol;
+use crate::value::{array_mut, ArrayCell, Value};
 
 pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
 
-pub async fn eval_block(stmts: &[Stmt], env: &mut Env) -> Result<Option<Value>, String> {
+/// How a statement left its block early: `return` unwinds to the enclosing
+/// function, `break`/`continue` to the nearest loop (or the loop with that label).
+pub enum Flow {
+    Return(Value),
+    Break(Option<Symbol>),
+    Continue(Option<Symbol>),
+}
+
+/// What a loop does with the flow its body ended with: `Ok(true)` goes on to
+/// the next iteration, `Ok(false)` leaves the loop, `Err(flow)` is passed on
+/// to an enclosing loop or function.
+fn loop_flow(flow: Option<Flow>, label: Option<Symbol>) -> Result<bool, Flow> {
+    match flow {
+        None | Some(Flow::Continue(None)) => Ok(true),
+        Some(Flow::Break(None)) => Ok(false),
+        Some(Flow::Continue(Some(l))) if Some(l) == label => Ok(true),
+        Some(Flow::Break(Some(l))) if Some(l) == label => Ok(false),
+        Some(flow) => Err(flow),
+    }
+}
+
+/// Result of a function body or script: the value of `return`, if any. A
+/// `break`/`continue` that got this far had no loop to stop.
+pub fn returned_value(flow: Option<Flow>) -> Result<Option<Value>, String> {
+    match flow {
+        None => Ok(None),
+        Some(Flow::Return(val)) => Ok(Some(val)),
+        Some(Flow::Break(None)) => Err("break outside of a loop".to_string()),
+        Some(Flow::Continue(None)) => Err("continue outside of a loop".to_string()),
+        Some(Flow::Break(Some(l))) => Err(format!("break {}: no enclosing loop with this label", l)),
+        Some(Flow::Continue(Some(l))) => Err(format!("continue {}: no enclosing loop with this label", l)),
+    }
+}
+
+pub async fn eval_block(stmts: &[Stmt], env: &mut Env) -> Result<Option<Flow>, String> {
+    let mut line = 0;
     for stmt in stmts {
-        if let Some(val) = eval_stmt(stmt, env).await? {
-            return Ok(Some(val));
+        if let Stmt::Line(n) = stmt {
+            line = *n;
+            continue;
+        }
+        if env.trace() {
+            eprintln!("line {}: {}", line, stmt.summary());
+        }
+        if let Some(flow) = eval_stmt(stmt, env).await? {
+            return Ok(Some(flow));
         }
     }
     Ok(None)
 }
 
-fn eval_stmt<'a>(stmt: &'a Stmt, env: &'a mut Env) -> BoxFuture<'a, Result<Option<Value>, String>> {
+/// Evaluate an if/while/for body in its own block scope: variables first
+/// assigned inside are dropped when the body finishes, assignments to
+/// existing variables persist. The loop variable itself is assigned outside
+/// the body and stays visible after the loop.
+async fn eval_scoped(stmts: &[Stmt], env: &mut Env) -> Result<Option<Flow>, String> {
+    let mark = env.enter_scope();
+    let result = eval_block(stmts, env).await;
+    env.exit_scope(mark);
+    result
+}
+
+fn eval_stmt<'a>(stmt: &'a Stmt, env: &'a mut Env) -> BoxFuture<'a, Result<Option<Flow>, String>> {
     Box::pin(async move {
+        env.count_step()?;
         match stmt {
             Stmt::Expr(expr) => {
-                eval_expr(expr, env).await?;
+                eval_value(expr, env).await?;
                 Ok(None)
             }
             Stmt::Assign { name, value } => {
-                let val = eval_expr(value, env).await?;
-                env.set_var(name.clone(), val);
+                let val = eval_value(value, env).await?;
+                env.set_var(*name, val);
+                Ok(None)
+            }
+            Stmt::MultiAssign { targets, values } => {
+                // Все правые части вычисляются до присваивания, поэтому `a, b = b, a` меняет значения местами
+                let items = if let [value] = values.as_slice() {
+                    match eval_value(value, env).await? {
+                        Value::Array(arr_rc) => arr_rc.borrow().clone(),
+                        other => return Err(format!("Cannot unpack {} into {} variables", other.type_name(), targets.len())),
+                    }
+                } else {
+                    let mut items = Vec::with_capacity(values.len());
+                    for value in values {
+                        items.push(eval_value(value, env).await?);
+                    }
+                    items
+                };
+                if items.len() != targets.len() {
+                    return Err(format!("Cannot unpack {} values into {} variables", items.len(), targets.len()));
+                }
+                for (name, item) in targets.iter().zip(items) {
+                    env.set_var(*name, item);
+                }
+                Ok(None)
+            }
+            Stmt::IndexAssign { target, index, value } => {
+                let target_val = eval_value(target, env).await?;
+                let idx_val = eval_value(index, env).await?;
+                let val = eval_value(value, env).await?;
+                if let Some(method) = instance_method(&target_val, "__setitem__") {
+                    call_method(&method, target_val.clone(), vec![idx_val, val], env).await?;
+                } else {
+                    set_index(&target_val, idx_val, val)?;
+                }
                 Ok(None)
             }
             Stmt::If { condition, then_branch, elif_branches, else_branch } => {
-                if eval_expr(condition, env).await?.as_bool() {
-                    return eval_block(then_branch, env).await;
+                if eval_value(condition, env).await?.as_bool() {
+                    return eval_scoped(then_branch, env).await;
                 }
                 for (cond, branch) in elif_branches {
-                    if eval_expr(cond, env).await?.as_bool() {
-                        return eval_block(branch, env).await;
+                    if eval_value(cond, env).await?.as_bool() {
+                        return eval_scoped(branch, env).await;
                     }
                 }
                 if let Some(branch) = else_branch {
-                    return eval_block(branch, env).await;
+                    return eval_scoped(branch, env).await;
                 }
                 Ok(None)
             }
-            Stmt::While { condition, body } => {
-                while eval_expr(condition, env).await?.as_bool() {
-                    if let Some(val) = eval_block(body, env).await? {
-                        return Ok(Some(val));
+            Stmt::Compiled { chunk, original } => {
+                // With --trace the loop runs on the evaluator so every statement is logged
+                if env.trace() {
+                    return eval_stmt(original, env).await;
+                }
+                Box::pin(crate::vm::execute(chunk, env)).await?;
+                Ok(None)
+            }
+            Stmt::While { condition, body, label } => {
+                while eval_value(condition, env).await?.as_bool() {
+                    match loop_flow(eval_scoped(body, env).await?, *label) {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(flow) => return Ok(Some(flow)),
                     }
                 }
                 Ok(None)
             }
-            Stmt::For { var, start, end, body } => {
-                let start_val = eval_expr(start, env).await?;
-                let end_val = eval_expr(end, env).await?;
-                let start_num = match start_val {
-                    Value::Number(n) => n as i64,
-                    _ => return Err("start value must be number".to_string()),
+            Stmt::For { var, start, end, step, body, label } => {
+                let start_val = eval_value(start, env).await?;
+                let end_val = eval_value(end, env).await?;
+                let step_val = match step {
+                    Some(step) => eval_value(step, env).await?,
+                    None => Value::Int(1),
                 };
-                let end_num = match end_val {
-                    Value::Number(n) => n as i64,
-                    _ => return Err("end value must be number".to_string()),
-                };
-                for i in start_num..=end_num {
-                    env.set_var(var.clone(), Value::Number(i as f64));
-                    if let Some(val) = eval_block(body, env).await? {
-                        return Ok(Some(val));
+                for i in ForRange::new(&start_val, &end_val, &step_val)? {
+                    env.set_var(*var, Value::Int(i));
+                    match loop_flow(eval_scoped(body, env).await?, *label) {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(flow) => return Ok(Some(flow)),
                     }
                 }
                 Ok(None)
             }
-            Stmt::ForIn { var, array, body } => {
-                let arr_val = eval_expr(array, env).await?;
+            Stmt::ForIn { var, array, body, label } => {
+                let mut arr_val = eval_value(array, env).await?;
+                // Протокол итерации: `__iter__` возвращает массив, словарь, множество
+                // или объект с `__next__`, который вызывается до первого null
+                if let Some(method) = instance_method(&arr_val, "__iter__") {
+                    arr_val = call_method(&method, arr_val.clone(), Vec::new(), env).await?;
+                }
                 match arr_val {
                     Value::Array(arr_rc) => {
                         let arr = arr_rc.borrow().clone();
                         for item in arr {
-                            env.set_var(var.clone(), item);
-                            if let Some(val) = eval_block(body, env).await? {
-                                return Ok(Some(val));
+                            env.set_var(*var, item);
+                            match loop_flow(eval_scoped(body, env).await?, *label) {
+                                Ok(true) => {}
+                                Ok(false) => break,
+                                Err(flow) => return Ok(Some(flow)),
+                            }
+                        }
+                        Ok(None)
+                    }
+                    // Обход словаря по ключам: отсортированным или в порядке вставки (ordered_map)
+                    Value::Map(map_rc) => {
+                        let keys = map_rc.borrow().keys();
+                        for key in keys {
+                            env.set_var(*var, Value::String(key));
+                            match loop_flow(eval_scoped(body, env).await?, *label) {
+                                Ok(true) => {}
+                                Ok(false) => break,
+                                Err(flow) => return Ok(Some(flow)),
+                            }
+                        }
+                        Ok(None)
+                    }
+                    Value::Set(set_rc) => {
+                        let items: Vec<Value> = set_rc.borrow().iter().map(|k| k.to_value()).collect();
+                        for item in items {
+                            env.set_var(*var, item);
+                            match loop_flow(eval_scoped(body, env).await?, *label) {
+                                Ok(true) => {}
+                                Ok(false) => break,
+                                Err(flow) => return Ok(Some(flow)),
+                            }
+                        }
+                        Ok(None)
+                    }
+                    Value::Instance { .. } => {
+                        let next = instance_method(&arr_val, "__next__")
+                            .ok_or("for-in: instance must define __iter__ or __next__")?;
+                        loop {
+                            let item = call_method(&next, arr_val.clone(), Vec::new(), env).await?;
+                            if let Value::Null = item {
+                                break;
+                            }
+                            env.set_var(*var, item);
+                            match loop_flow(eval_scoped(body, env).await?, *label) {
+                                Ok(true) => {}
+                                Ok(false) => break,
+                                Err(flow) => return Ok(Some(flow)),
                             }
                         }
                         Ok(None)
                     }
-                    _ => Err("for-in: right side must be array".to_string()),
+                    _ => Err("for-in: right side must be array, map, set or an iterable instance".to_string()),
                 }
             }
-            Stmt::Return(expr) => {
-                let val = eval_expr(expr, env).await?;
-                Ok(Some(val))
+            Stmt::Return(values) => {
+                let val = if let [value] = values.as_slice() {
+                    eval_value(value, env).await?
+                } else {
+                    let mut items = Vec::with_capacity(values.len());
+                    for value in values {
+                        items.push(eval_value(value, env).await?);
+                    }
+                    Value::Array(Rc::new(ArrayCell::new(items)))
+                };
+                Ok(Some(Flow::Return(val)))
             }
-            Stmt::FunctionDef { name, params, body, is_async } => {
+            Stmt::Yield(expr) => {
+                let val = eval_value(expr, env).await?;
+                env.push_yield(val)?;
+                Ok(None)
+            }
+            Stmt::Break(label) => Ok(Some(Flow::Break(*label))),
+            Stmt::Continue(label) => Ok(Some(Flow::Continue(*label))),
+            Stmt::FunctionDef { name, params, body, is_async, is_static } => {
+                if *is_static {
+                    return Err(format!("'static' is only allowed for methods inside a class (function '{}')", name));
+                }
                 let func = UserFunction {
                     name: name.clone(),
                     params: params.clone(),
                     body: body.clone(),
                     is_async: *is_async,
+                    is_static: false,
+                    is_generator: contains_yield(body),
                 };
                 env.define_func(name.clone(), func);
                 Ok(None)
             }
             Stmt::Print(exprs) => {
-                let mut first = true;
+                let mut parts = Vec::with_capacity(exprs.len());
                 for expr in exprs {
-                    if !first {
-                        print!(" ");
-                    }
-                    first = false;
-                    let val = eval_expr(expr, env).await?;
-                    print!("{}", val);
+                    let val = eval_value(expr, env).await?;
+                    parts.push(display_string(&val, env).await?);
                 }
-                println!();
+                env.print_line(&parts.join(" "))?;
                 Ok(None)
             }
             Stmt::LoadFrom { folder, target } => {
@@ -148,15 +310,14 @@ fn eval_stmt<'a>(stmt: &'a Stmt, env: &'a mut Env) -> BoxFuture<'a, Result<Optio
                         .map_err(|e| format!("Failed to read file '{}': {}", full_path.display(), e))?;
                     let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
                     let stmts = crate::parser::parse(&lines)?;
-                    eval_block(&stmts, env).await?;
+                    returned_value(eval_block(&stmts, env).await?)?;
                 }
                 Ok(None)
             }
             Stmt::TryCatch { try_body, catch_body } => {
                 let env_snapshot = env.clone();
                 match eval_block(try_body, env).await {
-                    Ok(Some(val)) => Ok(Some(val)),
-                    Ok(None) => Ok(None),
+                    Ok(flow) => Ok(flow),
                     Err(_) => {
                         *env = env_snapshot;
                         eval_block(catch_body, env).await
@@ -164,9 +325,14 @@ fn eval_stmt<'a>(stmt: &'a Stmt, env: &'a mut Env) -> BoxFuture<'a, Result<Optio
                 }
             }
             Stmt::ClassDef { name, parent, fields, methods } => {
+                if let Some(Value::Class { sealed, .. }) = env.get_class(name) {
+                    if sealed.get() {
+                        return Err(format!("Class {} is sealed and cannot be redefined", name));
+                    }
+                }
                 let mut field_map = HashMap::new();
                 for (fname, fexpr) in fields {
-                    let val = eval_expr(fexpr, env).await?;
+                    let val = eval_value(fexpr, env).await?;
                     field_map.insert(fname.clone(), val);
                 }
                 let parent_val = if let Some(p) = parent {
@@ -182,10 +348,12 @@ fn eval_stmt<'a>(stmt: &'a Stmt, env: &'a mut Env) -> BoxFuture<'a, Result<Optio
                     parent: parent_val,
                     fields: Rc::new(RefCell::new(field_map)),
                     methods: methods.iter().map(|m| (m.name.clone(), Rc::new(m.clone()))).collect(),
+                    sealed: Rc::new(Cell::new(false)),
                 };
                 env.define_class(name.clone(), class_value);
                 Ok(None)
             }
+            Stmt::Line(_) => Ok(None),
             Stmt::ImportDll { path, name, alias } => {
                 let lib = env.get_dll(path)?;
                 let lib_clone = Rc::clone(&lib);
@@ -203,11 +371,11 @@ fn eval_stmt<'a>(stmt: &'a Stmt, env: &'a mut Env) -> BoxFuture<'a, Result<Optio
                                 Err(e) => return Err(format!("Failed to get function '{}': {}", func_name, e)),
                             };
                             let result = func();
-                            Ok(Value::Number(result as f64))
+                            Ok(Value::Int(result as i64))
                         }
                     })
                 });
-                env.add_builtin(&alias, wrapper);
+                env.add_builtin(alias, wrapper);
                 Ok(None)
             }
         }
@@ -216,193 +384,590 @@ fn eval_stmt<'a>(stmt: &'a Stmt, env: &'a mut Env) -> BoxFuture<'a, Result<Optio
 
 pub fn eval_expr<'a>(expr: &'a Expr, env: &'a mut Env) -> BoxFuture<'a, Result<Value, String>> {
     Box::pin(async move {
+        env.count_step()?;
         match expr {
             Expr::Number(n) => Ok(Value::Number(*n)),
+            Expr::Int(n) => Ok(Value::Int(*n)),
             Expr::String(s) => Ok(Value::String(s.clone())),
             Expr::Boolean(b) => Ok(Value::Boolean(*b)),
             Expr::Null => Ok(Value::Null),
-            Expr::Variable(name) => {
-                env.get_var(name).ok_or_else(|| format!("Variable '{}' not defined", name))
-            }
+            Expr::Variable(name) => lookup_name(*name, env),
             Expr::BinaryOp { left, op, right } => {
-                let left_val = eval_expr(left, env).await?;
-                let right_val = eval_expr(right, env).await?;
-                match op {
-                    BinaryOpKind::Add => add(&left_val, &right_val).await,
-                    BinaryOpKind::Sub => sub(&left_val, &right_val).await,
-                    BinaryOpKind::Mul => mul(&left_val, &right_val).await,
-                    BinaryOpKind::Div => div(&left_val, &right_val).await,
-                    BinaryOpKind::Mod => modulo(&left_val, &right_val).await,
-                    BinaryOpKind::Eq => Ok(Value::Boolean(left_val == right_val)),
-                    BinaryOpKind::Ne => Ok(Value::Boolean(left_val != right_val)),
-                    BinaryOpKind::Lt => cmp(&left_val, &right_val, |a, b| a < b).await,
-                    BinaryOpKind::Le => cmp(&left_val, &right_val, |a, b| a <= b).await,
-                    BinaryOpKind::Gt => cmp(&left_val, &right_val, |a, b| a > b).await,
-                    BinaryOpKind::Ge => cmp(&left_val, &right_val, |a, b| a >= b).await,
-                    BinaryOpKind::And => Ok(Value::Boolean(left_val.as_bool() && right_val.as_bool())),
-                    BinaryOpKind::Or => Ok(Value::Boolean(left_val.as_bool() || right_val.as_bool())),
-                }
+                let left_val = eval_value(left, env).await?;
+                let right_val = eval_value(right, env).await?;
+                apply_binary(*op, left_val, right_val, env).await
             }
             Expr::UnaryOp { op, expr } => {
-                let val = eval_expr(expr, env).await?;
-                match op {
-                    UnaryOpKind::Not => Ok(Value::Boolean(!val.as_bool())),
-                    UnaryOpKind::Neg => match val {
-                        Value::Number(n) => Ok(Value::Number(-n)),
-                        _ => Err("Unary minus applied to non-number".to_string()),
-                    },
-                }
+                let val = eval_value(expr, env).await?;
+                apply_unary(*op, val, env).await
             }
+            Expr::Coalesce { left, right } => match eval_value(left, env).await? {
+                Value::Null => eval_value(right, env).await,
+                val => Ok(val),
+            },
             Expr::Call { name, args } => {
                 let mut arg_vals = Vec::new();
                 for arg in args {
-                    arg_vals.push(eval_expr(arg, env).await?);
+                    arg_vals.push(eval_value(arg, env).await?);
                 }
-                if let Some(class_val) = env.get_class(name) {
+                if let Some(class_val) = env.get_class(name.as_str()) {
                     return class_val.call_as_class(arg_vals, env).await;
                 }
-                if let Some(builtin) = env.get_builtin(name) {
+                // Функции скрипта важнее встроенных: `function sum(a, b)` не ломается
+                // от появления builtin `sum`
+                if let Some(func) = env.get_func_sym(*name) {
+                    return call_user_function(&func, arg_vals, env).await;
+                }
+                if let Some(builtin) = env.get_builtin(name.as_str()) {
                     return builtin(arg_vals, env).await;
                 }
-                if let Some(func) = env.get_func(name) {
-                    if arg_vals.len() != func.params.len() {
-                        return Err(format!("Function '{}' expects {} arguments, got {}", name, func.params.len(), arg_vals.len()));
-                    }
-                    let mut local_env = env.child();
-                    for (p, v) in func.params.iter().zip(arg_vals) {
-                        local_env.set_var(p.clone(), v);
-                    }
-                    let result = eval_block(&func.body, &mut local_env).await?;
-                    Ok(result.unwrap_or(Value::Null))
-                } else {
-                    Err(format!("Unknown function or class '{}'", name))
+                // A variable holding a function value (e.g. one returned by memoize or a bound method)
+                match env.get_sym(*name) {
+                    Some(callee @ (Value::Function(_) | Value::Builtin(_) | Value::Method(..))) => call_value(&callee, arg_vals, env).await,
+                    _ => Err(format!("Unknown function or class '{}'", name)),
                 }
             }
             Expr::Index { array, index } => {
-                let arr_val = eval_expr(array, env).await?;
-                let idx_val = eval_expr(index, env).await?;
-                match (arr_val, idx_val) {
-                    (Value::Array(arr_rc), Value::Number(n)) => {
-                        let arr = arr_rc.borrow();
-                        let i = n as usize;
-                        if i < arr.len() {
-                            Ok(arr[i].clone())
-                        } else {
-                            Err("Index out of bounds".to_string())
-                        }
-                    }
-                    (Value::String(s), Value::Number(n)) => {
-                        let i = n as usize;
-                        if i < s.len() {
-                            Ok(Value::String(s.chars().nth(i).unwrap().to_string()))
-                        } else {
-                            Err("String index out of bounds".to_string())
-                        }
-                    }
-                    _ => Err("Invalid index access".to_string()),
+                let arr_val = eval_value(array, env).await?;
+                let idx_val = eval_value(index, env).await?;
+                if let Some(method) = instance_method(&arr_val, "__getitem__") {
+                    return call_method(&method, arr_val, vec![idx_val], env).await;
                 }
+                index_value(arr_val, idx_val)
             }
-            Expr::GetAttr { object, attr } => {
-                let obj_val = eval_expr(object, env).await?;
-                obj_val.get_attr(attr).ok_or_else(|| format!("Attribute '{}' not found", attr))
+            Expr::GetAttr { object, attr, optional } => {
+                let obj_val = eval_value(object, env).await?;
+                get_attr(obj_val, attr, *optional)
             }
             Expr::SetAttr { object, attr, value } => {
-                let obj_val = eval_expr(object, env).await?;
-                let val = eval_expr(value, env).await?;
+                let obj_val = eval_value(object, env).await?;
+                let val = eval_value(value, env).await?;
                 obj_val.set_attr(attr.clone(), val)?;
                 Ok(Value::Null)
             }
             Expr::CallMethod { object, method, args } => {
-                let obj_val = eval_expr(object, env).await?;
+                let obj_val = eval_value(object, env).await?;
                 let mut arg_vals = Vec::new();
                 for arg in args {
-                    arg_vals.push(eval_expr(arg, env).await?);
+                    arg_vals.push(eval_value(arg, env).await?);
                 }
                 let method_val = obj_val.get_attr(method).ok_or_else(|| format!("Method '{}' not found", method))?;
                 match method_val {
-                    Value::Method(func, class_or_self) => {
-                        let mut call_args = vec![obj_val.clone()];
-                        call_args.extend(arg_vals);
-                        if call_args.len() != func.params.len() {
-                            return Err(format!("Method '{}' expects {} arguments, got {}", method, func.params.len(), call_args.len()));
-                        }
-                        let mut local_env = env.child();
-                        for (p, v) in func.params.iter().zip(call_args) {
-                            local_env.set_var(p.clone(), v);
-                        }
-                        let result = eval_block(&func.body, &mut local_env).await?;
-                        Ok(result.unwrap_or(Value::Null))
-                    }
+                    Value::Method(func, _receiver) => call_method(&func, obj_val, arg_vals, env).await,
                     _ => Err("Not a method".to_string()),
                 }
             }
-            Expr::Super { args } => {
+            Expr::Super { args: _ } => {
                 Err("super not implemented yet".to_string())
             }
         }
     })
 }
 
-async fn add(a: &Value, b: &Value) -> Result<Value, String> {
+/// Call a user-defined function with already evaluated arguments in a fresh child scope.
+pub async fn call_user_function(func: &UserFunction, args: Vec<Value>, env: &mut Env) -> Result<Value, String> {
+    if args.len() != func.params.len() {
+        return Err(format!("Function '{}' expects {} arguments, got {}", func.name, func.params.len(), args.len()));
+    }
+    let mut local_env = env.child();
+    local_env.enter_call()?;
+    for (p, v) in func.params.iter().zip(args) {
+        local_env.set_var(*p, v);
+    }
+    run_body(func, &mut local_env).await
+}
+
+/// Run a function body in its prepared call environment. A generator is run
+/// to the end (or to `return`, whose value is dropped) and returns everything
+/// it yielded as an array, so infinite generators are not supported.
+async fn run_body(func: &UserFunction, local_env: &mut Env) -> Result<Value, String> {
+    if func.is_generator {
+        local_env.start_generator();
+    }
+    let result = returned_value(eval_block(&func.body, local_env).await?)?;
+    match local_env.take_yielded() {
+        Some(values) => Ok(Value::Array(Rc::new(ArrayCell::new(values)))),
+        None => Ok(result.unwrap_or(Value::Null)),
+    }
+}
+
+/// Call a method on `receiver`, see [`bind_method_args`]. Static methods get
+/// no receiver; other methods need an instance, not the class itself.
+///
+/// The future is boxed: dunder dispatch (`print`, operators) awaits it from
+/// hot paths, which would otherwise grow by the size of a whole `Env`.
+pub fn call_method<'a>(func: &'a UserFunction, receiver: Value, args: Vec<Value>, env: &'a mut Env) -> BoxFuture<'a, Result<Value, String>> {
+    Box::pin(async move {
+        if func.is_static {
+            return call_user_function(func, args, env).await;
+        }
+        if let Value::Class { name, .. } = &receiver {
+            return Err(format!("Method '{}' is not static; call it on an instance of {}", func.name, name));
+        }
+        let mut local_env = env.child();
+        local_env.enter_call()?;
+        bind_method_args("Method", func, receiver, args, &mut local_env)?;
+        run_body(func, &mut local_env).await
+    })
+}
+
+/// Bind the receiver and arguments of a method call in `local_env`.
+///
+/// The receiver is passed as the first parameter when that parameter is named
+/// `self` or `this`, or when the method declares exactly one parameter more
+/// than it was given arguments (`function area(me):`). Otherwise it is
+/// implicit (`function area():`). In both cases the body can also refer to it
+/// as `self` and `this`, unless a parameter uses those names.
+pub fn bind_method_args(kind: &str, func: &UserFunction, receiver: Value, args: Vec<Value>, local_env: &mut Env) -> Result<(), String> {
+    let (self_sym, this_sym) = (Symbol::intern("self"), Symbol::intern("this"));
+    let named_receiver = matches!(func.params.first(), Some(p) if *p == self_sym || *p == this_sym);
+    let explicit = named_receiver || func.params.len() == args.len() + 1;
+    let expected = if explicit { func.params.len() - 1 } else { func.params.len() };
+    if args.len() != expected {
+        return Err(format!("{} '{}' expects {} arguments, got {}", kind, func.name, expected, args.len()));
+    }
+    local_env.set_var(self_sym, receiver.clone());
+    local_env.set_var(this_sym, receiver.clone());
+    let params = if explicit {
+        local_env.set_var(func.params[0], receiver);
+        &func.params[1..]
+    } else {
+        &func.params[..]
+    };
+    for (p, v) in params.iter().zip(args) {
+        local_env.set_var(*p, v);
+    }
+    Ok(())
+}
+
+/// Text of `val` for `print` and `tostring`: an instance whose class defines
+/// `__str__` (or else `__repr__`) is converted by calling it, everything else
+/// uses `Display`. Instances nested in arrays and maps are shown by `Display`.
+pub async fn display_string(val: &Value, env: &mut Env) -> Result<String, String> {
+    if let Some(method) = instance_method(val, "__str__").or_else(|| instance_method(val, "__repr__")) {
+        return match call_method(&method, val.clone(), Vec::new(), env).await? {
+            Value::String(s) => Ok(s),
+            other => Err(format!("{} must return a string, got {}", method.name, other.type_name())),
+        };
+    }
+    Ok(val.to_string())
+}
+
+/// Method `name` of an instance's class, for dunder dispatch.
+fn instance_method(val: &Value, name: &str) -> Option<Rc<UserFunction>> {
+    match val {
+        Value::Instance { class, .. } => match &**class {
+            Value::Class { methods, .. } => methods.get(name).cloned(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Method that overloads `op` when the left operand is an instance.
+fn operator_dunder(op: BinaryOpKind) -> Option<&'static str> {
+    match op {
+        BinaryOpKind::Add => Some("__add__"),
+        BinaryOpKind::Sub => Some("__sub__"),
+        BinaryOpKind::Mul => Some("__mul__"),
+        BinaryOpKind::Div => Some("__div__"),
+        BinaryOpKind::Mod => Some("__mod__"),
+        BinaryOpKind::Lt => Some("__lt__"),
+        BinaryOpKind::Le => Some("__le__"),
+        BinaryOpKind::Gt => Some("__gt__"),
+        BinaryOpKind::Ge => Some("__ge__"),
+        _ => None,
+    }
+}
+
+/// `binary_op` with operator overloading: if the left operand is an instance
+/// whose class defines the matching dunder (`__add__`, `__lt__`, ...), that
+/// method is called with the right operand and its result is the value.
+/// `==` / `!=` use `__eq__` (negated for `!=`) and otherwise compare identity.
+/// Only the left operand is dispatched on; `"s" + obj` appends `obj`'s
+/// `__str__` text, like `print` shows it.
+pub async fn apply_binary(op: BinaryOpKind, left: Value, right: Value, env: &mut Env) -> Result<Value, String> {
+    if let Some(method) = operator_dunder(op).and_then(|name| instance_method(&left, name)) {
+        return call_method(&method, left, vec![right], env).await;
+    }
+    // "s" + obj склеивает строку с тем, что напечатал бы print(obj)
+    if let (BinaryOpKind::Add, Value::String(s), true) = (op, &left, is_instance(&right)) {
+        let text = display_string(&right, env).await?;
+        return Ok(Value::String(format!("{}{}", s, text)));
+    }
+    if let BinaryOpKind::Eq | BinaryOpKind::Ne = op {
+        // Без `__eq__` экземпляры сравниваются по идентичности (как в binary_op)
+        if let Some(method) = instance_method(&left, "__eq__") {
+            let equal = call_method(&method, left, vec![right], env).await?.as_bool();
+            return Ok(Value::Boolean(equal == matches!(op, BinaryOpKind::Eq)));
+        }
+    }
+    binary_op(op, &left, &right)
+}
+
+/// `unary_op` with `__neg__` for instances.
+pub async fn apply_unary(op: UnaryOpKind, val: Value, env: &mut Env) -> Result<Value, String> {
+    if let (UnaryOpKind::Neg, Some(method)) = (op, instance_method(&val, "__neg__")) {
+        return call_method(&method, val, Vec::new(), env).await;
+    }
+    unary_op(op, val)
+}
+
+/// Call any callable value (function, bound method, builtin or class) – used by builtins that take callbacks.
+pub async fn call_value(callee: &Value, args: Vec<Value>, env: &mut Env) -> Result<Value, String> {
+    match callee {
+        Value::Function(func) => call_user_function(func, args, env).await,
+        Value::Method(func, receiver) => call_method(func, (**receiver).clone(), args, env).await,
+        Value::Builtin(f) => f(args, env).await,
+        Value::Class { .. } => callee.call_as_class(args, env).await,
+        _ => Err(format!("Value of type {} is not callable", callee.type_name())),
+    }
+}
+
+/// True if `expr` can be evaluated without awaiting: no calls anywhere inside.
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(_) | Expr::Int(_) | Expr::String(_) | Expr::Boolean(_) | Expr::Null | Expr::Variable(_) => true,
+        Expr::BinaryOp { left, right, .. } => is_pure(left) && is_pure(right),
+        Expr::UnaryOp { expr, .. } => is_pure(expr),
+        Expr::Coalesce { left, right } => is_pure(left) && is_pure(right),
+        Expr::Index { array, index } => is_pure(array) && is_pure(index),
+        Expr::GetAttr { object, .. } => is_pure(object),
+        _ => false,
+    }
+}
+
+/// Why the synchronous path gave up on an expression.
+enum PureError {
+    /// An operand is an instance: its `__add__`, `__eq__`, `__getitem__` ...
+    /// are only called on the async path, so the expression is evaluated there.
+    NeedsAsync,
+    Failed(String),
+}
+
+impl From<String> for PureError {
+    fn from(e: String) -> Self {
+        PureError::Failed(e)
+    }
+}
+
+fn is_instance(val: &Value) -> bool {
+    matches!(val, Value::Instance { .. })
+}
+
+/// Synchronous evaluation of a pure expression: same semantics and step
+/// accounting as `eval_expr`, but without a boxed future per node.
+fn eval_pure(expr: &Expr, env: &mut Env) -> Result<Value, PureError> {
+    env.count_step()?;
+    match expr {
+        Expr::Number(n) => Ok(Value::Number(*n)),
+        Expr::Int(n) => Ok(Value::Int(*n)),
+        Expr::String(s) => Ok(Value::String(s.clone())),
+        Expr::Boolean(b) => Ok(Value::Boolean(*b)),
+        Expr::Null => Ok(Value::Null),
+        Expr::Variable(name) => Ok(lookup_name(*name, env)?),
+        Expr::BinaryOp { left, op, right } => {
+            let left_val = eval_pure(left, env)?;
+            let right_val = eval_pure(right, env)?;
+            if is_instance(&left_val) || is_instance(&right_val) {
+                return Err(PureError::NeedsAsync);
+            }
+            Ok(binary_op(*op, &left_val, &right_val)?)
+        }
+        Expr::UnaryOp { op, expr } => {
+            let val = eval_pure(expr, env)?;
+            if is_instance(&val) {
+                return Err(PureError::NeedsAsync);
+            }
+            Ok(unary_op(*op, val)?)
+        }
+        Expr::Coalesce { left, right } => match eval_pure(left, env)? {
+            Value::Null => eval_pure(right, env),
+            val => Ok(val),
+        },
+        Expr::Index { array, index } => {
+            let arr_val = eval_pure(array, env)?;
+            let idx_val = eval_pure(index, env)?;
+            if is_instance(&arr_val) {
+                return Err(PureError::NeedsAsync);
+            }
+            Ok(index_value(arr_val, idx_val)?)
+        }
+        Expr::GetAttr { object, attr, optional } => {
+            let obj_val = eval_pure(object, env)?;
+            Ok(get_attr(obj_val, attr, *optional)?)
+        }
+        _ => Err(PureError::Failed("internal error: impure expression on the pure path".to_string())),
+    }
+}
+
+/// Evaluate an expression, taking the synchronous path when nothing in it can await.
+async fn eval_value(expr: &Expr, env: &mut Env) -> Result<Value, String> {
+    if !is_pure(expr) {
+        return eval_expr(expr, env).await;
+    }
+    let steps = env.steps_taken();
+    match eval_pure(expr, env) {
+        Ok(val) => Ok(val),
+        Err(PureError::Failed(e)) => Err(e),
+        Err(PureError::NeedsAsync) => {
+            // Чистое выражение без побочных эффектов: вычисляем заново, не считая шаги дважды
+            env.rewind_steps(steps);
+            eval_expr(expr, env).await
+        }
+    }
+}
+
+/// Counter of a numeric `for` loop: `start` to `end` inclusive, moving by
+/// `step` (negative for descending ranges). Shared with the VM.
+#[derive(Debug, Clone)]
+pub struct ForRange {
+    next: Option<i64>,
+    end: i64,
+    step: i64,
+}
+
+impl ForRange {
+    pub fn new(start: &Value, end: &Value, step: &Value) -> Result<Self, String> {
+        let start = start.as_i64().ok_or("start value must be number")?;
+        let end = end.as_i64().ok_or("end value must be number")?;
+        let step = step.as_i64().ok_or("step value must be number")?;
+        if step == 0 {
+            return Err("for loop step must not be zero".to_string());
+        }
+        Ok(Self { next: Some(start), end, step })
+    }
+}
+
+impl Iterator for ForRange {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        let i = self.next?;
+        let in_range = if self.step > 0 { i <= self.end } else { i >= self.end };
+        if !in_range {
+            self.next = None;
+            return None;
+        }
+        self.next = i.checked_add(self.step); // переполнение i64 завершает цикл
+        Some(i)
+    }
+}
+
+/// `object.attr`, or `object?.attr`, which gives null instead of an error when `object` is null.
+fn get_attr(obj_val: Value, attr: &str, optional: bool) -> Result<Value, String> {
+    if optional && matches!(obj_val, Value::Null) {
+        return Ok(Value::Null);
+    }
+    obj_val.get_attr(attr).ok_or_else(|| format!("Attribute '{}' not found", attr))
+}
+
+/// `array[index]`, `string[index]` and `map[key]`.
+fn index_value(arr_val: Value, idx_val: Value) -> Result<Value, String> {
+    match (arr_val, idx_val) {
+        (Value::Array(arr_rc), idx @ (Value::Number(_) | Value::Int(_))) => {
+            let arr = arr_rc.borrow();
+            let i = idx.as_i64().unwrap_or(0) as usize;
+            if i < arr.len() {
+                Ok(arr[i].clone())
+            } else {
+                Err("Index out of bounds".to_string())
+            }
+        }
+        (Value::String(s), idx @ (Value::Number(_) | Value::Int(_))) => {
+            // индекс считается в символах, а не в байтах
+            let i = idx.as_i64().unwrap_or(0) as usize;
+            let c = s.chars().nth(i).ok_or("String index out of bounds")?;
+            Ok(Value::String(c.to_string()))
+        }
+        (Value::Map(map_rc), Value::String(key)) => {
+            map_rc.borrow().get(&key).cloned().ok_or_else(|| format!("Key '{}' not found", key))
+        }
+        _ => Err("Invalid index access".to_string()),
+    }
+}
+
+/// Store `val` at `target[idx]`: an existing array slot or a map key.
+fn set_index(target: &Value, idx_val: Value, val: Value) -> Result<(), String> {
+    match (target, idx_val) {
+        (Value::Array(arr_rc), idx @ (Value::Number(_) | Value::Int(_))) => {
+            let mut arr = array_mut("index assignment", arr_rc)?;
+            let i = idx.as_i64().unwrap_or(0) as usize;
+            match arr.get_mut(i) {
+                Some(slot) => {
+                    *slot = val;
+                    Ok(())
+                }
+                None => Err("Index out of bounds".to_string()),
+            }
+        }
+        (Value::Map(map_rc), Value::String(key)) => {
+            map_rc.borrow_mut().insert(key, val);
+            Ok(())
+        }
+        (Value::String(_), _) => Err("Strings are immutable; index assignment is not allowed".to_string()),
+        _ => Err("Invalid index assignment".to_string()),
+    }
+}
+
+/// Resolve a name used as a value: variables first, then functions, builtins and
+/// classes, which can be referenced by name as values.
+pub fn lookup_name(name: Symbol, env: &Env) -> Result<Value, String> {
+    if let Some(val) = env.get_sym(name) {
+        return Ok(val);
+    }
+    if let Some(func) = env.get_func_sym(name) {
+        return Ok(Value::Function(Rc::new(func)));
+    }
+    if let Some(builtin) = env.get_builtin(name.as_str()) {
+        return Ok(Value::Builtin(builtin));
+    }
+    if let Some(class_val) = env.get_class(name.as_str()) {
+        return Ok(class_val);
+    }
+    Err(format!("Variable '{}' not defined", name))
+}
+
+/// Apply a binary operator to evaluated operands (shared with the constant folder).
+pub fn binary_op(op: BinaryOpKind, left: &Value, right: &Value) -> Result<Value, String> {
+    match op {
+        BinaryOpKind::Add => add(left, right),
+        BinaryOpKind::Sub => sub(left, right),
+        BinaryOpKind::Mul => mul(left, right),
+        BinaryOpKind::Div => div(left, right),
+        BinaryOpKind::Mod => modulo(left, right),
+        BinaryOpKind::Eq => Ok(Value::Boolean(left == right)),
+        BinaryOpKind::Ne => Ok(Value::Boolean(left != right)),
+        BinaryOpKind::Lt => cmp(left, right, |a, b| a < b),
+        BinaryOpKind::Le => cmp(left, right, |a, b| a <= b),
+        BinaryOpKind::Gt => cmp(left, right, |a, b| a > b),
+        BinaryOpKind::Ge => cmp(left, right, |a, b| a >= b),
+        BinaryOpKind::And => Ok(Value::Boolean(left.as_bool() && right.as_bool())),
+        BinaryOpKind::Or => Ok(Value::Boolean(left.as_bool() || right.as_bool())),
+    }
+}
+
+/// Apply a unary operator to an evaluated operand.
+pub fn unary_op(op: UnaryOpKind, val: Value) -> Result<Value, String> {
+    match op {
+        UnaryOpKind::Not => Ok(Value::Boolean(!val.as_bool())),
+        UnaryOpKind::Neg => match val {
+            Value::Number(n) => Ok(Value::Number(-n)),
+            Value::Int(n) => Ok(n.checked_neg().map(Value::Int).unwrap_or(Value::Number(-(n as f64)))),
+            _ => Err("Unary minus applied to non-number".to_string()),
+        },
+    }
+}
+
+fn add(a: &Value, b: &Value) -> Result<Value, String> {
     match (a, b) {
-        (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x + y)),
+        (Value::Int(x), Value::Int(y)) => Ok(int_or_float(x.checked_add(*y), *x as f64 + *y as f64)),
         (Value::String(x), Value::String(y)) => Ok(Value::String(format!("{}{}", x, y))),
-        (Value::String(x), y) => Ok(Value::String(format!("{}{}", x, y))),
-        (x, Value::String(y)) => Ok(Value::String(format!("{}{}", x, y))),
+        // Экземпляр справа выводится через `__str__` (см. apply_binary)
+        (Value::String(x), y) if !is_instance(y) => Ok(Value::String(format!("{}{}", x, y))),
+        // Экземпляр слева складывается только через `__add__`
+        (x, Value::String(y)) if !is_instance(x) => Ok(Value::String(format!("{}{}", x, y))),
+        (x, y) if is_number(x) && is_number(y) => Ok(Value::Number(num(x) + num(y))),
         (Value::Array(x_rc), Value::Array(y_rc)) => {
             let x = x_rc.borrow();
             let y = y_rc.borrow();
             let mut new_vec = x.clone();
             new_vec.extend(y.clone());
-            Ok(Value::Array(Rc::new(RefCell::new(new_vec))))
+            Ok(Value::Array(Rc::new(ArrayCell::new(new_vec))))
         }
         _ => Err("Invalid operands for +".to_string()),
     }
 }
 
-async fn sub(a: &Value, b: &Value) -> Result<Value, String> {
+fn sub(a: &Value, b: &Value) -> Result<Value, String> {
     match (a, b) {
-        (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x - y)),
+        (Value::Int(x), Value::Int(y)) => Ok(int_or_float(x.checked_sub(*y), *x as f64 - *y as f64)),
+        (x, y) if is_number(x) && is_number(y) => Ok(Value::Number(num(x) - num(y))),
         _ => Err("Invalid operands for -".to_string()),
     }
 }
 
-async fn mul(a: &Value, b: &Value) -> Result<Value, String> {
+fn mul(a: &Value, b: &Value) -> Result<Value, String> {
     match (a, b) {
-        (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x * y)),
+        (Value::Int(x), Value::Int(y)) => Ok(int_or_float(x.checked_mul(*y), *x as f64 * *y as f64)),
+        (x, y) if is_number(x) && is_number(y) => Ok(Value::Number(num(x) * num(y))),
         _ => Err("Invalid operands for *".to_string()),
     }
 }
 
-async fn div(a: &Value, b: &Value) -> Result<Value, String> {
+fn div(a: &Value, b: &Value) -> Result<Value, String> {
     match (a, b) {
-        (Value::Number(x), Value::Number(y)) => {
-            if *y == 0.0 {
+        // Целое частное остаётся Int, иначе результат дробный: 6 / 3 == 2, 5 / 2 == 2.5
+        (Value::Int(x), Value::Int(y)) => {
+            if *y == 0 {
+                Err("Division by zero".to_string())
+            } else if x.checked_rem(*y) == Some(0) {
+                Ok(int_or_float(x.checked_div(*y), *x as f64 / *y as f64))
+            } else {
+                Ok(Value::Number(*x as f64 / *y as f64))
+            }
+        }
+        (x, y) if is_number(x) && is_number(y) => {
+            if num(y) == 0.0 {
                 Err("Division by zero".to_string())
             } else {
-                Ok(Value::Number(x / y))
+                Ok(Value::Number(num(x) / num(y)))
             }
         }
         _ => Err("Invalid operands for /".to_string()),
     }
 }
 
-async fn modulo(a: &Value, b: &Value) -> Result<Value, String> {
+fn modulo(a: &Value, b: &Value) -> Result<Value, String> {
     match (a, b) {
-        (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x % y)),
+        (Value::Int(x), Value::Int(y)) => {
+            if *y == 0 {
+                Err("Modulo by zero".to_string())
+            } else {
+                Ok(Value::Int(x.wrapping_rem(*y)))
+            }
+        }
+        // Как и у деления, нулевой делитель --- ошибка и для дробных: 5.0 % 0 не даёт NaN
+        (x, y) if is_number(x) && is_number(y) => {
+            if num(y) == 0.0 {
+                Err("Modulo by zero".to_string())
+            } else {
+                Ok(Value::Number(num(x) % num(y)))
+            }
+        }
         _ => Err("Invalid operands for %".to_string()),
     }
 }
 
-async fn cmp<F>(a: &Value, b: &Value, f: F) -> Result<Value, String>
+fn cmp<F>(a: &Value, b: &Value, f: F) -> Result<Value, String>
 where
-    F: FnOnce(f64, f64) -> bool,
+    F: FnOnce(Ordering, Ordering) -> bool,
 {
-    match (a, b) {
-        (Value::Number(x), Value::Number(y)) => Ok(Value::Boolean(f(*x, *y))),
-        (Value::String(x), Value::String(y)) => {
-            Ok(Value::Boolean(f(x.len() as f64, y.len() as f64)))
-        }
-        _ => Err("Comparison not supported for these types".to_string()),
+    // Сравнение через Ordering, чтобы большие Int сравнивались точно, без перевода в f64
+    let ord = match (a, b) {
+        (Value::Int(x), Value::Int(y)) => Some(x.cmp(y)),
+        (x, y) if is_number(x) && is_number(y) => num(x).partial_cmp(&num(y)),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => return Err("Comparison not supported for these types".to_string()),
+    };
+    match ord {
+        Some(ord) => Ok(Value::Boolean(f(ord, Ordering::Equal))),
+        None => Ok(Value::Boolean(false)), // NaN
+    }
+}
+
+fn is_number(v: &Value) -> bool {
+    matches!(v, Value::Number(_) | Value::Int(_))
+}
+
+fn num(v: &Value) -> f64 {
+    v.as_f64().unwrap_or(0.0)
+}
+
+/// Result of checked integer arithmetic, falling back to float on overflow.
+fn int_or_float(checked: Option<i64>, fallback: f64) -> Value {
+    match checked {
+        Some(n) => Value::Int(n),
+        None => Value::Number(fallback),
     }
 }
\ No newline at end of file
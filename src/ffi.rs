@@ -0,0 +1,163 @@
+// ffi.rs – generic DLL calls through libffi (built with `--features libffi`).
+//
+// Without this feature `dll_call` dispatches on a hand-written match of up to
+// 12 `i64` arguments. libffi builds the call frame at run time instead, so any
+// number of arguments and any mix of integer, floating point and pointer types
+// work; `dll_call_typed` takes the C signature as a string, e.g.
+// `"double(double, int)"`.
+
+use std::ffi::{c_void, CString};
+use std::os::raw::{c_int, c_long, c_longlong, c_schar, c_short, c_uchar, c_uint, c_ulonglong, c_ushort};
+use libffi::middle::{arg, Arg, Cif, CodePtr, Type};
+use libloading::Library;
+use crate::ctype::CType;
+use crate::value::Value;
+
+fn ffi_type(ty: CType) -> Type {
+    match ty {
+        CType::Void => Type::void(),
+        CType::Char => Type::c_schar(),
+        CType::UChar => Type::c_uchar(),
+        CType::Short => Type::c_short(),
+        CType::UShort => Type::c_ushort(),
+        CType::Int => Type::c_int(),
+        CType::UInt => Type::c_uint(),
+        CType::Long => Type::c_long(),
+        CType::LongLong => Type::c_longlong(),
+        CType::ULongLong => Type::c_ulonglong(),
+        CType::SizeT => Type::usize(),
+        CType::Float => Type::f32(),
+        CType::Double => Type::f64(),
+        CType::Pointer => Type::pointer(),
+    }
+}
+
+/// Parse a signature like `"double(double, int)"` into return and parameter types.
+pub fn parse_signature(sig: &str) -> Result<(CType, Vec<CType>), String> {
+    let open = sig.find('(').ok_or_else(|| format!("Invalid signature '{}': expected 'ret(args)'", sig))?;
+    let inner = sig[open + 1..]
+        .trim_end()
+        .strip_suffix(')')
+        .ok_or_else(|| format!("Invalid signature '{}': missing ')'", sig))?;
+    let ret = CType::parse(&sig[..open])?;
+    let mut params = Vec::new();
+    if !inner.trim().is_empty() && inner.trim() != "void" {
+        for name in inner.split(',') {
+            match CType::parse(name)? {
+                CType::Void => return Err(format!("Invalid signature '{}': void parameter", sig)),
+                ty => params.push(ty),
+            }
+        }
+    }
+    Ok((ret, params))
+}
+
+/// Argument storage that outlives the call; libffi takes pointers to these.
+enum Slot {
+    Char(c_schar),
+    UChar(c_uchar),
+    Short(c_short),
+    UShort(c_ushort),
+    Int(c_int),
+    UInt(c_uint),
+    Long(c_long),
+    LongLong(c_longlong),
+    ULongLong(c_ulonglong),
+    SizeT(usize),
+    Float(f32),
+    Double(f64),
+    Pointer(*const c_void),
+}
+
+fn to_slot(ty: CType, value: &Value, strings: &mut Vec<CString>) -> Result<Slot, String> {
+    let int = || match value {
+        Value::Boolean(b) => Ok(*b as i64),
+        v => v.as_i64().ok_or_else(|| format!("Expected number for C argument, got {}", v.type_name())),
+    };
+    Ok(match ty {
+        CType::Char => Slot::Char(int()? as c_schar),
+        CType::UChar => Slot::UChar(int()? as c_uchar),
+        CType::Short => Slot::Short(int()? as c_short),
+        CType::UShort => Slot::UShort(int()? as c_ushort),
+        CType::Int => Slot::Int(int()? as c_int),
+        CType::UInt => Slot::UInt(int()? as c_uint),
+        CType::Long => Slot::Long(int()? as c_long),
+        CType::LongLong => Slot::LongLong(int()?),
+        CType::ULongLong => Slot::ULongLong(int()? as c_ulonglong),
+        CType::SizeT => Slot::SizeT(int()? as usize),
+        CType::Float => Slot::Float(value.as_f64().ok_or("Expected number for float argument")? as f32),
+        CType::Double => Slot::Double(value.as_f64().ok_or("Expected number for double argument")?),
+        CType::Pointer => match value {
+            Value::String(s) => {
+                let c = CString::new(s.as_str()).map_err(|_| "String argument contains a NUL byte".to_string())?;
+                let ptr = c.as_ptr() as *const c_void;
+                strings.push(c);
+                Slot::Pointer(ptr)
+            }
+            Value::Null => Slot::Pointer(std::ptr::null()),
+            _ => Slot::Pointer(int()? as usize as *const c_void),
+        },
+        CType::Void => return Err("void is not a valid argument type".to_string()),
+    })
+}
+
+/// Call `name` from `lib` with the given C signature.
+///
+/// # Safety
+/// The signature must match the actual function, as with any FFI call.
+#[allow(clippy::useless_conversion)] // c_long is i64 here but i32 on Windows
+pub unsafe fn call(lib: &Library, name: &str, ret: CType, params: &[CType], args: &[Value]) -> Result<Value, String> {
+    if params.len() != args.len() {
+        return Err(format!("'{}' expects {} arguments, got {}", name, params.len(), args.len()));
+    }
+    let func: libloading::Symbol<*const c_void> = lib
+        .get(name.as_bytes())
+        .map_err(|e| format!("Failed to get function '{}': {}", name, e))?;
+    let code = CodePtr::from_ptr(*func);
+
+    let mut strings = Vec::new();
+    let slots = params
+        .iter()
+        .zip(args)
+        .map(|(ty, v)| to_slot(*ty, v, &mut strings))
+        .collect::<Result<Vec<_>, _>>()?;
+    let ffi_args: Vec<Arg> = slots
+        .iter()
+        .map(|slot| match slot {
+            Slot::Char(v) => arg(v),
+            Slot::UChar(v) => arg(v),
+            Slot::Short(v) => arg(v),
+            Slot::UShort(v) => arg(v),
+            Slot::Int(v) => arg(v),
+            Slot::UInt(v) => arg(v),
+            Slot::Long(v) => arg(v),
+            Slot::LongLong(v) => arg(v),
+            Slot::ULongLong(v) => arg(v),
+            Slot::SizeT(v) => arg(v),
+            Slot::Float(v) => arg(v),
+            Slot::Double(v) => arg(v),
+            Slot::Pointer(v) => arg(v),
+        })
+        .collect();
+    let cif = Cif::new(params.iter().map(|t| ffi_type(*t)), ffi_type(ret));
+
+    Ok(match ret {
+        CType::Void => {
+            cif.call::<()>(code, &ffi_args);
+            Value::Null
+        }
+        CType::Char => Value::Int(i64::from(cif.call::<c_schar>(code, &ffi_args))),
+        CType::UChar => Value::Int(i64::from(cif.call::<c_uchar>(code, &ffi_args))),
+        CType::Short => Value::Int(i64::from(cif.call::<c_short>(code, &ffi_args))),
+        CType::UShort => Value::Int(i64::from(cif.call::<c_ushort>(code, &ffi_args))),
+        CType::Int => Value::Int(i64::from(cif.call::<c_int>(code, &ffi_args))),
+        CType::UInt => Value::Int(i64::from(cif.call::<c_uint>(code, &ffi_args))),
+        CType::Long => Value::Int(i64::from(cif.call::<c_long>(code, &ffi_args))),
+        CType::LongLong => Value::Int(cif.call::<c_longlong>(code, &ffi_args)),
+        CType::ULongLong => Value::Int(cif.call::<c_ulonglong>(code, &ffi_args) as i64),
+        CType::SizeT => Value::Int(cif.call::<usize>(code, &ffi_args) as i64),
+        CType::Float => Value::Number(cif.call::<f32>(code, &ffi_args) as f64),
+        CType::Double => Value::Number(cif.call::<f64>(code, &ffi_args)),
+        CType::Pointer => Value::Int(cif.call::<*const c_void>(code, &ffi_args) as usize as i64),
+    })
+}
@@ -0,0 +1,190 @@
+//! Forge interpreter as a library.
+//!
+//! ```
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), String> {
+//! let mut interp = forge_interpreter::Interpreter::new();
+//! interp.run("x = 1 + 2").await?;
+//! assert!(matches!(interp.get_var("x"), Some(forge_interpreter::Value::Int(3))));
+//! # Ok(())
+//! # }
+//! ```
+pub mod ast;
+pub mod cache;
+pub mod ctype;
+pub mod env;
+pub mod eval;
+#[cfg(feature = "libffi")]
+pub mod ffi;
+pub mod optimize;
+pub mod symbol;
+pub mod value;
+pub mod vm;
+mod builtins;
+mod parser;
+
+pub use builtins::BUILTIN_CATEGORIES;
+pub use env::{BuiltinFn, Env, OutputSink};
+pub use eval::BoxFuture;
+pub use value::Value;
+
+/// Stack size of the interpreter thread in executables built by `builder`.
+pub const BUILT_STACK_SIZE: usize = 512 * 1024 * 1024;
+
+/// Parse Forge source into statements without executing it.
+pub fn try_parse(source: &str) -> Result<Vec<ast::Stmt>, String> {
+    let lines: Vec<String> = source.lines().map(|s| s.trim_end().to_string()).collect();
+    parser::parse(&lines)
+}
+
+/// An interpreter instance: a global environment with all builtins installed.
+/// State (variables, functions, classes) persists across `run` calls.
+///
+/// Every Forge call nests several futures and takes tens of kilobytes of native
+/// stack (over 100 KB for a method call in a debug build). Recursion stops with
+/// `maximum recursion depth exceeded` once half of the stack is used, assuming
+/// a 2 MB stack; on a bigger thread call [`Interpreter::set_stack_size`] to
+/// allow deeper recursion, on a smaller one to keep the check safe.
+pub struct Interpreter {
+    env: Env,
+    optimize: bool,
+    vm: bool,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let mut env = Env::new();
+        builtins::install(&mut env);
+        Self { env, optimize: true, vm: false }
+    }
+
+    /// The interpreter of an executable built by `builder`: `debug_assert`
+    /// checks are skipped, and recursion is limited for the
+    /// [`BUILT_STACK_SIZE`] stack of the thread the generated `main` starts.
+    pub fn for_built_executable() -> Self {
+        let mut interp = Self::new();
+        interp.set_stack_size(BUILT_STACK_SIZE);
+        interp.set_debug_asserts(false);
+        interp
+    }
+
+    /// Run a script. Returns the value of a top-level `return`, if any.
+    /// The step limit (`Env::set_max_steps`) applies to each run separately.
+    ///
+    /// The script runs inside its own `LocalSet`, so `spawn` works on any
+    /// current-thread runtime; tasks not joined by the end of the script are dropped.
+    pub async fn run(&mut self, source: &str) -> Result<Option<Value>, String> {
+        self.run_parsed(try_parse(source)?).await
+    }
+
+    /// Run already parsed statements (e.g. loaded from the `.forgec` cache).
+    pub async fn run_parsed(&mut self, mut stmts: Vec<ast::Stmt>) -> Result<Option<Value>, String> {
+        self.env.mark_stack_base();
+        // `--max-steps` ограничивает каждый запуск, а не все вместе
+        self.env.rewind_steps(0);
+        if self.optimize {
+            optimize::fold_constants(&mut stmts);
+        }
+        if self.vm {
+            vm::compile_program(&mut stmts);
+        }
+        let tasks = tokio::task::LocalSet::new();
+        let flow = tasks.run_until(eval::eval_block(&stmts, &mut self.env)).await?;
+        eval::returned_value(flow)
+    }
+
+    /// Enable or disable constant folding of parsed scripts (on by default).
+    pub fn set_optimize(&mut self, enabled: bool) {
+        self.optimize = enabled;
+    }
+
+    /// Run pure numeric loops on the bytecode VM (off by default).
+    pub fn set_vm(&mut self, enabled: bool) {
+        self.vm = enabled;
+    }
+
+    /// Size in bytes of the stack of the thread calling `run` (2 MB by default);
+    /// see the recursion note on [`Interpreter`].
+    pub fn set_stack_size(&mut self, bytes: usize) {
+        self.env.set_stack_size(bytes);
+    }
+
+    /// Run `debug_assert` checks (on by default; the builder turns them off).
+    pub fn set_debug_asserts(&mut self, enabled: bool) {
+        self.env.set_debug_asserts(enabled);
+    }
+
+    /// Capture script output (`print`, `write_out`, `eprint`, `dump`, `input`
+    /// prompts) in `sink` instead of writing it to stdout/stderr.
+    ///
+    /// ```
+    /// # use std::{cell::RefCell, rc::Rc};
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<(), String> {
+    /// # let mut interp = forge_interpreter::Interpreter::new();
+    /// let buf = Rc::new(RefCell::new(Vec::<u8>::new()));
+    /// interp.set_output(buf.clone());
+    /// interp.run("print(1 + 2)").await?;
+    /// assert_eq!(buf.borrow().as_slice(), b"3\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_output(&mut self, sink: OutputSink) {
+        self.env.set_output(Some(sink));
+    }
+
+    /// Evaluate a single expression, e.g. `"x * 2"`. Like `run`, it has its own
+    /// `LocalSet`, so the expression may `spawn` tasks.
+    pub async fn eval_expr_str(&mut self, source: &str) -> Result<Value, String> {
+        let expr = parser::parse_expr(source.trim())?;
+        self.env.mark_stack_base();
+        let tasks = tokio::task::LocalSet::new();
+        tasks.run_until(eval::eval_expr(&expr, &mut self.env)).await
+    }
+
+    /// Register a synchronous Rust closure as a Forge builtin.
+    ///
+    /// ```
+    /// # use forge_interpreter::{Interpreter, Value};
+    /// # let mut interp = Interpreter::new();
+    /// interp.register_fn("host_version", |_args| Ok(Value::String("1.0".into())));
+    /// ```
+    pub fn register_fn<F>(&mut self, name: &str, f: F)
+    where
+        F: Fn(Vec<Value>) -> Result<Value, String> + 'static,
+    {
+        let f = std::rc::Rc::new(f);
+        let builtin: BuiltinFn = std::rc::Rc::new(move |args: Vec<Value>, _env: &mut Env| -> BoxFuture<'_, Result<Value, String>> {
+            let f = std::rc::Rc::clone(&f);
+            Box::pin(async move { f(args) })
+        });
+        self.env.add_builtin(name, builtin);
+    }
+
+    /// Register an async builtin that gets access to the calling environment.
+    pub fn register_builtin(&mut self, name: &str, f: BuiltinFn) {
+        self.env.add_builtin(name, f);
+    }
+
+    pub fn get_var(&self, name: &str) -> Option<Value> {
+        self.env.get_var(name)
+    }
+
+    pub fn set_var(&mut self, name: &str, value: Value) {
+        self.env.set_var(name, value);
+    }
+
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    pub fn env_mut(&mut self) -> &mut Env {
+        &mut self.env
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,23 +1,128 @@
 // main.rs (updated)
-mod ast;
-mod env;
-mod eval;
-mod parser;
-mod builtins;
-mod value;
-
+use forge_interpreter::{Interpreter, BUILTIN_CATEGORIES};
 use std::fs;
+use std::io::Read;
 use std::path::Path;
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<(), String> {
+/// Stack size of the interpreter thread. Every Forge call nests several boxed
+/// futures, so the default 8 MB main stack would cut recursion short of the
+/// `--max-depth` limit.
+const INTERPRETER_STACK_SIZE: usize = 512 * 1024 * 1024;
+
+/// Command line options: `forge_interpreter [--help] [--version] [--trace] [--no-optimize] [--cache] [--vm] [--max-steps N] [--max-depth N] <file.forge | ->`.
+/// A filename of `-` reads the script from stdin.
+#[derive(Default)]
+struct Options {
+    help: bool,
+    version: bool,
+    filename: Option<String>,
+    trace: bool,
+    no_optimize: bool,
+    cache: bool,
+    vm: bool,
+    max_steps: Option<u64>,
+    max_depth: Option<usize>,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut opts = Options::default();
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--help" | "-h" => opts.help = true,
+            "--version" | "-V" => opts.version = true,
+            "--trace" => opts.trace = true,
+            "--no-optimize" => opts.no_optimize = true,
+            "--cache" => opts.cache = true,
+            "--vm" => opts.vm = true,
+            "--max-steps" => {
+                let n = iter.next().ok_or("--max-steps expects a number")?;
+                let n = n.parse::<u64>().map_err(|_| format!("Invalid --max-steps value '{}'", n))?;
+                opts.max_steps = Some(n);
+            }
+            "--max-depth" => {
+                let n = iter.next().ok_or("--max-depth expects a number")?;
+                let n = n.parse::<usize>().map_err(|_| format!("Invalid --max-depth value '{}'", n))?;
+                opts.max_depth = Some(n);
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option '{}'", flag)),
+            _ if opts.filename.is_none() => opts.filename = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    Ok(opts)
+}
+
+fn main() -> Result<(), String> {
+    let handle = std::thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(|| {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| format!("Failed to start runtime: {}", e))?
+                .block_on(run_main())
+        })
+        .map_err(|e| format!("Failed to start interpreter thread: {}", e))?;
+    handle.join().map_err(|_| "Interpreter thread panicked".to_string())?
+}
+
+fn usage(program: &str) -> String {
+    format!("Usage: {} [--trace] [--no-optimize] [--cache] [--vm] [--max-steps N] [--max-depth N] <file.forge | ->", program)
+}
+
+fn print_help(program: &str) {
+    println!("{}", usage(program));
+    println!();
+    println!("Options:");
+    println!("  -h, --help         show this help and exit");
+    println!("  -V, --version      print the interpreter version and exit");
+    println!("  --trace            log every executed statement to stderr");
+    println!("  --no-optimize      do not fold constant expressions before running");
+    println!("  --cache            reuse the parsed script from <file>.forgec if unchanged");
+    println!("  --vm               run pure arithmetic loops on the bytecode VM");
+    println!("  --max-steps N      abort after N evaluation steps");
+    println!("  --max-depth N      maximum nested call depth (default 1000, capped by the stack)");
+    println!("  -                  read the script from stdin");
+    println!();
+    println!("Builtins:");
+    for (category, names) in BUILTIN_CATEGORIES {
+        println!("  {:<10} {}", category, names.join(", "));
+    }
+}
+
+async fn run_main() -> Result<(), String> {
     // Normal mode: read script from command line argument
     let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: {} <file.forge>", args[0]);
-        std::process::exit(1);
+    let opts = match parse_args(&args) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if opts.version {
+        println!("forge {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    if opts.help {
+        print_help(&args[0]);
+        return Ok(());
+    }
+    let filename = match &opts.filename {
+        Some(f) => f,
+        None => {
+            eprintln!("{}", usage(&args[0]));
+            std::process::exit(1);
+        }
+    };
+    if filename == "-" {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|e| format!("Failed to read stdin: {}", e))?;
+        return run_script(&content, None, &opts).await;
     }
-    let filename = &args[1];
     if !filename.ends_with(".forge") {
         eprintln!("File must have .forge extension");
         std::process::exit(1);
@@ -28,15 +133,29 @@ async fn main() -> Result<(), String> {
     }
     let content = fs::read_to_string(filename)
         .map_err(|e| format!("Failed to read file: {}", e))?;
-    run_script(&content).await
+    run_script(&content, Some(Path::new(filename)), &opts).await
 }
 
-/// Execute a Forge script given its source code.
-async fn run_script(source: &str) -> Result<(), String> {
-    let lines: Vec<String> = source.lines().map(|s| s.trim_end().to_string()).collect();
-    let stmts = parser::parse(&lines)?;
-    let mut env = env::Env::new();
-    builtins::install(&mut env);
-    eval::eval_block(&stmts, &mut env).await?;
+/// Execute a Forge script given its source code; `path` enables the `--cache` lookup.
+async fn run_script(source: &str, path: Option<&Path>, opts: &Options) -> Result<(), String> {
+    let mut interp = Interpreter::new();
+    interp.set_stack_size(INTERPRETER_STACK_SIZE);
+    interp.set_optimize(!opts.no_optimize);
+    interp.set_vm(opts.vm);
+    let env = interp.env_mut();
+    env.set_trace(opts.trace);
+    env.set_max_steps(opts.max_steps);
+    if let Some(depth) = opts.max_depth {
+        env.set_max_call_depth(depth);
+    }
+    match path {
+        Some(path) if opts.cache => {
+            let (stmts, _cached) = forge_interpreter::cache::load_or_parse(path, source)?;
+            interp.run_parsed(stmts).await?;
+        }
+        _ => {
+            interp.run(source).await?;
+        }
+    }
     Ok(())
-}
\ No newline at end of file
+}
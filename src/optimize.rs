@@ -0,0 +1,143 @@
+// optimize.rs – constant folding over the AST, run once after parsing.
+//
+// `BinaryOp`/`UnaryOp` nodes whose operands are literals are replaced by the
+// literal result, computed with the same `eval::binary_op`/`unary_op` as at
+// runtime. Operations that fail (division by zero, `-"text"`, ...) are left in
+// place so the error is still raised when – and only if – the code runs.
+
+use crate::ast::{Expr, Stmt};
+use crate::eval::{binary_op, unary_op};
+use crate::value::Value;
+
+pub fn fold_constants(stmts: &mut [Stmt]) {
+    for stmt in stmts {
+        fold_stmt(stmt);
+    }
+}
+
+fn fold_stmt(stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Expr(expr) | Stmt::Yield(expr) => fold_expr(expr),
+        Stmt::Assign { value, .. } => fold_expr(value),
+        Stmt::MultiAssign { values, .. } | Stmt::Return(values) => values.iter_mut().for_each(fold_expr),
+        Stmt::IndexAssign { target, index, value } => {
+            fold_expr(target);
+            fold_expr(index);
+            fold_expr(value);
+        }
+        Stmt::If { condition, then_branch, elif_branches, else_branch } => {
+            fold_expr(condition);
+            fold_constants(then_branch);
+            for (cond, body) in elif_branches {
+                fold_expr(cond);
+                fold_constants(body);
+            }
+            if let Some(body) = else_branch {
+                fold_constants(body);
+            }
+        }
+        Stmt::While { condition, body, .. } => {
+            fold_expr(condition);
+            fold_constants(body);
+        }
+        Stmt::For { start, end, step, body, .. } => {
+            fold_expr(start);
+            fold_expr(end);
+            if let Some(step) = step {
+                fold_expr(step);
+            }
+            fold_constants(body);
+        }
+        Stmt::ForIn { array, body, .. } => {
+            fold_expr(array);
+            fold_constants(body);
+        }
+        Stmt::FunctionDef { body, .. } => fold_constants(body),
+        Stmt::Print(exprs) => exprs.iter_mut().for_each(fold_expr),
+        Stmt::TryCatch { try_body, catch_body } => {
+            fold_constants(try_body);
+            fold_constants(catch_body);
+        }
+        Stmt::ClassDef { fields, methods, .. } => {
+            fields.iter_mut().for_each(|(_, expr)| fold_expr(expr));
+            methods.iter_mut().for_each(|m| fold_constants(&mut m.body));
+        }
+        Stmt::LoadFrom { .. } | Stmt::ImportDll { .. } | Stmt::Line(_) | Stmt::Compiled { .. } => {}
+        Stmt::Break(_) | Stmt::Continue(_) => {}
+    }
+}
+
+fn fold_expr(expr: &mut Expr) {
+    let folded = match expr {
+        Expr::BinaryOp { left, op, right } => {
+            fold_expr(left);
+            fold_expr(right);
+            match (literal_value(left), literal_value(right)) {
+                (Some(l), Some(r)) => binary_op(*op, &l, &r).ok().and_then(|v| value_literal(&v)),
+                _ => None,
+            }
+        }
+        Expr::UnaryOp { op, expr: inner } => {
+            fold_expr(inner);
+            literal_value(inner).and_then(|v| unary_op(*op, v).ok()).and_then(|v| value_literal(&v))
+        }
+        Expr::Coalesce { left, right } => {
+            fold_expr(left);
+            fold_expr(right);
+            match literal_value(left) {
+                Some(Value::Null) => Some((**right).clone()),
+                Some(_) => Some((**left).clone()),
+                None => None,
+            }
+        }
+        Expr::Call { args, .. } | Expr::Super { args } => {
+            args.iter_mut().for_each(fold_expr);
+            None
+        }
+        Expr::CallMethod { object, args, .. } => {
+            fold_expr(object);
+            args.iter_mut().for_each(fold_expr);
+            None
+        }
+        Expr::Index { array, index } => {
+            fold_expr(array);
+            fold_expr(index);
+            None
+        }
+        Expr::GetAttr { object, .. } => {
+            fold_expr(object);
+            None
+        }
+        Expr::SetAttr { object, value, .. } => {
+            fold_expr(object);
+            fold_expr(value);
+            None
+        }
+        _ => None,
+    };
+    if let Some(lit) = folded {
+        *expr = lit;
+    }
+}
+
+fn literal_value(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Number(n) => Some(Value::Number(*n)),
+        Expr::Int(n) => Some(Value::Int(*n)),
+        Expr::String(s) => Some(Value::String(s.clone())),
+        Expr::Boolean(b) => Some(Value::Boolean(*b)),
+        Expr::Null => Some(Value::Null),
+        _ => None,
+    }
+}
+
+fn value_literal(value: &Value) -> Option<Expr> {
+    match value {
+        Value::Number(n) => Some(Expr::Number(*n)),
+        Value::Int(n) => Some(Expr::Int(*n)),
+        Value::String(s) => Some(Expr::String(s.clone())),
+        Value::Boolean(b) => Some(Expr::Boolean(*b)),
+        Value::Null => Some(Expr::Null),
+        _ => None,
+    }
+}
@@ -1,22 +1,29 @@
 use regex::Regex;
 use crate::ast::*;
+use crate::symbol::Symbol;
 use lazy_static::lazy_static;
 use std::iter::Peekable;
 use std::vec::IntoIter;
 
 lazy_static! {
-    static ref RE_FUNCTION: Regex = Regex::new(r"^(?:(async)\s+)?function\s+(\w+)\s*\(([^)]*)\):$").unwrap();
+    static ref RE_FUNCTION: Regex = Regex::new(r"^(static\s+)?(?:(async)\s+)?function\s+(\w+)\s*\(([^)]*)\):$").unwrap();
     static ref RE_IF: Regex = Regex::new(r"^if\s+(.+):$").unwrap();
     static ref RE_ELIF: Regex = Regex::new(r"^elif\s+(.+):$").unwrap();
     static ref RE_ELSE: Regex = Regex::new(r"^else:$").unwrap();
     static ref RE_WHILE: Regex = Regex::new(r"^while\s+(.+):$").unwrap();
-    static ref RE_FOR: Regex = Regex::new(r"^for\s+(\w+)\s*=\s*(.+),\s*(.+)\s*do$").unwrap();
+    static ref RE_FOR: Regex = Regex::new(r"^for\s+(\w+)\s*=\s*(.+?)\s*do$").unwrap();
     static ref RE_FOR_IN: Regex = Regex::new(r"^for\s+(\w+)\s+in\s+(.+):$").unwrap();
     static ref RE_TRY: Regex = Regex::new(r"^try:$").unwrap();
     static ref RE_CATCH: Regex = Regex::new(r"^catch:$").unwrap();
+    static ref RE_LABEL: Regex = Regex::new(r"^(\w+):\s+((?:while|for)\s.*)$").unwrap();
+    static ref RE_BREAK: Regex = Regex::new(r"^(break|continue)(?:\s+(\w+))?$").unwrap();
     static ref RE_RETURN: Regex = Regex::new(r"^return\s+(.+)$").unwrap();
+    static ref RE_YIELD: Regex = Regex::new(r"^yield\s+(.+)$").unwrap();
     static ref RE_PRINT: Regex = Regex::new(r"^print\((.*)\)$").unwrap();
+    static ref RE_MULTI_ASSIGN: Regex = Regex::new(r"^(?:\[\s*(\w+(?:\s*,\s*\w+)*)\s*\]|(\w+(?:\s*,\s*\w+)+))\s*=\s*([^=].*)$").unwrap();
     static ref RE_ASSIGN: Regex = Regex::new(r"^(\w+)\s*=\s*(.+)$").unwrap();
+    static ref RE_ATTR_ASSIGN: Regex = Regex::new(r"^(.+?)\.(\w+)\s*=\s*([^=].*)$").unwrap();
+    static ref RE_INDEX_ASSIGN: Regex = Regex::new(r"^(.+?\])\s*=\s*([^=].*)$").unwrap();
     static ref RE_CALL: Regex = Regex::new(r"^(\w+)\((.*)\)$").unwrap();
     static ref RE_LOAD: Regex = Regex::new(r"^load\s+from\s+(\w+)\s+(.+)$").unwrap();
     static ref RE_CLASS: Regex = Regex::new(r"^class\s+(\w+)(?:\s*\(\s*(\w*)\s*\))?:$").unwrap();
@@ -26,6 +33,7 @@ lazy_static! {
 #[derive(Debug, Clone, PartialEq)]
 enum Token {
     Number(f64),
+    Int(i64),
     String(String),
     Ident(String),
     Keyword(String),
@@ -36,12 +44,14 @@ enum Token {
     RBracket,
     Comma,
     Dot,
-    EOF,
+    QuestionDot,           // `?.`
+    Eof,
 }
 
 pub fn parse(lines: &[String]) -> Result<Vec<Stmt>, String> {
     let mut stmts = Vec::new();
-    let mut i = 0;
+    // Строка shebang (`#!/usr/bin/env forge`) пропускается целиком, номера строк сохраняются
+    let mut i = if lines.first().is_some_and(|l| l.starts_with("#!")) { 1 } else { 0 };
     while i < lines.len() {
         let line = &lines[i];
         if line.trim().is_empty() || is_comment(line) {
@@ -97,15 +107,18 @@ fn parse_block(lines: &[String], min_indent: usize, start: usize) -> Result<(Vec
                     let (nested, next_i) = parse_block(lines, indent, i)?;
                     for stmt in nested {
                         match stmt {
-                            Stmt::Assign { name, value } => fields.push((name, value)),
-                            Stmt::FunctionDef { name, params, body, is_async } => {
+                            Stmt::Assign { name, value } => fields.push((name.to_string(), value)),
+                            Stmt::FunctionDef { name, params, body, is_async, is_static } => {
                                 methods.push(crate::env::UserFunction {
                                     name,
                                     params,
+                                    is_generator: crate::ast::contains_yield(&body),
                                     body,
                                     is_async,
+                                    is_static,
                                 });
                             }
+                            Stmt::Line(_) => {}
                             _ => return Err(format!("Invalid statement inside class at line {}", i + 1)),
                         }
                     }
@@ -116,7 +129,8 @@ fn parse_block(lines: &[String], min_indent: usize, start: usize) -> Result<(Vec
             }
         }
         let trimmed = line.trim();
-        let stmt = parse_stmt(trimmed, i + 1)?;
+        let line_num = i + 1;
+        let stmt = parse_stmt(trimmed, line_num)?;
 
         // Обработка if-elif-else
         if let Stmt::If { condition, .. } = stmt {
@@ -187,6 +201,7 @@ fn parse_block(lines: &[String], min_indent: usize, start: usize) -> Result<(Vec
                     break;
                 }
             }
+            stmts.push(Stmt::Line(line_num));
             stmts.push(current_if);
             continue;
         }
@@ -240,9 +255,11 @@ fn parse_block(lines: &[String], min_indent: usize, start: usize) -> Result<(Vec
             } else {
                 return Err("Expected catch after try".to_string());
             }
+            stmts.push(Stmt::Line(line_num));
             stmts.push(current_try);
             continue;
         } else {
+            stmts.push(Stmt::Line(line_num));
             stmts.push(stmt);
             i += 1;
         }
@@ -251,19 +268,36 @@ fn parse_block(lines: &[String], min_indent: usize, start: usize) -> Result<(Vec
 }
 
 fn parse_stmt(line: &str, line_num: usize) -> Result<Stmt, String> {
+    if let Some(caps) = RE_LABEL.captures(line) {
+        let mut stmt = parse_stmt(&caps[2], line_num)?;
+        match &mut stmt {
+            Stmt::While { label, .. } | Stmt::For { label, .. } | Stmt::ForIn { label, .. } => {
+                *label = Some(Symbol::intern(&caps[1]));
+            }
+            _ => return Err(format!("Line {}: label '{}' must be followed by a loop", line_num, &caps[1])),
+        }
+        return Ok(stmt);
+    }
+    if let Some(caps) = RE_BREAK.captures(line) {
+        let label = caps.get(2).map(|m| Symbol::intern(m.as_str()));
+        return Ok(if &caps[1] == "break" { Stmt::Break(label) } else { Stmt::Continue(label) });
+    }
     if let Some(caps) = RE_FUNCTION.captures(line) {
-        let is_async = caps.get(1).is_some();
-        let name = caps[2].to_string();
-        let params: Vec<String> = caps[3]
+        let is_static = caps.get(1).is_some();
+        let is_async = caps.get(2).is_some();
+        let name = caps[3].to_string();
+        let params: Vec<Symbol> = caps[4]
             .split(',')
-            .map(|s| s.trim().to_string())
+            .map(|s| s.trim())
             .filter(|s| !s.is_empty())
+            .map(Symbol::intern)
             .collect();
         return Ok(Stmt::FunctionDef {
             name,
             params,
             body: vec![],
             is_async,
+            is_static,
         });
     }
     if let Some(caps) = RE_IF.captures(line) {
@@ -280,26 +314,34 @@ fn parse_stmt(line: &str, line_num: usize) -> Result<Stmt, String> {
         return Ok(Stmt::While {
             condition: cond,
             body: vec![],
+            label: None,
         });
     }
     if let Some(caps) = RE_FOR.captures(line) {
-        let var = caps[1].to_string();
-        let start = parse_expr(&caps[2])?;
-        let end = parse_expr(&caps[3])?;
+        let var = Symbol::intern(&caps[1]);
+        // `for i = start, end do` или `for i = start, end, step do`
+        let mut bounds = parse_arguments(&caps[2])?.into_iter();
+        let (start, end, step) = match (bounds.next(), bounds.next(), bounds.next(), bounds.next()) {
+            (Some(start), Some(end), step, None) => (start, end, step),
+            _ => return Err(format!("Line {}: for expects 'start, end' or 'start, end, step'", line_num)),
+        };
         return Ok(Stmt::For {
             var,
             start,
             end,
+            step,
             body: vec![],
+            label: None,
         });
     }
     if let Some(caps) = RE_FOR_IN.captures(line) {
-        let var = caps[1].to_string();
+        let var = Symbol::intern(&caps[1]);
         let array = parse_expr(&caps[2])?;
         return Ok(Stmt::ForIn {
             var,
             array,
             body: vec![],
+            label: None,
         });
     }
     if RE_TRY.is_match(line) {
@@ -309,21 +351,49 @@ fn parse_stmt(line: &str, line_num: usize) -> Result<Stmt, String> {
         });
     }
     if let Some(caps) = RE_RETURN.captures(line) {
+        let values = parse_arguments(&caps[1])?;
+        return Ok(Stmt::Return(values));
+    }
+    if let Some(caps) = RE_YIELD.captures(line) {
         let expr = parse_expr(&caps[1])?;
-        return Ok(Stmt::Return(expr));
+        return Ok(Stmt::Yield(expr));
     }
     if let Some(caps) = RE_PRINT.captures(line) {
         let args_str = &caps[1];
         let args = parse_arguments(args_str)?;
         return Ok(Stmt::Print(args));
     }
+    if let Some(caps) = RE_MULTI_ASSIGN.captures(line) {
+        let names = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+        let targets = names.split(',').map(|n| Symbol::intern(n.trim())).collect();
+        let values = parse_arguments(&caps[3])?;
+        return Ok(Stmt::MultiAssign { targets, values });
+    }
     if let Some(caps) = RE_ASSIGN.captures(line) {
-        let name = caps[1].to_string();
+        let name = Symbol::intern(&caps[1]);
         let expr = parse_expr(&caps[2])?;
         return Ok(Stmt::Assign { name, value: expr });
     }
+    if let Some(caps) = RE_ATTR_ASSIGN.captures(line) {
+        // `obj.attr = value`, `self.pos.x = value`; объект слева --- любое выражение
+        if let Ok(object) = parse_expr(&caps[1]) {
+            let value = parse_expr(&caps[3])?;
+            return Ok(Stmt::Expr(Expr::SetAttr {
+                object: Box::new(object),
+                attr: caps[2].to_string(),
+                value: Box::new(value),
+            }));
+        }
+    }
+    if let Some(caps) = RE_INDEX_ASSIGN.captures(line) {
+        // Левая часть должна быть индексом целиком: `a[i]`, `obj.items[0]`, `grid[y][x]`
+        if let Ok(Expr::Index { array, index }) = parse_expr(&caps[1]) {
+            let value = parse_expr(&caps[2])?;
+            return Ok(Stmt::IndexAssign { target: *array, index: *index, value });
+        }
+    }
     if let Some(caps) = RE_CALL.captures(line) {
-        let name = caps[1].to_string();
+        let name = Symbol::intern(&caps[1]);
         let args_str = &caps[2];
         let args = parse_arguments(args_str)?;
         return Ok(Stmt::Expr(Expr::Call { name, args }));
@@ -370,17 +440,11 @@ fn parse_arguments(s: &str) -> Result<Vec<Expr>, String> {
     for ch in s.chars() {
         if in_string {
             if escaped {
-                match ch {
-                    'n' => current.push('\n'),
-                    'r' => current.push('\r'),
-                    't' => current.push('\t'),
-                    '\\' => current.push('\\'),
-                    '"' => current.push('"'),
-                    '\'' => current.push('\''),
-                    _ => current.push(ch),
-                }
+                // Escapes are decoded by the tokenizer; keep them verbatim here.
+                current.push(ch);
                 escaped = false;
             } else if ch == '\\' {
+                current.push(ch);
                 escaped = true;
             } else {
                 current.push(ch);
@@ -419,12 +483,12 @@ fn parse_arguments(s: &str) -> Result<Vec<Expr>, String> {
 
 // ---------- Парсер выражений ----------
 
-fn parse_expr(input: &str) -> Result<Expr, String> {
+pub fn parse_expr(input: &str) -> Result<Expr, String> {
     let tokens = tokenize(input)?;
     let mut iter = tokens.into_iter().peekable();
-    let expr = parse_or(&mut iter)?;
-    if iter.peek().is_some() && iter.peek().unwrap() != &Token::EOF {
-        return Err(format!("Unexpected tokens at end of expression"));
+    let expr = parse_pipe(&mut iter)?;
+    if iter.peek().is_some() && iter.peek().unwrap() != &Token::Eof {
+        return Err("Unexpected tokens at end of expression".to_string());
     }
     Ok(expr)
 }
@@ -441,6 +505,18 @@ fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             ']' => tokens.push(Token::RBracket),
             ',' => tokens.push(Token::Comma),
             '.' => tokens.push(Token::Dot),
+            '?' if chars.peek() == Some(&'?') => {
+                chars.next();
+                tokens.push(Token::Operator("??".to_string()));
+            }
+            '|' if chars.peek() == Some(&'>') => {
+                chars.next();
+                tokens.push(Token::Operator("|>".to_string()));
+            }
+            '?' if chars.peek() == Some(&'.') => {
+                chars.next();
+                tokens.push(Token::QuestionDot);
+            }
             '+' | '-' | '*' | '/' | '%' | '=' | '!' | '<' | '>' => {
                 let mut op = ch.to_string();
                 if ch == '=' || ch == '!' || ch == '<' || ch == '>' {
@@ -456,7 +532,7 @@ fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                 let quote = ch;
                 let mut s = String::new();
                 let mut escaped = false;
-                while let Some(next) = chars.next() {
+                for next in chars.by_ref() {
                     if escaped {
                         match next {
                             'n' => s.push('\n'),
@@ -487,8 +563,13 @@ fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                         break;
                     }
                 }
-                let n = num.parse::<f64>().map_err(|_| format!("Invalid number: {}", num))?;
-                tokens.push(Token::Number(n));
+                // Целые литералы остаются i64; слишком большие — f64
+                if let Ok(n) = num.parse::<i64>() {
+                    tokens.push(Token::Int(n));
+                } else {
+                    let n = num.parse::<f64>().map_err(|_| format!("Invalid number: {}", num))?;
+                    tokens.push(Token::Number(n));
+                }
             }
             _ if ch.is_alphabetic() || ch == '_' => {
                 let mut ident = ch.to_string();
@@ -511,10 +592,53 @@ fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             _ => return Err(format!("Unexpected character: {}", ch)),
         }
     }
-    tokens.push(Token::EOF);
+    tokens.push(Token::Eof);
     Ok(tokens)
 }
 
+// `x |> f(a)` == `f(x, a)`, `x |> obj.m` == `obj.m(x)`; связывает слабее всех
+// остальных операторов и раскрывается в обычный вызов уже при разборе
+fn parse_pipe(iter: &mut Peekable<IntoIter<Token>>) -> Result<Expr, String> {
+    let mut left = parse_coalesce(iter)?;
+    while let Some(Token::Operator(op)) = iter.peek() {
+        if op != "|>" {
+            break;
+        }
+        iter.next();
+        left = match parse_postfix(iter)? {
+            Expr::Variable(name) => Expr::Call { name, args: vec![left] },
+            Expr::Call { name, mut args } => {
+                args.insert(0, left);
+                Expr::Call { name, args }
+            }
+            Expr::GetAttr { object, attr, optional: false } => Expr::CallMethod { object, method: attr, args: vec![left] },
+            Expr::CallMethod { object, method, mut args } => {
+                args.insert(0, left);
+                Expr::CallMethod { object, method, args }
+            }
+            _ => return Err("Right side of '|>' must be a function or a call".to_string()),
+        };
+    }
+    Ok(left)
+}
+
+// `??` связывает слабее всех операторов, кроме `|>`: `a or b ?? c` == `(a or b) ?? c`
+fn parse_coalesce(iter: &mut Peekable<IntoIter<Token>>) -> Result<Expr, String> {
+    let mut left = parse_or(iter)?;
+    while let Some(Token::Operator(op)) = iter.peek() {
+        if op != "??" {
+            break;
+        }
+        iter.next();
+        let right = parse_or(iter)?;
+        left = Expr::Coalesce {
+            left: Box::new(left),
+            right: Box::new(right),
+        };
+    }
+    Ok(left)
+}
+
 fn parse_or(iter: &mut Peekable<IntoIter<Token>>) -> Result<Expr, String> {
     let mut left = parse_and(iter)?;
     while let Some(Token::Keyword(kw)) = iter.peek() {
@@ -676,7 +800,7 @@ fn parse_postfix(iter: &mut Peekable<IntoIter<Token>>) -> Result<Expr, String> {
                     iter.next();
                 } else {
                     loop {
-                        let arg = parse_or(iter)?;
+                        let arg = parse_pipe(iter)?;
                         args.push(arg);
                         match iter.next() {
                             Some(Token::Comma) => continue,
@@ -686,7 +810,10 @@ fn parse_postfix(iter: &mut Peekable<IntoIter<Token>>) -> Result<Expr, String> {
                     }
                 }
                 match left {
-                    Expr::GetAttr { object, attr } => {
+                    Expr::GetAttr { optional: true, .. } => {
+                        return Err("Optional chaining '?.' is not supported for method calls".to_string());
+                    }
+                    Expr::GetAttr { object, attr, .. } => {
                         left = Expr::CallMethod {
                             object,
                             method: attr,
@@ -701,7 +828,7 @@ fn parse_postfix(iter: &mut Peekable<IntoIter<Token>>) -> Result<Expr, String> {
             }
             Some(Token::LBracket) => {
                 iter.next();
-                let index = parse_or(iter)?;
+                let index = parse_pipe(iter)?;
                 match iter.next() {
                     Some(Token::RBracket) => {}
                     _ => return Err("Expected ']' after index".to_string()),
@@ -711,13 +838,14 @@ fn parse_postfix(iter: &mut Peekable<IntoIter<Token>>) -> Result<Expr, String> {
                     index: Box::new(index),
                 };
             }
-            Some(Token::Dot) => {
-                iter.next();
+            Some(Token::Dot) | Some(Token::QuestionDot) => {
+                let optional = iter.next() == Some(Token::QuestionDot);
                 match iter.next() {
                     Some(Token::Ident(attr)) => {
                         left = Expr::GetAttr {
                             object: Box::new(left),
                             attr,
+                            optional,
                         };
                     }
                     _ => return Err("Expected attribute name after '.'".to_string()),
@@ -732,6 +860,7 @@ fn parse_postfix(iter: &mut Peekable<IntoIter<Token>>) -> Result<Expr, String> {
 fn parse_primary(iter: &mut Peekable<IntoIter<Token>>) -> Result<Expr, String> {
     match iter.next() {
         Some(Token::Number(n)) => Ok(Expr::Number(n)),
+        Some(Token::Int(n)) => Ok(Expr::Int(n)),
         Some(Token::String(s)) => Ok(Expr::String(s)),
         Some(Token::Keyword(kw)) => match kw.as_str() {
             "true" => Ok(Expr::Boolean(true)),
@@ -740,15 +869,15 @@ fn parse_primary(iter: &mut Peekable<IntoIter<Token>>) -> Result<Expr, String> {
             "super" => Ok(Expr::Super { args: vec![] }),
             _ => Err(format!("Unexpected keyword: {}", kw)),
         },
-        Some(Token::Ident(name)) => Ok(Expr::Variable(name)),
+        Some(Token::Ident(name)) => Ok(Expr::Variable(Symbol::intern(&name))),
         Some(Token::LParen) => {
-            let expr = parse_or(iter)?;
+            let expr = parse_pipe(iter)?;
             match iter.next() {
                 Some(Token::RParen) => Ok(expr),
                 _ => Err("Expected ')'".to_string()),
             }
         }
-        Some(Token::EOF) => Err("Unexpected end of expression".to_string()),
+        Some(Token::Eof) => Err("Unexpected end of expression".to_string()),
         _ => Err("Unexpected token".to_string()),
     }
 }
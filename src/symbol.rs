@@ -0,0 +1,91 @@
+// symbol.rs – interned identifiers.
+//
+// Every variable, function and parameter name in the AST is interned once by
+// the parser, so `Env` hashes and compares a `u32` instead of an owned
+// `String` on every lookup. Interned strings are leaked and live for the rest
+// of the process; the set of identifiers in a program is small and fixed.
+// Names that only become known at run time (`Interpreter::get_var`, builtins)
+// go through `Symbol::lookup`, which never allocates: a name that was never
+// interned cannot be bound to anything.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+impl Symbol {
+    /// Intern `name`, returning the existing symbol if it was seen before.
+    pub fn intern(name: &str) -> Symbol {
+        INTERNER.with(|i| {
+            let mut i = i.borrow_mut();
+            if let Some(sym) = i.ids.get(name) {
+                return *sym;
+            }
+            let sym = Symbol(i.names.len() as u32);
+            let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+            i.names.push(name);
+            i.ids.insert(name, sym);
+            sym
+        })
+    }
+
+    /// Symbol for `name` if it has already been interned.
+    pub fn lookup(name: &str) -> Option<Symbol> {
+        INTERNER.with(|i| i.borrow().ids.get(name).copied())
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.with(|i| i.borrow().names[self.0 as usize])
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::intern(&name)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// В кэше (.forgec) символы хранятся строками: номера зависят от порядка интернирования
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Symbol::intern(&name))
+    }
+}
@@ -1,43 +1,233 @@
 use std::rc::Rc;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt;
-use crate::env::UserFunction;
+use crate::env::{BuiltinFn, UserFunction};
 
 #[derive(Clone)]
 pub enum Value {
     Number(f64),
+    Int(i64),                            // 64-битное целое (адреса, указатели, счётчики)
     String(String),
     Boolean(bool),
-    Array(Rc<RefCell<Vec<Value>>>),
+    Array(Rc<ArrayCell>),
+    Map(Rc<RefCell<MapValue>>),          // словарь со строковыми ключами, см. MapValue
+    Set(Rc<RefCell<BTreeSet<SetKey>>>),  // множество скаляров
     Null,
     Class {
         name: String,
         parent: Option<Rc<Value>>,
         fields: Rc<RefCell<HashMap<String, Value>>>,
         methods: HashMap<String, Rc<UserFunction>>,
+        sealed: Rc<Cell<bool>>,          // после seal() поля класса не меняются, а сам он не переопределяется
     },
     Instance {
         class: Rc<Value>,
         fields: Rc<RefCell<HashMap<String, Value>>>,
     },
     Method(Rc<UserFunction>, Rc<Value>), // метод, связанный с экземпляром или классом
+    Function(Rc<UserFunction>),          // пользовательская функция как значение
+    Builtin(BuiltinFn),                  // встроенная (нативная) функция как значение
     Dll(Rc<libloading::Library>),
 }
 
+/// Entries of a Forge map. `map()` iterates its keys in sorted order;
+/// `ordered_map()` remembers insertion order instead (re-setting an existing
+/// key keeps its position).
+#[derive(Clone, Default)]
+pub struct MapValue {
+    entries: BTreeMap<String, Value>,
+    order: Option<Vec<String>>,
+}
+
+impl MapValue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ordered() -> Self {
+        Self { entries: BTreeMap::new(), order: Some(Vec::new()) }
+    }
+
+    pub fn is_ordered(&self) -> bool {
+        self.order.is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.get(key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        if let Some(order) = &mut self.order {
+            if !self.entries.contains_key(&key) {
+                order.push(key.clone());
+            }
+        }
+        self.entries.insert(key, value)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        let removed = self.entries.remove(key);
+        if let (Some(order), Some(_)) = (&mut self.order, &removed) {
+            order.retain(|k| k != key);
+        }
+        removed
+    }
+
+    /// Keys in iteration order (sorted, or insertion order for ordered maps).
+    pub fn keys(&self) -> Vec<String> {
+        match &self.order {
+            Some(order) => order.clone(),
+            None => self.entries.keys().cloned().collect(),
+        }
+    }
+
+    /// Entries in iteration order.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (&String, &Value)> + '_> {
+        match &self.order {
+            Some(order) => Box::new(order.iter().map(move |k| (k, &self.entries[k]))),
+            None => Box::new(self.entries.iter()),
+        }
+    }
+}
+
+impl From<BTreeMap<String, Value>> for MapValue {
+    fn from(entries: BTreeMap<String, Value>) -> Self {
+        Self { entries, order: None }
+    }
+}
+
+/// Storage behind `Value::Array`: the elements and the `freeze` flag.
+/// Reads go through `borrow`; writes only through `array_mut`, which checks the flag.
+pub struct ArrayCell {
+    items: RefCell<Vec<Value>>,
+    frozen: Cell<bool>,
+}
+
+impl ArrayCell {
+    pub fn new(items: Vec<Value>) -> Self {
+        Self { items: RefCell::new(items), frozen: Cell::new(false) }
+    }
+
+    pub fn borrow(&self) -> Ref<'_, Vec<Value>> {
+        self.items.borrow()
+    }
+}
+
+/// Mark `arr` read-only: builtins that modify arrays in place refuse to touch it.
+/// Freezing is shallow; nested arrays stay mutable unless frozen themselves.
+pub fn freeze_array(arr: &Rc<ArrayCell>) {
+    arr.frozen.set(true);
+}
+
+pub fn is_frozen(arr: &Rc<ArrayCell>) -> bool {
+    arr.frozen.get()
+}
+
+/// Mutable access to an array for builtin `name`, or an error if it is frozen.
+pub fn array_mut<'a>(name: &str, arr: &'a Rc<ArrayCell>) -> Result<RefMut<'a, Vec<Value>>, String> {
+    if is_frozen(arr) {
+        return Err(format!("{}: array is frozen", name));
+    }
+    Ok(arr.items.borrow_mut())
+}
+
+/// Element of a set: a scalar value. Numbers are normalised so that `3` and
+/// `3.0` are the same element; NaN and non-scalars are rejected.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SetKey {
+    Null,
+    Boolean(bool),
+    Int(i64),
+    Float(FloatKey),
+    String(String),
+}
+
+/// A non-NaN float with a total order, for use in `SetKey`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct FloatKey(f64);
+
+impl Eq for FloatKey {}
+
+// NaN never gets here and -0.0 becomes Int(0), so equal keys have equal bits
+impl std::hash::Hash for FloatKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl PartialOrd for FloatKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FloatKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl SetKey {
+    pub fn from_value(value: &Value) -> Result<SetKey, String> {
+        match value {
+            Value::Null => Ok(SetKey::Null),
+            Value::Boolean(b) => Ok(SetKey::Boolean(*b)),
+            Value::Int(n) => Ok(SetKey::Int(*n)),
+            Value::Number(n) if n.is_nan() => Err("NaN cannot be a set element".to_string()),
+            Value::Number(n) if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 => Ok(SetKey::Int(*n as i64)),
+            // -0.0 и 0.0 равны, поэтому попадают в ветку выше как Int(0)
+            Value::Number(n) => Ok(SetKey::Float(FloatKey(*n))),
+            Value::String(s) => Ok(SetKey::String(s.clone())),
+            other => Err(format!("{} cannot be a set element (only scalars can)", other.type_name())),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            SetKey::Null => Value::Null,
+            SetKey::Boolean(b) => Value::Boolean(*b),
+            SetKey::Int(n) => Value::Int(*n),
+            SetKey::Float(f) => Value::Number(f.0),
+            SetKey::String(s) => Value::String(s.clone()),
+        }
+    }
+}
+
+/// Numbers follow IEEE 754: `NaN` is not equal to anything, itself included,
+/// and `Int` equals `Number` when the float has the same value (`3 == 3.0`).
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Int(a), Value::Number(b)) | (Value::Number(b), Value::Int(a)) => *a as f64 == *b,
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
             (Value::Array(a), Value::Array(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            (Value::Set(a), Value::Set(b)) => Rc::ptr_eq(a, b),
             (Value::Null, Value::Null) => true,
             (Value::Class { name, .. }, Value::Class { name: name2, .. }) => name == name2,
             (Value::Instance { class, fields }, Value::Instance { class: class2, fields: fields2 }) => {
                 Rc::ptr_eq(class, class2) && Rc::ptr_eq(fields, fields2)
             }
             (Value::Method(f, o), Value::Method(f2, o2)) => Rc::ptr_eq(f, f2) && Rc::ptr_eq(o, o2),
+            (Value::Function(f), Value::Function(f2)) => Rc::ptr_eq(f, f2),
+            (Value::Builtin(f), Value::Builtin(f2)) => Rc::ptr_eq(f, f2),
             (Value::Dll(l), Value::Dll(l2)) => Rc::ptr_eq(l, l2),
             _ => false,
         }
@@ -49,30 +239,193 @@ impl Value {
         match self {
             Value::Boolean(b) => *b,
             Value::Number(n) => *n != 0.0,
+            Value::Int(n) => *n != 0,
             Value::String(s) => !s.is_empty(),
             Value::Array(arr) => !arr.borrow().is_empty(),
+            Value::Map(map) => !map.borrow().is_empty(),
+            Value::Set(set) => !set.borrow().is_empty(),
             Value::Null => false,
             Value::Class { .. } => true,
             Value::Instance { .. } => true,
             Value::Method(..) => true,
+            Value::Function(_) | Value::Builtin(_) => true,
             Value::Dll(..) => true,
         }
     }
 
+    /// Numeric value as `f64` (`Int` is converted, possibly losing precision past 2^53).
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Int(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    /// Numeric value as `i64`; floats are truncated toward zero.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Number(n) => Some(*n as i64),
+            Value::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
     pub fn type_name(&self) -> &'static str {
         match self {
-            Value::Number(_) => "number",
+            Value::Number(_) | Value::Int(_) => "number",
             Value::String(_) => "string",
             Value::Boolean(_) => "boolean",
             Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Set(_) => "set",
             Value::Null => "null",
             Value::Class { .. } => "class",
             Value::Instance { .. } => "instance",
             Value::Method(..) => "method",
+            Value::Function(_) | Value::Builtin(_) => "function",
             Value::Dll(_) => "dll",
         }
     }
 
+    /// Structural equality: arrays, maps and sets compare element-wise, everything else as `==`.
+    /// Self-referencing containers are handled by treating a pair already under comparison as equal.
+    pub fn deep_eq(&self, other: &Value) -> bool {
+        fn inner(a: &Value, b: &Value, seen: &mut Vec<(usize, usize)>) -> bool {
+            match (a, b) {
+                (Value::Array(x), Value::Array(y)) => {
+                    if Rc::ptr_eq(x, y) {
+                        return true;
+                    }
+                    let key = (Rc::as_ptr(x) as usize, Rc::as_ptr(y) as usize);
+                    if seen.contains(&key) {
+                        return true;
+                    }
+                    let (xs, ys) = (x.borrow(), y.borrow());
+                    if xs.len() != ys.len() {
+                        return false;
+                    }
+                    seen.push(key);
+                    let equal = xs.iter().zip(ys.iter()).all(|(l, r)| inner(l, r, seen));
+                    seen.pop();
+                    equal
+                }
+                (Value::Map(x), Value::Map(y)) => {
+                    if Rc::ptr_eq(x, y) {
+                        return true;
+                    }
+                    let key = (Rc::as_ptr(x) as usize, Rc::as_ptr(y) as usize);
+                    if seen.contains(&key) {
+                        return true;
+                    }
+                    let (xs, ys) = (x.borrow(), y.borrow());
+                    if xs.len() != ys.len() {
+                        return false;
+                    }
+                    seen.push(key);
+                    // Равенство по содержимому: порядок ключей ordered_map не учитывается
+                    let equal = xs.iter().all(|(k, lv)| ys.get(k).is_some_and(|rv| inner(lv, rv, seen)));
+                    seen.pop();
+                    equal
+                }
+                (Value::Set(x), Value::Set(y)) => *x.borrow() == *y.borrow(),
+                _ => a == b,
+            }
+        }
+        inner(self, other, &mut Vec::new())
+    }
+
+    /// Multi-line debug form used by `dump`: strings are quoted, containers are
+    /// indented by two spaces per level, instances carry their class name.
+    /// Containers already being printed (cycles) are shown as `[...]` / `{...}`.
+    pub fn pretty(&self) -> String {
+        fn quote(s: &str) -> String {
+            format!("{:?}", s)
+        }
+        fn fields_block(
+            entries: Vec<(String, Value)>,
+            indent: usize,
+            seen: &mut Vec<usize>,
+            out: &mut String,
+        ) {
+            if entries.is_empty() {
+                out.push_str("{}");
+                return;
+            }
+            out.push_str("{\n");
+            let count = entries.len();
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                out.push_str(&"  ".repeat(indent + 1));
+                out.push_str(&quote(&k));
+                out.push_str(": ");
+                inner(&v, indent + 1, seen, out);
+                out.push_str(if i + 1 < count { ",\n" } else { "\n" });
+            }
+            out.push_str(&"  ".repeat(indent));
+            out.push('}');
+        }
+        fn inner(v: &Value, indent: usize, seen: &mut Vec<usize>, out: &mut String) {
+            match v {
+                Value::String(s) => out.push_str(&quote(s)),
+                Value::Array(arr) => {
+                    let ptr = Rc::as_ptr(arr) as usize;
+                    let items = arr.borrow();
+                    if seen.contains(&ptr) {
+                        out.push_str("[...]");
+                        return;
+                    }
+                    if items.is_empty() {
+                        out.push_str("[]");
+                        return;
+                    }
+                    seen.push(ptr);
+                    out.push_str("[\n");
+                    for (i, item) in items.iter().enumerate() {
+                        out.push_str(&"  ".repeat(indent + 1));
+                        inner(item, indent + 1, seen, out);
+                        out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                    }
+                    out.push_str(&"  ".repeat(indent));
+                    out.push(']');
+                    seen.pop();
+                }
+                Value::Map(map) => {
+                    let ptr = Rc::as_ptr(map) as usize;
+                    if seen.contains(&ptr) {
+                        out.push_str("{...}");
+                        return;
+                    }
+                    seen.push(ptr);
+                    let entries = map.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                    fields_block(entries, indent, seen, out);
+                    seen.pop();
+                }
+                Value::Instance { class, fields } => {
+                    let name = match &**class {
+                        Value::Class { name, .. } => name.as_str(),
+                        _ => "?",
+                    };
+                    out.push_str(&format!("<instance {}> ", name));
+                    let ptr = Rc::as_ptr(fields) as usize;
+                    if seen.contains(&ptr) {
+                        out.push_str("{...}");
+                        return;
+                    }
+                    seen.push(ptr);
+                    let mut entries: Vec<(String, Value)> =
+                        fields.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                    entries.sort_by(|a, b| a.0.cmp(&b.0));
+                    fields_block(entries, indent, seen, out);
+                    seen.pop();
+                }
+                other => out.push_str(&other.to_string()),
+            }
+        }
+        let mut out = String::new();
+        inner(self, 0, &mut Vec::new(), &mut out);
+        out
+    }
+
     pub fn get_attr(&self, attr: &str) -> Option<Value> {
         match self {
             Value::Instance { class, fields } => {
@@ -84,7 +437,8 @@ impl Value {
                         return Some(val);
                     }
                     if let Some(m) = methods.get(attr) {
-                        return Some(Value::Method(Rc::clone(m), Rc::clone(class)));
+                        // Метод связывается с самим экземпляром, чтобы `m = obj.f` можно было вызвать позже
+                        return Some(Value::Method(Rc::clone(m), Rc::new(self.clone())));
                     }
                 }
                 None
@@ -108,7 +462,10 @@ impl Value {
                 fields.borrow_mut().insert(attr, value);
                 Ok(())
             }
-            Value::Class { fields, .. } => {
+            Value::Class { name, fields, sealed, .. } => {
+                if sealed.get() {
+                    return Err(format!("Cannot set '{}' on sealed class {}", attr, name));
+                }
                 fields.borrow_mut().insert(attr, value);
                 Ok(())
             }
@@ -116,24 +473,66 @@ impl Value {
         }
     }
 
+    /// Copy of a class field default for a new instance. Arrays and maps are
+    /// duplicated all the way down, so instances never share mutable state
+    /// through a default; structure shared inside the default (cycles
+    /// included) stays shared inside the copy. Frozen arrays are read-only and
+    /// are kept as they are.
+    pub fn copy_default(&self) -> Value {
+        fn copy(val: &Value, copies: &mut HashMap<usize, Value>) -> Value {
+            match val {
+                Value::Array(arr) if !is_frozen(arr) => {
+                    let ptr = Rc::as_ptr(arr) as usize;
+                    if let Some(done) = copies.get(&ptr) {
+                        return done.clone();
+                    }
+                    let new = Rc::new(ArrayCell::new(Vec::new()));
+                    copies.insert(ptr, Value::Array(Rc::clone(&new)));
+                    let items: Vec<Value> = arr.borrow().iter().map(|v| copy(v, copies)).collect();
+                    *new.items.borrow_mut() = items;
+                    Value::Array(new)
+                }
+                Value::Map(map) => {
+                    let ptr = Rc::as_ptr(map) as usize;
+                    if let Some(done) = copies.get(&ptr) {
+                        return done.clone();
+                    }
+                    // Клон сохраняет порядок ordered_map, значения заменяются копиями на месте
+                    let new = Rc::new(RefCell::new(map.borrow().clone()));
+                    copies.insert(ptr, Value::Map(Rc::clone(&new)));
+                    let entries: Vec<(String, Value)> =
+                        map.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                    for (key, item) in entries {
+                        let item = copy(&item, copies);
+                        new.borrow_mut().insert(key, item);
+                    }
+                    Value::Map(new)
+                }
+                Value::Set(set) => Value::Set(Rc::new(RefCell::new(set.borrow().clone()))),
+                other => other.clone(),
+            }
+        }
+        copy(self, &mut HashMap::new())
+    }
+
     pub async fn call_as_class(&self, args: Vec<Value>, env: &mut crate::env::Env) -> Result<Value, String> {
         match self {
-            Value::Class { name, parent, fields, methods } => {
+            Value::Class { methods, fields: class_fields, .. } => {
+                // Каждый экземпляр получает свои копии полей класса, см. copy_default
+                let fields = class_fields
+                    .borrow()
+                    .iter()
+                    .map(|(name, val)| (name.clone(), val.copy_default()))
+                    .collect();
                 let instance = Value::Instance {
                     class: Rc::new(self.clone()),
-                    fields: Rc::new(RefCell::new(HashMap::new())),
+                    fields: Rc::new(RefCell::new(fields)),
                 };
                 if let Some(init) = methods.get("__init__") {
-                    let mut call_args = vec![instance.clone()];
-                    call_args.extend(args);
-                    if call_args.len() != init.params.len() {
-                        return Err(format!("Constructor __init__ expects {} arguments, got {}", init.params.len(), call_args.len()));
-                    }
                     let mut local_env = env.child();
-                    for (p, v) in init.params.iter().zip(call_args) {
-                        local_env.set_var(p.clone(), v);
-                    }
-                    crate::eval::eval_block(&init.body, &mut local_env).await?;
+                    local_env.enter_call()?;
+                    crate::eval::bind_method_args("Constructor", init, instance.clone(), args, &mut local_env)?;
+                    crate::eval::returned_value(crate::eval::eval_block(&init.body, &mut local_env).await?)?;
                 }
                 Ok(instance)
             }
@@ -146,6 +545,7 @@ impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Value::Number(n) => write!(f, "{}", n),
+            Value::Int(n) => write!(f, "{}", n),
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Array(arr) => {
@@ -153,6 +553,20 @@ impl fmt::Display for Value {
                 let elems: Vec<String> = arr.iter().map(|v| format!("{}", v)).collect();
                 write!(f, "[{}]", elems.join(", "))
             }
+            Value::Map(map) => {
+                let map = map.borrow();
+                let entries: Vec<String> = map.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Value::Set(set) => {
+                let set = set.borrow();
+                if set.is_empty() {
+                    // "{}" уже занято пустым словарём
+                    return write!(f, "set_of()");
+                }
+                let elems: Vec<String> = set.iter().map(|k| format!("{}", k.to_value())).collect();
+                write!(f, "{{{}}}", elems.join(", "))
+            }
             Value::Null => write!(f, "null"),
             Value::Class { name, .. } => write!(f, "<class {}>", name),
             Value::Instance { class, .. } => {
@@ -163,6 +577,8 @@ impl fmt::Display for Value {
                 }
             }
             Value::Method(_, _) => write!(f, "<method>"),
+            Value::Function(func) => write!(f, "<function {}>", func.name),
+            Value::Builtin(_) => write!(f, "<builtin>"),
             Value::Dll(_) => write!(f, "<dll>"),
         }
     }
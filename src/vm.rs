@@ -0,0 +1,344 @@
+// vm.rs – stack-based bytecode for hot loops (enabled with `--vm`).
+//
+// `compile_program` replaces every `while`/`for` loop whose body stays inside
+// the pure subset (literals, variables, arithmetic, assignment, if/while/for,
+// print) with `Stmt::Compiled`. Such loops run in a synchronous VM without a
+// boxed future per node. Anything else – calls, returns, attribute access,
+// indexing – keeps the loop on the tree-walking evaluator, so async builtins
+// are never reached from the VM. Operators are the same `eval::binary_op` /
+// `eval::unary_op` used by the evaluator, so results are identical. The only
+// ways back into user code are instances: printing one (`__str__`) or using
+// it as an operand (`__add__`, ...). The synchronous loop then pauses and
+// `execute` makes the call on the evaluator before resuming.
+
+use std::rc::Rc;
+use serde::{Deserialize, Serialize};
+use crate::ast::{BinaryOpKind, Expr, Stmt, UnaryOpKind};
+use crate::env::Env;
+use crate::symbol::Symbol;
+use crate::eval::{apply_binary, apply_unary, binary_op, display_string, lookup_name, unary_op, ForRange};
+use crate::value::Value;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Const {
+    Number(f64),
+    Int(i64),
+    String(String),
+    Boolean(bool),
+    Null,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Op {
+    Push(Const),
+    Load(Symbol),
+    Store(Symbol),
+    Pop,
+    Binary(BinaryOpKind),
+    Unary(UnaryOpKind),
+    Jump(usize),
+    JumpIfFalse(usize),       // снимает условие со стека
+    Print(usize),             // печатает N верхних значений через пробел
+    ForInit,                  // start, end, step -> счётчик цикла
+    ForNext(Symbol, usize),   // следующий шаг или переход на выход
+    EnterScope,               // начало тела if/while/for (см. Env::enter_scope)
+    ExitScope,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+}
+
+/// Compile eligible loops in place, including loops inside function and method bodies.
+pub fn compile_program(stmts: &mut [Stmt]) {
+    for stmt in stmts.iter_mut() {
+        match stmt {
+            Stmt::While { .. } | Stmt::For { .. } => {
+                let mut code = Vec::new();
+                if compile_stmt(stmt, &mut code) {
+                    let original = Box::new(stmt.clone());
+                    *stmt = Stmt::Compiled { chunk: Rc::new(Chunk { code }), original };
+                    continue;
+                }
+                match stmt {
+                    Stmt::While { body, .. } | Stmt::For { body, .. } => compile_program(body),
+                    _ => unreachable!(),
+                }
+            }
+            Stmt::If { then_branch, elif_branches, else_branch, .. } => {
+                compile_program(then_branch);
+                for (_, body) in elif_branches {
+                    compile_program(body);
+                }
+                if let Some(body) = else_branch {
+                    compile_program(body);
+                }
+            }
+            Stmt::ForIn { body, .. } | Stmt::FunctionDef { body, .. } => compile_program(body),
+            Stmt::TryCatch { try_body, catch_body } => {
+                compile_program(try_body);
+                compile_program(catch_body);
+            }
+            Stmt::ClassDef { methods, .. } => methods.iter_mut().for_each(|m| compile_program(&mut m.body)),
+            _ => {}
+        }
+    }
+}
+
+/// Append code for an if/while/for body wrapped in its own block scope.
+fn compile_body(body: &[Stmt], code: &mut Vec<Op>) -> bool {
+    code.push(Op::EnterScope);
+    if !body.iter().all(|s| compile_stmt(s, code)) {
+        return false;
+    }
+    code.push(Op::ExitScope);
+    true
+}
+
+/// Append code for `stmt`; returns false if it is outside the compilable subset.
+fn compile_stmt(stmt: &Stmt, code: &mut Vec<Op>) -> bool {
+    match stmt {
+        Stmt::Line(_) => true,
+        Stmt::Expr(expr) => {
+            if !compile_expr(expr, code) {
+                return false;
+            }
+            code.push(Op::Pop);
+            true
+        }
+        Stmt::Assign { name, value } => {
+            if !compile_expr(value, code) {
+                return false;
+            }
+            code.push(Op::Store(*name));
+            true
+        }
+        Stmt::Print(exprs) => {
+            if !exprs.iter().all(|e| compile_expr(e, code)) {
+                return false;
+            }
+            code.push(Op::Print(exprs.len()));
+            true
+        }
+        Stmt::If { condition, then_branch, elif_branches, else_branch } => {
+            let mut exits = Vec::new();
+            let branches = std::iter::once((condition, then_branch))
+                .chain(elif_branches.iter().map(|(c, b)| (c, b)));
+            for (cond, body) in branches {
+                if !compile_expr(cond, code) {
+                    return false;
+                }
+                let skip = code.len();
+                code.push(Op::JumpIfFalse(0));
+                if !compile_body(body, code) {
+                    return false;
+                }
+                exits.push(code.len());
+                code.push(Op::Jump(0));
+                code[skip] = Op::JumpIfFalse(code.len());
+            }
+            if let Some(body) = else_branch {
+                if !compile_body(body, code) {
+                    return false;
+                }
+            }
+            let end = code.len();
+            for at in exits {
+                code[at] = Op::Jump(end);
+            }
+            true
+        }
+        Stmt::While { condition, body, .. } => {
+            let top = code.len();
+            if !compile_expr(condition, code) {
+                return false;
+            }
+            let exit = code.len();
+            code.push(Op::JumpIfFalse(0));
+            if !compile_body(body, code) {
+                return false;
+            }
+            code.push(Op::Jump(top));
+            code[exit] = Op::JumpIfFalse(code.len());
+            true
+        }
+        Stmt::For { var, start, end, step, body, .. } => {
+            if !compile_expr(start, code) || !compile_expr(end, code) {
+                return false;
+            }
+            match step {
+                Some(step) if !compile_expr(step, code) => return false,
+                Some(_) => {}
+                None => code.push(Op::Push(Const::Int(1))),
+            }
+            code.push(Op::ForInit);
+            let top = code.len();
+            code.push(Op::ForNext(*var, 0));
+            if !compile_body(body, code) {
+                return false;
+            }
+            code.push(Op::Jump(top));
+            code[top] = Op::ForNext(*var, code.len());
+            true
+        }
+        _ => false,
+    }
+}
+
+fn compile_expr(expr: &Expr, code: &mut Vec<Op>) -> bool {
+    match expr {
+        Expr::Number(n) => code.push(Op::Push(Const::Number(*n))),
+        Expr::Int(n) => code.push(Op::Push(Const::Int(*n))),
+        Expr::String(s) => code.push(Op::Push(Const::String(s.clone()))),
+        Expr::Boolean(b) => code.push(Op::Push(Const::Boolean(*b))),
+        Expr::Null => code.push(Op::Push(Const::Null)),
+        Expr::Variable(name) => code.push(Op::Load(*name)),
+        Expr::BinaryOp { left, op, right } => {
+            if !compile_expr(left, code) || !compile_expr(right, code) {
+                return false;
+            }
+            code.push(Op::Binary(*op));
+        }
+        Expr::UnaryOp { op, expr } => {
+            if !compile_expr(expr, code) {
+                return false;
+            }
+            code.push(Op::Unary(*op));
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Execution state, kept across the pauses of `run`.
+#[derive(Default)]
+struct Machine {
+    stack: Vec<Value>,
+    loops: Vec<ForRange>,
+    scopes: Vec<usize>,
+    pc: usize,
+}
+
+/// Step that needs the async evaluator because it involves an instance.
+enum Pause {
+    Print(Vec<Value>),
+    Binary(BinaryOpKind, Value, Value),
+    Unary(UnaryOpKind, Value),
+}
+
+/// Run a compiled chunk against `env`.
+pub async fn execute(chunk: &Chunk, env: &mut Env) -> Result<(), String> {
+    let mut machine = Machine::default();
+    // Цикл интерпретации синхронный; сюда он возвращается только ради методов экземпляров
+    while let Some(pause) = run(chunk, env, &mut machine)? {
+        match pause {
+            Pause::Print(values) => {
+                let mut parts = Vec::with_capacity(values.len());
+                for v in &values {
+                    parts.push(display_string(v, env).await?);
+                }
+                env.print_line(&parts.join(" "))?;
+            }
+            Pause::Binary(op, left, right) => {
+                let result = apply_binary(op, left, right, env).await?;
+                machine.stack.push(result);
+            }
+            Pause::Unary(op, val) => {
+                let result = apply_unary(op, val, env).await?;
+                machine.stack.push(result);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run until the end of the chunk (`None`) or until a step that involves an
+/// instance, which is returned for `execute`; `pc` is already past it.
+fn run(chunk: &Chunk, env: &mut Env, machine: &mut Machine) -> Result<Option<Pause>, String> {
+    let Machine { stack, loops, scopes, pc } = machine;
+    while *pc < chunk.code.len() {
+        env.count_step()?;
+        match &chunk.code[*pc] {
+            Op::Push(c) => stack.push(match c {
+                Const::Number(n) => Value::Number(*n),
+                Const::Int(n) => Value::Int(*n),
+                Const::String(s) => Value::String(s.clone()),
+                Const::Boolean(b) => Value::Boolean(*b),
+                Const::Null => Value::Null,
+            }),
+            Op::Load(name) => stack.push(lookup_name(*name, env)?),
+            Op::Store(name) => {
+                let val = pop(stack)?;
+                env.set_var(*name, val);
+            }
+            Op::Pop => {
+                pop(stack)?;
+            }
+            Op::Binary(op) => {
+                let right = pop(stack)?;
+                let left = pop(stack)?;
+                if matches!(left, Value::Instance { .. }) || matches!(right, Value::Instance { .. }) {
+                    *pc += 1;
+                    return Ok(Some(Pause::Binary(*op, left, right)));
+                }
+                stack.push(binary_op(*op, &left, &right)?);
+            }
+            Op::Unary(op) => {
+                let val = pop(stack)?;
+                if let Value::Instance { .. } = val {
+                    *pc += 1;
+                    return Ok(Some(Pause::Unary(*op, val)));
+                }
+                stack.push(unary_op(*op, val)?);
+            }
+            Op::Jump(target) => {
+                *pc = *target;
+                continue;
+            }
+            Op::JumpIfFalse(target) => {
+                if !pop(stack)?.as_bool() {
+                    *pc = *target;
+                    continue;
+                }
+            }
+            Op::Print(n) => {
+                let values = stack.split_off(stack.len().saturating_sub(*n));
+                if values.iter().any(|v| matches!(v, Value::Instance { .. })) {
+                    *pc += 1;
+                    return Ok(Some(Pause::Print(values)));
+                }
+                let parts: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                env.print_line(&parts.join(" "))?;
+            }
+            Op::ForInit => {
+                let step = pop(stack)?;
+                let end = pop(stack)?;
+                let start = pop(stack)?;
+                loops.push(ForRange::new(&start, &end, &step)?);
+            }
+            Op::ForNext(var, exit) => {
+                let range = loops.last_mut().ok_or("vm: for loop state missing")?;
+                match range.next() {
+                    Some(i) => env.set_var(*var, Value::Int(i)),
+                    None => {
+                        loops.pop();
+                        *pc = *exit;
+                        continue;
+                    }
+                }
+            }
+            Op::EnterScope => scopes.push(env.enter_scope()),
+            Op::ExitScope => {
+                let mark = scopes.pop().ok_or("vm: scope state missing")?;
+                env.exit_scope(mark);
+            }
+        }
+        *pc += 1;
+    }
+    Ok(None)
+}
+
+fn pop(stack: &mut Vec<Value>) -> Result<Value, String> {
+    stack.pop().ok_or_else(|| "vm: stack underflow".to_string())
+}
@@ -0,0 +1,109 @@
+use forge_interpreter::{Interpreter, Value};
+
+#[tokio::test(flavor = "current_thread")]
+async fn run_script_and_read_variable() {
+    let mut interp = Interpreter::new();
+    interp.run("x = 1 + 2\nname = \"forge\"").await.unwrap();
+    assert!(matches!(interp.get_var("x"), Some(Value::Int(3))));
+    assert!(matches!(interp.get_var("name"), Some(Value::String(s)) if s == "forge"));
+    assert!(interp.get_var("missing").is_none());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn state_persists_across_runs() {
+    let mut interp = Interpreter::new();
+    interp.run("function double(n):\n    return n * 2\ny = 5").await.unwrap();
+    interp.run("y = double(y)").await.unwrap();
+    assert!(matches!(interp.get_var("y"), Some(Value::Int(10))));
+    assert!(matches!(interp.eval_expr_str("y + 1").await, Ok(Value::Int(11))));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn set_var_is_visible_to_the_script() {
+    let mut interp = Interpreter::new();
+    interp.set_var("limit", Value::Int(4));
+    interp.run("z = limit * limit").await.unwrap();
+    assert!(matches!(interp.get_var("z"), Some(Value::Int(16))));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn script_errors_are_returned() {
+    let mut interp = Interpreter::new();
+    let Err(err) = interp.run("x = 1 / 0").await else {
+        panic!("division by zero succeeded");
+    };
+    assert!(err.contains("Division by zero"), "{}", err);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn step_limit_applies_to_each_run() {
+    let script = "total = 0\nfor i = 1, 20 do\n    total = total + i";
+    let mut interp = Interpreter::new();
+    interp.env_mut().set_max_steps(Some(u64::MAX));
+    interp.run(script).await.unwrap();
+    let one_run = interp.env().steps_taken();
+    // enough for one run, not for three
+    interp.env_mut().set_max_steps(Some(one_run + one_run / 2));
+    for _ in 0..3 {
+        interp.run(script).await.unwrap();
+    }
+    assert!(matches!(interp.get_var("total"), Some(Value::Int(210))));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn register_fn_round_trip() {
+    let mut interp = Interpreter::new();
+    interp.register_fn("host_add", |args| match args.as_slice() {
+        [Value::Int(a), Value::Int(b)] => Ok(Value::Int(a + b)),
+        _ => Err("host_add expects 2 integers".to_string()),
+    });
+    interp.run("r = host_add(40, 2)").await.unwrap();
+    assert!(matches!(interp.get_var("r"), Some(Value::Int(42))));
+    let Err(err) = interp.run("host_add(\"x\", 1)").await else {
+        panic!("host_add accepted a string");
+    };
+    assert!(err.contains("host_add expects 2 integers"), "{}", err);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn names_resolve_at_runtime() {
+    let mut interp = Interpreter::new();
+    // a name the parser has never seen is interned on first use
+    interp.set_var("host_only_name_1164", Value::Int(7));
+    assert!(matches!(interp.get_var("host_only_name_1164"), Some(Value::Int(7))));
+    interp.run("copy = host_only_name_1164 * 2").await.unwrap();
+    assert!(matches!(interp.get_var("copy"), Some(Value::Int(14))));
+    assert!(interp.get_var("never_defined_anywhere_1164").is_none());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn output_sink_captures_everything_the_script_prints() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    let mut interp = Interpreter::new();
+    let buf = Rc::new(RefCell::new(Vec::<u8>::new()));
+    interp.set_output(buf.clone());
+    interp
+        .run("print(\"a\", 1)\nwrite_out(\"b\")\neprint(\"c\")\ndump(array(1))")
+        .await
+        .unwrap();
+    assert_eq!(String::from_utf8(buf.borrow().clone()).unwrap(), "a 1\nbc\n[\n  1\n]\n");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn eval_expr_str_can_spawn_tasks() {
+    let mut interp = Interpreter::new();
+    interp.run("function answer():\n    return 42").await.unwrap();
+    assert!(matches!(interp.eval_expr_str("join_task(spawn(answer))").await, Ok(Value::Int(42))));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn output_sink_captures_input_prompts() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    let mut interp = Interpreter::new();
+    let buf = Rc::new(RefCell::new(Vec::<u8>::new()));
+    interp.set_output(buf.clone());
+    interp.run("print(\"start\")\nx = input_timeout(\"name? \", 0)").await.unwrap();
+    assert_eq!(String::from_utf8(buf.borrow().clone()).unwrap(), "start\nname? ");
+}
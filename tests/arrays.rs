@@ -0,0 +1,222 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn sort_by_orders_instances_by_key() {
+    let out = output(r#"
+        class Person:
+            name = ""
+            age = 0
+            function __init__(name, age):
+                self.name = name
+                self.age = age
+        function by_age(p):
+            return p.age
+        people = array(Person("Ann", 41), Person("Bob", 19), Person("Cid", 30))
+        for p in sort_by(people, by_age):
+            print(p.name)
+    "#);
+    assert_eq!(out, "Bob\nCid\nAnn\n");
+}
+
+#[test]
+fn sort_by_rejects_nan_keys_catchably() {
+    let out = output(r#"
+        function key(x):
+            if x == 2:
+                return tonumber("NaN")
+            return x
+        result = "sorted"
+        try:
+            sorted = sort_by(array(3, 2, 1, 5, 4, 9, 8, 7, 6, 10, 12, 11, 13, 15, 14, 16, 17, 20, 19, 18, 21), key)
+        catch:
+            result = "caught"
+        print(result)
+    "#);
+    assert_eq!(out, "caught\n");
+    let err = error(r#"
+        function key(x):
+            return tonumber("NaN")
+        sort_by(array(1, 2), key)
+    "#);
+    assert!(err.contains("NaN"), "{}", err);
+}
+
+#[test]
+fn set_grow_extends_with_nulls() {
+    let out = output(r#"
+        a = array(1, 2)
+        set_grow(a, 5, "x")
+        print(a)
+    "#);
+    assert_eq!(out, "[1, 2, null, null, null, x]\n");
+}
+
+#[test]
+fn set_grow_in_bounds_overwrites() {
+    let out = output(r#"
+        a = array(1, 2, 3)
+        set_grow(a, 1, 20)
+        print(a)
+    "#);
+    assert_eq!(out, "[1, 20, 3]\n");
+}
+
+#[test]
+fn set_grow_rejects_huge_indices() {
+    assert!(error("a = array()\nset_grow(a, 10000000000, 1)").contains("exceeds the limit"));
+}
+
+#[test]
+fn fill_and_resize_reject_huge_lengths() {
+    assert!(error("fill(0, 10000000000)").contains("fill: array length"));
+    assert!(error("a = array(1)\nresize(a, 10000000000, 0)").contains("resize: array length"));
+    assert_eq!(output("a = array(1)\nresize(a, 3, 0)\nprint(a)\nprint(fill(7, 2))"), "[1, 0, 0]\n[7, 7]\n");
+}
+
+#[test]
+fn unique_keeps_first_occurrence_order() {
+    let out = output(r#"
+        print(unique(array(3, 1, 3, 2, 1)))
+        print(unique(array("b", "a", "b")))
+        print(length(unique(array(1, 1.0, "1"))))
+    "#);
+    assert_eq!(out, "[3, 1, 2]\n[b, a]\n2\n");
+}
+
+#[test]
+fn unique_mixes_scalars_and_containers() {
+    let out = output(r#"
+        nan = tonumber("NaN")
+        print(unique(array(array(1, 2), 1, array(1, 2), "1", 1.0, array(1), null, null)))
+        print(length(unique(array(nan, nan, 1))))
+        big = array()
+        for i = 1, 20000 do
+            push(big, i % 100)
+        print(length(unique(big)))
+    "#);
+    assert_eq!(out, "[[1, 2], 1, 1, [1], null]\n3\n100\n");
+}
+
+#[test]
+fn group_by_even_and_odd() {
+    let out = output(r#"
+        function parity(x):
+            if x % 2 == 0:
+                return "even"
+            return "odd"
+        groups = group_by(array(1, 2, 3, 4, 5), parity)
+        print(groups["even"])
+        print(groups["odd"])
+    "#);
+    assert_eq!(out, "[2, 4]\n[1, 3, 5]\n");
+}
+
+#[test]
+fn first_last_rest_including_empty_arrays() {
+    let out = output(r#"
+        a = array(1, 2, 3)
+        e = array()
+        print(first(a))
+        print(last(a))
+        print(rest(a))
+        print(first(e))
+        print(last(e))
+        print(rest(e))
+    "#);
+    assert_eq!(out, "1\n3\n[2, 3]\nnull\nnull\n[]\n");
+}
+
+#[test]
+fn take_drop_take_while_drop_while() {
+    let out = output(r#"
+        a = array(1, 2, 3)
+        print(take(a, 2))
+        print(drop(a, 2))
+        print(take(a, 10))
+        print(drop(a, 10))
+        function small(x):
+            return x < 3
+        print(take_while(array(1, 2, 5, 1), small))
+        print(drop_while(array(1, 2, 5, 1), small))
+    "#);
+    assert_eq!(out, "[1, 2]\n[3]\n[1, 2, 3]\n[]\n[1, 2]\n[5, 1]\n");
+}
+
+#[test]
+fn chunk_and_windows() {
+    let out = output(r#"
+        print(chunk(array(1, 2, 3, 4, 5), 2))
+        print(windows(array(1, 2, 3, 4), 3))
+        print(windows(array(1, 2), 3))
+    "#);
+    assert_eq!(out, "[[1, 2], [3, 4], [5]]\n[[1, 2, 3], [2, 3, 4]]\n[]\n");
+}
+
+#[test]
+fn mutating_a_frozen_array_errors() {
+    assert!(error("a = freeze(array(1))\npush(a, 2)").contains("push: array is frozen"));
+    assert!(error("a = freeze(array(1))\na[0] = 2").contains("index assignment: array is frozen"));
+    let out = output(r#"
+        a = deep_freeze(array(1, array(2)))
+        inner = a[1]
+        try:
+            push(inner, 3)
+        catch:
+            print("inner blocked")
+        print(a)
+        print(is_frozen(inner))
+    "#);
+    assert_eq!(out, "inner blocked\n[1, [2]]\ntrue\n");
+}
+
+#[test]
+fn new_arrays_are_not_frozen_after_frozen_ones_are_dropped() {
+    let out = output(r#"
+        thawed = 0
+        for i = 1, 200 do
+            freeze(array(i))
+            a = array(i)
+            push(a, i)
+            if not is_frozen(a):
+                thawed = thawed + 1
+        print(thawed)
+    "#);
+    assert_eq!(out, "200\n");
+}
+
+#[test]
+fn count_if_any_all() {
+    let out = output(r#"
+        function even(x):
+            return x % 2 == 0
+        nums = array(1, 2, 3, 4)
+        print(count_if(nums, even))
+        print(any(nums, even))
+        print(all(nums, even))
+        print(all(array(), even))
+    "#);
+    assert_eq!(out, "2\ntrue\nfalse\ntrue\n");
+}
+
+#[test]
+fn find_index_of_value() {
+    let out = output(r#"
+        print(find_index(array(1, 2, 3), 3))
+        print(find_index(array("a", "b"), "b"))
+        print(find_index(array(1, 2, 3), 9))
+    "#);
+    assert_eq!(out, "2\n1\n-1\n");
+}
+
+#[test]
+fn scan_and_reduce_right() {
+    let out = output(r#"
+        function add(acc, item):
+            return acc + item
+        print(scan(array(1, 2, 3), add, 0))
+        print(reduce_right(array("a", "b", "c"), add, ""))
+    "#);
+    assert_eq!(out, "[1, 3, 6]\ncba\n");
+}
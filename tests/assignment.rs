@@ -0,0 +1,49 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn destructuring_assignment() {
+    let out = output(r#"
+        a, b = array(1, 2)
+        [c, d] = array("x", "y")
+        print(a + b)
+        print(c + d)
+    "#);
+    assert_eq!(out, "3\nxy\n");
+}
+
+#[test]
+fn destructuring_length_mismatch_is_an_error() {
+    let err = error("a, b = array(1, 2, 3)");
+    assert!(err.contains("Cannot unpack 3 values into 2 variables"), "{}", err);
+}
+
+#[test]
+fn swap_with_simultaneous_assignment() {
+    let out = output(r#"
+        x = 1
+        y = 2
+        x, y = y, x
+        print(x)
+        print(y)
+    "#);
+    assert_eq!(out, "2\n1\n");
+}
+
+#[test]
+fn right_side_is_evaluated_before_any_assignment() {
+    let out = output(r#"
+        x = 1
+        function current_x():
+            return x
+        x, z = 10, current_x()
+        print(z)
+        p = 0
+        q = 1
+        for k = 1, 5 do
+            p, q = q, p + q
+        print(p)
+    "#);
+    assert_eq!(out, "1\n5\n");
+}
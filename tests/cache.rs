@@ -0,0 +1,39 @@
+mod common;
+
+use common::block_on;
+use forge_interpreter::cache::{cache_path, load_or_parse};
+use forge_interpreter::Interpreter;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+fn run_cached(script: &Path) -> (String, bool) {
+    let source = std::fs::read_to_string(script).unwrap();
+    let (stmts, cached) = load_or_parse(script, &source).unwrap();
+    let mut interp = Interpreter::new();
+    let out = Rc::new(RefCell::new(Vec::<u8>::new()));
+    interp.set_output(out.clone());
+    block_on(interp.run_parsed(stmts)).unwrap();
+    let text = String::from_utf8(out.borrow().clone()).unwrap();
+    (text, cached)
+}
+
+#[test]
+fn cached_script_gives_identical_results_until_edited() {
+    let script = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cached.forge");
+    let _ = std::fs::remove_file(cache_path(&script));
+    std::fs::write(&script, "function sq(n):\n    return n * n\nfor i = 1, 3 do\n    print(sq(i))\n").unwrap();
+
+    let (first, cached) = run_cached(&script);
+    assert!(!cached);
+    assert!(cache_path(&script).exists());
+    let (second, cached) = run_cached(&script);
+    assert!(cached);
+    assert_eq!(first, "1\n4\n9\n");
+    assert_eq!(second, first);
+
+    std::fs::write(&script, "print(\"edited\")\n").unwrap();
+    let (third, cached) = run_cached(&script);
+    assert!(!cached);
+    assert_eq!(third, "edited\n");
+}
@@ -0,0 +1,271 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn add_dunder_runs_for_plain_variable_operands() {
+    let out = output(r#"
+        class Vec2:
+            x = 0
+            y = 0
+            function __init__(x, y):
+                self.x = x
+                self.y = y
+            function __add__(other):
+                return Vec2(self.x + other.x, self.y + other.y)
+        a = Vec2(1, 2)
+        b = Vec2(3, 4)
+        c = a + b
+        print(c.x, c.y)
+    "#);
+    assert_eq!(out, "4 6\n");
+}
+
+#[test]
+fn add_dunder_gets_a_string_operand() {
+    let out = output(r#"
+        class Tag:
+            function __add__(s):
+                return "tag:" + s
+        t = Tag()
+        print(t + "s")
+    "#);
+    assert_eq!(out, "tag:s\n");
+}
+
+#[test]
+fn instance_without_add_does_not_concatenate() {
+    let err = error(r#"
+        class Plain:
+            x = 1
+        p = Plain()
+        s = p + "s"
+    "#);
+    assert!(err.contains("Invalid operands for +"), "{}", err);
+}
+
+#[test]
+fn method_stored_in_a_variable_stays_bound() {
+    let out = output(r#"
+        class Counter:
+            n = 0
+            function inc():
+                self.n = self.n + 1
+                return self.n
+        c = Counter()
+        f = c.inc
+        f()
+        print(f())
+        print(c.n)
+    "#);
+    assert_eq!(out, "2\n2\n");
+}
+
+#[test]
+fn explicit_and_implicit_self() {
+    let out = output(r#"
+        class Rect:
+            w = 0
+            h = 0
+            function __init__(self, w, h):
+                self.w = w
+                this.h = h
+            function area(self):
+                return self.w * self.h
+            function scaled(k):
+                return this.w * k
+        r = Rect(2, 3)
+        print(r.area())
+        print(r.scaled(5))
+    "#);
+    assert_eq!(out, "6\n10\n");
+}
+
+#[test]
+fn static_factory_method() {
+    let out = output(r#"
+        class Rect:
+            w = 0
+            h = 0
+            function __init__(w, h):
+                self.w = w
+                self.h = h
+            function area():
+                return self.w * self.h
+            static function square(n):
+                return Rect(n, n)
+        s = Rect.square(4)
+        print(s.area())
+    "#);
+    assert_eq!(out, "16\n");
+}
+
+#[test]
+fn str_dunder_controls_printing() {
+    let out = output(r#"
+        class Rect:
+            w = 0
+            h = 0
+            function __init__(w, h):
+                self.w = w
+                self.h = h
+            function __str__():
+                return "Rect(" + tostring(self.w) + "x" + tostring(self.h) + ")"
+        r = Rect(2, 3)
+        print(r)
+        print(tostring(r) + "!")
+    "#);
+    assert_eq!(out, "Rect(2x3)\nRect(2x3)!\n");
+}
+
+#[test]
+fn eq_dunder_compares_points() {
+    let out = output(r#"
+        class Point:
+            x = 0
+            y = 0
+            function __init__(x, y):
+                self.x = x
+                self.y = y
+            function __eq__(other):
+                return self.x == other.x and self.y == other.y
+        p = Point(1, 2)
+        print(p == Point(1, 2))
+        print(p != Point(2, 1))
+        class Plain:
+            v = 1
+        q = Plain()
+        print(q == q)
+        print(q == Plain())
+    "#);
+    assert_eq!(out, "true\ntrue\ntrue\nfalse\n");
+}
+
+#[test]
+fn custom_iter_in_for_loop() {
+    let out = output(r#"
+        class Countdown:
+            n = 0
+            function __init__(n):
+                self.n = n
+            function __iter__():
+                return self
+            function __next__():
+                if self.n == 0:
+                    return null
+                self.n = self.n - 1
+                return self.n + 1
+        for x in Countdown(3):
+            print(x)
+        class Bag:
+            items = array()
+            function __iter__():
+                return self.items
+        b = Bag()
+        push(b.items, "a")
+        push(b.items, "b")
+        for x in b:
+            print(x)
+    "#);
+    assert_eq!(out, "3\n2\n1\na\nb\n");
+}
+
+#[test]
+fn getitem_and_setitem_dunders() {
+    let out = output(r#"
+        class Doubler:
+            cells = map()
+            function __getitem__(key):
+                return self.cells[key]
+            function __setitem__(key, value):
+                self.cells[key] = value * 2
+        d = Doubler()
+        d["a"] = 5
+        print(d["a"])
+        d["a"] = d["a"] + 1
+        print(d["a"])
+    "#);
+    assert_eq!(out, "10\n22\n");
+}
+
+#[test]
+fn field_defaults_are_copied_per_instance() {
+    let out = output(r#"
+        class Basket:
+            items = array()
+            tags = map()
+        b1 = Basket()
+        b2 = Basket()
+        push(b1.items, 1)
+        b1.tags["x"] = 1
+        print(length(b1.items))
+        print(length(b2.items))
+        print(map_keys(b2.tags))
+    "#);
+    assert_eq!(out, "1\n0\n[]\n");
+}
+
+#[test]
+fn fields_and_has_method() {
+    let out = output(r#"
+        class Animal:
+            name = ""
+            legs = 4
+            function speak():
+                return "..."
+        a = Animal()
+        print(fields(a))
+        print(fields(Animal))
+        print(has_method(a, "speak"))
+        print(has_method(Animal, "fetch"))
+    "#);
+    assert_eq!(out, "[legs, name]\n[legs, name]\ntrue\nfalse\n");
+}
+
+#[test]
+fn seal_freezes_static_fields_only() {
+    let out = output(r#"
+        class Config:
+            level = 1
+        seal(Config)
+        c = Config()
+        c.level = 5
+        print(c.level)
+        print(is_sealed(Config))
+        class Open:
+            level = 1
+        Open.level = 2
+        print(Open.level)
+        print(is_sealed(Open))
+    "#);
+    assert_eq!(out, "5\ntrue\n2\nfalse\n");
+
+    let err = error(r#"
+        class C:
+            x = 1
+        seal(C)
+        C.x = 2
+    "#);
+    assert!(err.contains("Cannot set 'x' on sealed class C"), "{}", err);
+}
+
+#[test]
+fn string_plus_instance_uses_str() {
+    let out = output(r#"
+        class V:
+            n = 1
+            function __str__():
+                return "V" + tostring(self.n)
+        class W:
+            n = 2
+        print("v = " + V())
+        print("w = " + W())
+    "#);
+    assert_eq!(out, "v = V1\nw = <instance of W>\n");
+    let err = error(r#"
+        class V:
+            n = 1
+        x = 2 + V()
+    "#);
+    assert!(err.contains("Invalid operands for +"), "{}", err);
+}
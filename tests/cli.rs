@@ -0,0 +1,144 @@
+//! Runs the `forge_interpreter` binary for behaviour that needs a real
+//! process: stdin, exit codes, command line options.
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+fn forge() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_forge_interpreter"))
+}
+
+/// Write `source` to a fresh script file in the target temp directory.
+fn script(name: &str, source: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let path = dir.join(format!("{}.forge", name));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+/// Run `path` with `stdin` piped in and wait for it to finish.
+fn run_with_stdin(path: &PathBuf, stdin: &str) -> Output {
+    let mut child = forge()
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stdout).into_owned()
+}
+
+#[test]
+fn input_reads_piped_lines() {
+    let path = script("input_piped", "a = input(\"first: \")\nb = input(\"second: \")\nprint(a + \"|\" + b)\n");
+    let out = run_with_stdin(&path, "hello\nworld\n");
+    assert!(out.status.success());
+    assert_eq!(stdout(&out), "first: second: hello|world\n");
+}
+
+#[test]
+fn input_returns_default_on_empty_line() {
+    let path = script("input_default", "name = input(\"name: \", \"anon\")\nprint(name)\n");
+    let out = run_with_stdin(&path, "\n");
+    assert_eq!(stdout(&out), "name: anon\n");
+}
+
+#[test]
+fn input_timeout_gives_up_and_the_process_exits() {
+    let path = script("input_timeout", "x = input_timeout(\"> \", 50)\nprint(x == null)\n");
+    // stdin stays open and silent: the pending read must not keep the process alive
+    let mut child = forge()
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if start.elapsed() > Duration::from_secs(10) {
+            child.kill().unwrap();
+            panic!("interpreter did not exit after input_timeout");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    assert!(status.success());
+    let mut text = String::new();
+    std::io::Read::read_to_string(&mut child.stdout.take().unwrap(), &mut text).unwrap();
+    assert_eq!(text, "> true\n");
+}
+
+#[test]
+fn max_steps_stops_an_infinite_loop() {
+    let path = script("max_steps", "n = 0\nwhile true:\n    n = n + 1\n");
+    let out = forge().arg("--max-steps").arg("1000").arg(&path).output().unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("execution step limit exceeded"), "{}", stderr);
+}
+
+#[test]
+fn trace_logs_each_statement_to_stderr() {
+    let path = script("trace", "x = 1\ny = x + 1\nprint(y)\n");
+    let out = forge().arg("--trace").arg(&path).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(stdout(&out), "2\n");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert_eq!(stderr, "line 1: Assign x\nline 2: Assign y\nline 3: Print\n");
+}
+
+#[test]
+fn script_from_stdin_with_dash() {
+    let mut child = forge()
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"x = 40\nprint(x + 2)\n").unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(stdout(&out), "42\n");
+}
+
+#[test]
+fn shebang_line_is_skipped() {
+    let path = script("shebang", "#!/usr/bin/env forge_interpreter\nprint(\"hi\")\n");
+    let out = forge().arg("--trace").arg(&path).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(stdout(&out), "hi\n");
+    // line numbers still count the shebang line
+    assert_eq!(String::from_utf8_lossy(&out.stderr), "line 2: Print\n");
+}
+
+#[test]
+fn version_and_help_exit_successfully() {
+    let out = forge().arg("--version").output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(stdout(&out), format!("forge {}\n", env!("CARGO_PKG_VERSION")));
+    let out = forge().arg("--help").output().unwrap();
+    assert!(out.status.success());
+    assert!(stdout(&out).contains("--max-steps N"));
+}
+
+#[test]
+fn input_password_reads_piped_line_without_a_terminal() {
+    let path = script("input_password", "p = input_password(\"pw: \")\nprint(length(p))\n");
+    let out = run_with_stdin(&path, "s3cret\n");
+    assert!(out.status.success());
+    assert_eq!(stdout(&out), "pw: 6\n");
+}
+
+#[test]
+fn printed_lines_and_prompts_stay_in_source_order() {
+    let path = script("print_prompt_order", "print(\"welcome\")\nwrite_out(\"step 1 \")\nname = input(\"name: \")\nprint(\"hi \" + name)\nage = input(\"age: \")\nprint(age)\n");
+    let out = run_with_stdin(&path, "ann\n41\n");
+    assert_eq!(stdout(&out), "welcome\nstep 1 name: hi ann\nage: 41\n");
+}
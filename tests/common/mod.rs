@@ -0,0 +1,60 @@
+//! Helpers shared by the integration tests: run a Forge script through the
+//! public API and capture what it prints.
+#![allow(dead_code)] // every test crate uses a different subset
+
+use forge_interpreter::Interpreter;
+use std::cell::RefCell;
+use std::future::Future;
+use std::rc::Rc;
+
+/// Drive `fut` on a fresh current-thread runtime, like `main.rs` does.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to start runtime")
+        .block_on(fut)
+}
+
+/// Strip the indentation shared by all non-blank lines, so scripts can be
+/// written indented inside the test function.
+pub fn dedent(source: &str) -> String {
+    let indent = source
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    source
+        .lines()
+        .map(|l| if l.len() >= indent { &l[indent..] } else { l.trim_start() })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Run `source` in `interp` and return everything it printed, or its error.
+pub fn run_with(interp: &mut Interpreter, source: &str) -> Result<String, String> {
+    let out = Rc::new(RefCell::new(Vec::<u8>::new()));
+    interp.set_output(out.clone());
+    block_on(interp.run(&dedent(source)))?;
+    let text = String::from_utf8(out.borrow().clone()).expect("output is not utf8");
+    Ok(text)
+}
+
+/// Run `source` in a fresh interpreter; see [`run_with`].
+pub fn run(source: &str) -> Result<String, String> {
+    run_with(&mut Interpreter::new(), source)
+}
+
+/// Output of a script that must succeed.
+pub fn output(source: &str) -> String {
+    run(source).unwrap_or_else(|e| panic!("script failed: {}", e))
+}
+
+/// Error of a script that must fail.
+pub fn error(source: &str) -> String {
+    match run(source) {
+        Ok(out) => panic!("script succeeded, printed {:?}", out),
+        Err(e) => e,
+    }
+}
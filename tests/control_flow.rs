@@ -0,0 +1,88 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn variable_first_assigned_in_if_does_not_leak() {
+    let err = error(r#"
+        if true:
+            temp = 1
+        print(temp)
+    "#);
+    assert!(err.contains("Variable 'temp' not defined"), "{}", err);
+}
+
+#[test]
+fn outer_variable_mutated_in_loop_persists() {
+    let out = output(r#"
+        total = 0
+        n = 0
+        while n < 4:
+            n = n + 1
+            if n % 2 == 0:
+                total = total + n
+        print(total)
+        print(n)
+    "#);
+    assert_eq!(out, "6\n4\n");
+}
+
+#[test]
+fn for_loop_ascending_descending_and_custom_step() {
+    let out = output(r#"
+        up = array()
+        for i = 1, 3 do
+            push(up, i)
+        down = array()
+        for i = 3, 1, -1 do
+            push(down, i)
+        odd = array()
+        for i = 1, 8, 3 do
+            push(odd, i)
+        print(up)
+        print(down)
+        print(odd)
+        print(i)
+    "#);
+    assert_eq!(out, "[1, 2, 3]\n[3, 2, 1]\n[1, 4, 7]\n7\n");
+}
+
+#[test]
+fn for_loop_bounds_are_evaluated_once() {
+    let out = output(r#"
+        n = 3
+        count = 0
+        for i = 1, n do
+            n = n + 1
+            count = count + 1
+        print(count)
+    "#);
+    assert_eq!(out, "3\n");
+}
+
+#[test]
+fn for_loop_zero_step_is_an_error() {
+    let err = error("for i = 1, 3, 0 do\n    print(i)");
+    assert!(err.contains("for loop step must not be zero"), "{}", err);
+}
+
+#[test]
+fn labeled_break_and_continue_leave_the_outer_loop() {
+    let out = output(r#"
+        found = 0
+        outer: for i = 1, 5 do
+            for j = 1, 5 do
+                if i * j == 6:
+                    found = i * 10 + j
+                    break outer
+        print(found)
+        count = 0
+        rows: for i = 1, 3 do
+            for j = 1, 3 do
+                if j == 2:
+                    continue rows
+                count = count + 1
+        print(count)
+    "#);
+    assert_eq!(out, "23\n3\n");
+}
@@ -0,0 +1,59 @@
+//! DLL calls against the C library, which every Linux test machine has.
+#![cfg(target_os = "linux")]
+
+mod common;
+
+#[cfg(not(feature = "libffi"))]
+#[test]
+fn too_many_dll_arguments_names_the_arity() {
+    let err = common::error(r#"
+        libc = dll_load("libc.so.6")
+        dll_call(libc, "abs", 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13)
+    "#);
+    assert!(err.contains("13 arguments given, at most 12"), "{}", err);
+}
+
+#[cfg(feature = "libffi")]
+#[test]
+fn fifteen_argument_call_through_libffi() {
+    let out = common::output(r#"
+        libc = dll_load("libc.so.6")
+        buf = malloc(64)
+        n = dll_call(libc, "snprintf", heap_ptr(buf), 64, "%d%d%d%d%d%d%d%d%d%d%d%d", 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2)
+        bytes = array()
+        for i = 0, n - 1 do
+            push(bytes, peek(buf, i))
+        print(decode(bytes, "ascii"))
+    "#);
+    assert_eq!(out, "123456789012\n");
+}
+
+#[test]
+fn dll_writes_into_a_heap_out_buffer() {
+    let out = common::output(r#"
+        libc = dll_load("libc.so.6")
+        buf = malloc(16)
+        dll_call(libc, "strcpy", heap_ptr(buf), "forge")
+        dll_call(libc, "memset", heap_ptr(buf, 8), 7, 4)
+        bytes = array()
+        for i = 0, 4 do
+            push(bytes, peek(buf, i))
+        print(decode(bytes, "ascii"))
+        print(peek(buf, 5))
+        print(peek32(buf, 8))
+        free(buf)
+    "#);
+    assert_eq!(out, "forge\n0\n117901063\n");
+}
+
+#[cfg(feature = "libffi")]
+#[test]
+fn typed_calls_accept_the_sizeof_names() {
+    let out = common::output(r#"
+        libc = dll_load("libc.so.6")
+        print(dll_call_typed(libc, "strlen", "size_t(char*)", "forge"))
+        print(dll_call_typed(libc, "abs", "int32(int32)", -7))
+        print(dll_call_typed(libc, "toupper", "int(uint8)", 97))
+    "#);
+    assert_eq!(out, "5\n7\n65\n");
+}
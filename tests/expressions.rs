@@ -0,0 +1,37 @@
+mod common;
+
+use common::{output, run_with};
+use forge_interpreter::Interpreter;
+
+/// Steps a script takes under a generous step limit.
+fn steps(source: &str) -> u64 {
+    let mut interp = Interpreter::new();
+    interp.env_mut().set_max_steps(Some(1_000_000));
+    run_with(&mut interp, source).unwrap();
+    interp.env().steps_taken()
+}
+
+#[test]
+fn async_calls_inside_expressions() {
+    let out = output(r#"
+        function later(x):
+            sleep(1)
+            return x
+        a = 1 + later(2) * 3
+        b = sum(array(1, 2)) + later(10)
+        c = length(array(1, 2, 3)) > 2 and later(true)
+        print(a)
+        print(b)
+        print(c)
+    "#);
+    assert_eq!(out, "7\n13\ntrue\n");
+}
+
+#[test]
+fn operator_dunder_is_charged_steps_once() {
+    // `p + q` first tries the synchronous path, finds an instance and falls
+    // back; it must cost the same as the equivalent explicit call.
+    let prelude = "class V:\n    function __add__(o):\n        return 1\nfunction add(a, b):\n    return 1\np = V()\nq = V()\n";
+    assert_eq!(steps(&format!("{}z = p + q\n", prelude)), steps(&format!("{}z = add(p, q)\n", prelude)));
+    assert_eq!(steps(&format!("{}z = p + q + 1\n", prelude)), steps(&format!("{}z = add(p, q) + 1\n", prelude)));
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::output;
+use std::path::PathBuf;
+
+/// A fresh path in the target temp directory, quoted as a Forge string literal.
+fn temp_path(name: &str) -> (PathBuf, String) {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_file(&path);
+    let literal = format!("{:?}", path.to_str().unwrap());
+    (path, literal)
+}
+
+#[test]
+fn load_env_file_and_read_values() {
+    let (path, literal) = temp_path("two_keys.forge.env");
+    std::fs::write(&path, "# settings\nFORGE_TEST_HOST=localhost\nFORGE_TEST_PORT=\"8080\"\n").unwrap();
+    let out = output(&format!(
+        "print(load_env({}))\nprint(env_get(\"FORGE_TEST_HOST\"))\nprint(env_get(\"FORGE_TEST_PORT\"))\nprint(env_get(\"FORGE_TEST_MISSING\", \"none\"))",
+        literal
+    ));
+    assert_eq!(out, "2\nlocalhost\n8080\nnone\n");
+}
+
+#[test]
+fn fprintf_appends_formatted_text() {
+    let (path, literal) = temp_path("fprintf.txt");
+    let out = output(&format!(
+        "fprintf({p}, \"{{}} + {{}} = \", 2, 3)\nfprintln({p}, \"{{}} {{{{ok}}}}\", 5)\nprint(read({p}))",
+        p = literal
+    ));
+    assert_eq!(out, "2 + 3 = 5 {ok}\n\n");
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "2 + 3 = 5 {ok}\n");
+}
+
+#[test]
+fn path_helpers() {
+    let out = output(r#"
+        print(path_join("a", "b", "c.txt"))
+        print(basename("/tmp/x/file.tar.gz"))
+        print(dirname("/tmp/x/file.tar.gz"))
+        print(extension("/tmp/x/file.tar.gz"))
+        print(extension("noext") == "")
+    "#);
+    let joined = std::path::Path::new("a").join("b").join("c.txt");
+    assert_eq!(out, format!("{}\nfile.tar.gz\n/tmp/x\ngz\ntrue\n", joined.display()));
+}
+
+#[test]
+fn wait_for_change_returns_after_the_file_is_modified() {
+    let (path, literal) = temp_path("watched.txt");
+    std::fs::write(&path, "old").unwrap();
+    // an old mtime, so the rewrite below is a change even on coarse clocks
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+    std::fs::File::options().write(true).open(&path).unwrap().set_modified(old).unwrap();
+    let out = output(&format!(
+        r#"
+        function touch():
+            sleep(30)
+            write({p}, "new")
+        t = spawn(touch)
+        before = file_mtime({p})
+        after = wait_for_change({p}, 5)
+        join_task(t)
+        print(after > before)
+        print(read({p}))
+        "#,
+        p = literal
+    ));
+    assert_eq!(out, "true\nnew\n");
+}
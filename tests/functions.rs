@@ -0,0 +1,133 @@
+mod common;
+
+use common::output;
+
+#[test]
+fn user_function_shadows_builtin_of_the_same_name() {
+    let out = output(r#"
+        function sum(x, y):
+            return x * 10 + y
+        print(sum(1, 2))
+        f = sum
+        print(f(3, 4))
+    "#);
+    assert_eq!(out, "12\n34\n");
+}
+
+#[test]
+fn builtin_still_works_without_a_user_function() {
+    assert_eq!(output("print(sum(array(1, 2, 3)))"), "6\n");
+}
+
+#[test]
+fn memoize_keys_keep_nested_types_apart() {
+    let out = output(r#"
+        function describe(a):
+            return type(a[0])
+        d = memoize(describe)
+        print(d(array(1)))
+        print(d(array("1")))
+        print(d(array(1.0)))
+        print(d(array(1)))
+    "#);
+    assert_eq!(out, "number\nstring\nnumber\nnumber\n");
+}
+
+#[test]
+fn memoize_returns_cached_result_for_equal_arguments() {
+    let out = output(r#"
+        calls = array()
+        function slow(m):
+            push(calls, 1)
+            return m["k"]
+        f = memoize(slow)
+        print(f(map("k", array(1, 2))))
+        print(f(map("k", array(1, 2))))
+        print(length(calls))
+    "#);
+    assert_eq!(out, "[1, 2]\n[1, 2]\n1\n");
+}
+
+#[test]
+fn benchmark_times_each_call() {
+    let out = output(r#"
+        calls = array()
+        function work():
+            push(calls, 1)
+            sleep(5)
+        stats = benchmark(work, 3)
+        print(length(calls))
+        print(stats["min_ms"] >= 4)
+        print(stats["total_ms"] >= 3 * stats["min_ms"])
+        print(stats["max_ms"] >= stats["avg_ms"])
+    "#);
+    assert_eq!(out, "3\ntrue\ntrue\ntrue\n");
+}
+
+#[test]
+fn generator_collects_every_yield() {
+    let out = output(r#"
+        function gen():
+            yield 1
+            yield 2
+            yield 3
+        total = 0
+        for x in gen():
+            total = total + x
+        print(gen())
+        print(total)
+    "#);
+    assert_eq!(out, "[1, 2, 3]\n6\n");
+}
+
+#[test]
+fn return_of_several_values_destructures() {
+    let out = output(r#"
+        function divmod(a, b):
+            return (a - a % b) / b, a % b
+        q, r = divmod(7, 2)
+        print(q)
+        print(r)
+        print(divmod(9, 4))
+    "#);
+    assert_eq!(out, "3\n1\n[2, 1]\n");
+}
+
+#[test]
+fn once_calls_the_function_a_single_time() {
+    let out = output(r#"
+        calls = array()
+        function init(x):
+            push(calls, x)
+            return x * 10
+        setup = once(init)
+        print(setup(1))
+        print(setup(2))
+        print(calls)
+    "#);
+    assert_eq!(out, "10\n10\n[1]\n");
+}
+
+#[test]
+fn retry_until_success_or_out_of_attempts() {
+    let out = output(r#"
+        tries = array()
+        function flaky():
+            push(tries, 1)
+            if length(tries) < 3:
+                x = 1 / 0
+            return "done"
+        print(retry(flaky, 5, 1))
+        print(length(tries))
+        calls = array()
+        function broken():
+            push(calls, 1)
+            x = 1 / 0
+        try:
+            retry(broken, 2, 1)
+        catch:
+            print("failed")
+        print(length(calls))
+    "#);
+    assert_eq!(out, "done\n3\nfailed\n2\n");
+}
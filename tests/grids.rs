@@ -0,0 +1,22 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn set_center_of_3x3_grid() {
+    let out = output(r#"
+        g = grid(3, 3, 0)
+        grid_set(g, 1, 1, 5)
+        print(g)
+        print(grid_get(g, 1, 1))
+    "#);
+    assert_eq!(out, "[[0, 0, 0], [0, 5, 0], [0, 0, 0]]\n5\n");
+}
+
+#[test]
+fn out_of_bounds_cells_are_errors() {
+    let err = error("g = grid(3, 3, 0)\ngrid_set(g, 1, 3, 1)");
+    assert!(err.contains("column 3 out of range"), "{}", err);
+    let err = error("g = grid(3, 3, 0)\ngrid_get(g, 3, 0)");
+    assert!(err.contains("grid_get"), "{}", err);
+}
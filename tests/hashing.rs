@@ -0,0 +1,31 @@
+mod common;
+
+use common::output;
+
+#[test]
+fn crc32_and_sha256_known_vectors() {
+    let out = output(r#"
+        print(crc32("123456789"))
+        print(sha256_hex("abc"))
+        print(sha256_hex(""))
+    "#);
+    assert_eq!(
+        out,
+        "3421780262\n\
+         ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n\
+         e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\n"
+    );
+}
+
+#[test]
+fn file_hash_equals_in_memory_hash() {
+    let path = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("hashed.txt");
+    // bigger than one read buffer, so the streaming path is exercised
+    let text = "forge hashing line\n".repeat(10_000);
+    std::fs::write(&path, &text).unwrap();
+    let out = output(&format!(
+        "text = read({p:?})\nprint(file_sha256({p:?}) == sha256_hex(text))\nprint(file_crc32({p:?}) == crc32(text))",
+        p = path.to_str().unwrap()
+    ));
+    assert_eq!(out, "true\ntrue\n");
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::output;
+
+#[test]
+fn ordered_map_keeps_insertion_order() {
+    let out = output(r#"
+        om = ordered_map("z", 1, "a", 2, "m", 3)
+        om["b"] = 4
+        keys = array()
+        for k in om:
+            push(keys, k)
+        print(keys)
+        print(map_keys(map("z", 1, "a", 2)))
+    "#);
+    assert_eq!(out, "[z, a, m, b]\n[a, z]\n");
+}
+
+#[test]
+fn entries_of_map_and_instance() {
+    let out = output(r#"
+        print(entries(map("b", 2, "a", 1)))
+        class Cat:
+            name = ""
+            legs = 4
+        c = Cat()
+        c.name = "cat"
+        print(entries(c))
+    "#);
+    assert_eq!(out, "[[a, 1], [b, 2]]\n[[legs, 4], [name, cat]]\n");
+}
+
+#[test]
+fn merge_and_deep_merge() {
+    let out = output(r#"
+        a = map("x", 1, "inner", map("p", 1))
+        b = map("y", 2, "inner", map("q", 2))
+        print(merge(a, b))
+        print(deep_merge(a, b))
+    "#);
+    assert_eq!(out, "{inner: {q: 2}, x: 1, y: 2}\n{inner: {p: 1, q: 2}, x: 1, y: 2}\n");
+}
+
+#[test]
+fn pick_and_omit_keys() {
+    let out = output(r#"
+        a = map("x", 1, "inner", map("p", 1))
+        print(pick(a, array("x")))
+        print(omit(a, array("x")))
+    "#);
+    assert_eq!(out, "{x: 1}\n{inner: {p: 1}}\n");
+}
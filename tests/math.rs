@@ -0,0 +1,87 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn modulo_by_zero_errors_for_ints_and_floats() {
+    assert!(error("x = 5\nprint(x % 0)").contains("Modulo by zero"));
+    assert!(error("x = 5.0\nprint(x % 0)").contains("Modulo by zero"));
+    assert!(error("x = 5\nprint(x % 0.0)").contains("Modulo by zero"));
+}
+
+#[test]
+fn is_nan_and_is_finite() {
+    let out = output(r#"
+        n = tonumber("NaN")
+        inf = tonumber("inf")
+        print(is_nan(n))
+        print(is_nan(1.5))
+        print(is_finite(1))
+        print(is_finite(inf))
+        print(is_finite(n))
+        print(n == n)
+    "#);
+    assert_eq!(out, "true\nfalse\ntrue\nfalse\nfalse\nfalse\n");
+}
+
+#[test]
+fn overflow_to_infinity_is_not_finite() {
+    let out = output(r#"
+        x = 1.0
+        for i = 1, 400 do
+            x = x * 10
+        print(x)
+        print(is_finite(x))
+        print(is_infinite(x))
+        print(is_nan(x - x))
+    "#);
+    assert_eq!(out, "inf\nfalse\ntrue\ntrue\n");
+}
+
+#[test]
+fn clamp_and_lerp() {
+    let out = output(r#"
+        print(clamp(15, 0, 10))
+        print(clamp(-3, 0, 10))
+        print(clamp(2.5, 0, 10))
+        print(lerp(0, 10, 0.25))
+        print(lerp(10, 20, 1))
+    "#);
+    assert_eq!(out, "10\n0\n2.5\n2.5\n20\n");
+}
+
+#[test]
+fn radians_and_atan2() {
+    let out = output(r#"
+        print(to_radians(180))
+        print(atan2(1, 1))
+        print(to_degrees(atan2(1, 1)))
+    "#);
+    assert_eq!(
+        out,
+        format!("{}\n{}\n45\n", std::f64::consts::PI, std::f64::consts::FRAC_PI_4)
+    );
+}
+
+#[test]
+fn gcd_lcm_and_is_prime() {
+    let out = output(r#"
+        print(gcd(12, 18))
+        print(lcm(4, 6))
+        print(is_prime(97))
+        print(is_prime(91))
+    "#);
+    assert_eq!(out, "6\n12\ntrue\nfalse\n");
+}
+
+#[test]
+fn min_max_propagate_nan_from_any_position() {
+    let out = output(r#"
+        nan = tonumber("NaN")
+        print(min(array(nan, 1, 2)))
+        print(min(array(1, nan, 2)))
+        print(max(array(1, nan, 2)))
+        print(max(1, 2, nan))
+    "#);
+    assert_eq!(out, "NaN\nNaN\nNaN\nNaN\n");
+}
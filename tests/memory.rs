@@ -0,0 +1,74 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn poke64_peek64_round_trip_is_bit_exact() {
+    let out = output(r#"
+        buf = malloc(16)
+        poke64(buf, 0, -1)
+        poke64(buf, 8, 9223372036854775807)
+        print(peek64(buf, 0))
+        print(peek64(buf, 8))
+        print(peek(buf, 0))
+        free(buf)
+    "#);
+    assert_eq!(out, "-1\n9223372036854775807\n255\n");
+}
+
+#[test]
+fn negative_sizes_and_addresses_are_errors() {
+    assert!(error("malloc(-1)").contains("must not be negative"));
+    assert!(error("sleep(-5)").contains("must not be negative"));
+    assert!(error("mem_read(-1)").contains("must not be negative"));
+    assert!(error("buf = malloc(4)\npeek(buf, -1)").contains("must not be negative"));
+    assert!(error("buf = malloc(8)\npoke64(buf, -8, 1)").contains("must not be negative"));
+}
+
+#[test]
+fn huge_malloc_is_an_error_not_an_abort() {
+    assert!(error("malloc(9223372036854775807)").contains("cannot allocate"));
+}
+
+#[test]
+fn mem_write_inside_a_loop_persists() {
+    let out = output(r#"
+        for i = 0, 4 do
+            mem_write(100 + i, i * 10)
+            if i == 2:
+                continue
+            set_reg("last", i)
+        function sum_mem():
+            s = 0
+            for i = 0, 4 do
+                s = s + mem_read(100 + i)
+            return s
+        print(sum_mem())
+        print(mem_read(102))
+        print(get_reg("last"))
+    "#);
+    assert_eq!(out, "100\n20\n4\n");
+}
+
+#[test]
+fn sizeof_pointer_and_mixed_struct_offsets() {
+    let out = output(r#"
+        print(sizeof("pointer"))
+        print(sizeof("int"))
+        print(struct_offset(array(1, 4, 8), 1))
+        print(struct_offset(array(1, 4, 8), 2))
+        print(struct_offset(array(1, 2, 1, 8), 3))
+    "#);
+    assert_eq!(out, format!("{}\n4\n4\n8\n8\n", std::mem::size_of::<usize>()));
+}
+
+#[test]
+fn sizeof_knows_the_dll_call_typed_names() {
+    let out = output(r#"
+        print(sizeof("char"), sizeof("short"), sizeof("int8"), sizeof("uint32"))
+        print(sizeof("size_t") == sizeof("ptr"), sizeof("char*") == sizeof("void*"))
+    "#);
+    assert_eq!(out, "1 2 1 4\ntrue true\n");
+    assert!(error(r#"sizeof("word")"#).contains("sizeof: Unknown C type 'word'"));
+    assert!(error(r#"sizeof("void")"#).contains("sizeof: void has no size"));
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn null_coalescing_evaluates_right_side_only_for_null() {
+    let out = output(r#"
+        calls = array()
+        function side(v):
+            push(calls, v)
+            return v
+        a = null
+        print(a ?? side("fallback"))
+        print(0 ?? side("unused"))
+        print(false ?? side("unused"))
+        print(length(calls))
+    "#);
+    assert_eq!(out, "fallback\n0\nfalse\n1\n");
+}
+
+#[test]
+fn optional_chaining_stops_at_null() {
+    let out = output(r#"
+        class Node:
+            next = null
+            value = 0
+        n = Node()
+        n.value = 3
+        print(n?.value)
+        print(n?.next?.value)
+        m = null
+        print(m?.anything)
+    "#);
+    assert_eq!(out, "3\nnull\nnull\n");
+}
+
+#[test]
+fn plain_attribute_access_on_null_is_an_error() {
+    let err = error("m = null\nprint(m.x)");
+    assert!(err.contains("Attribute 'x' not found"), "{}", err);
+}
+
+#[test]
+fn pipe_passes_left_value_as_first_argument() {
+    let out = output(r#"
+        function double(x):
+            return x * 2
+        function add(x, y):
+            return x + y
+        print(3 |> double |> add(1))
+        print(null ?? 2 |> double)
+    "#);
+    assert_eq!(out, "7\n4\n");
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::run_with;
+use forge_interpreter::ast::{Expr, Stmt};
+use forge_interpreter::optimize::fold_constants;
+use forge_interpreter::{try_parse, Interpreter};
+
+fn folded(source: &str) -> Expr {
+    let mut stmts = try_parse(source).unwrap();
+    fold_constants(&mut stmts);
+    // skip the `Line` markers the parser puts before statements
+    match stmts.into_iter().find(|s| !matches!(s, Stmt::Line(_))) {
+        Some(Stmt::Assign { value, .. }) => value,
+        other => panic!("expected an assignment, got {:?}", other),
+    }
+}
+
+#[test]
+fn folded_script_prints_the_same() {
+    let source = r#"
+        seconds = 2 * 3600 + 15
+        ratio = (1 + 2) / 4
+        label = "t" + "=" + tostring(seconds)
+        print(seconds)
+        print(ratio)
+        print(label)
+        print(-(3 - 5) * 2.5)
+    "#;
+    let mut plain = Interpreter::new();
+    plain.set_optimize(false);
+    let expected = run_with(&mut plain, source).unwrap();
+    assert_eq!(run_with(&mut Interpreter::new(), source).unwrap(), expected);
+    assert_eq!(expected, "7215\n0.75\nt=7215\n5\n");
+    assert!(matches!(folded("x = 2 * 3600"), Expr::Int(7200)));
+}
+
+#[test]
+fn division_by_zero_is_not_folded() {
+    assert!(matches!(folded("x = 1 / 0"), Expr::BinaryOp { .. }));
+    let err = run_with(&mut Interpreter::new(), "x = 1 / 0").unwrap_err();
+    assert!(err.contains("Division by zero"), "{}", err);
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::output;
+
+#[test]
+fn seeded_shuffle_is_reproducible() {
+    let out = output(r#"
+        seed(42)
+        a = shuffle(array(1, 2, 3, 4, 5))
+        seed(42)
+        b = shuffle(array(1, 2, 3, 4, 5))
+        print(deep_equal(a, b))
+        print(sum(a))
+        print(length(a))
+    "#);
+    assert_eq!(out, "true\n15\n5\n");
+}
+
+#[test]
+fn sample_picks_distinct_elements() {
+    let out = output(r#"
+        for round = 1, 20 do
+            s = sample(array(1, 2, 3, 4, 5), 3)
+            if length(unique(s)) != 3:
+                print("duplicate")
+        print(length(sample(array(1, 2, 3), 3)))
+    "#);
+    assert_eq!(out, "3\n");
+}
+
+#[test]
+fn seeded_weighted_choice_follows_the_weights() {
+    let out = output(r#"
+        seed(7)
+        counts = map("a", 0, "b", 0, "c", 0)
+        for i = 1, 3000 do
+            k = weighted_choice(array("a", "b", "c"), array(1, 2, 7))
+            counts[k] = counts[k] + 1
+        print(counts["a"] > 200 and counts["a"] < 400)
+        print(counts["b"] > 500 and counts["b"] < 700)
+        print(counts["c"] > 1950 and counts["c"] < 2250)
+        print(weighted_choice(array("x", "y"), array(0, 5)))
+    "#);
+    assert_eq!(out, "true\ntrue\ntrue\ny\n");
+}
+
+#[test]
+fn uuid_is_a_fresh_version_4_string() {
+    let out = output("a = uuid()\nb = uuid()\nprint(a == b)\nprint(a)");
+    let (distinct, id) = out.trim_end().split_once('\n').unwrap();
+    assert_eq!(distinct, "false");
+    let groups: Vec<&str> = id.split('-').collect();
+    assert_eq!(groups.iter().map(|g| g.len()).collect::<Vec<_>>(), [8, 4, 4, 4, 12]);
+    assert!(id.chars().all(|c| c == '-' || c.is_ascii_hexdigit()), "{}", id);
+    assert!(groups[2].starts_with('4'), "{}", id);
+    assert!(matches!(groups[3].chars().next(), Some('8' | '9' | 'a' | 'b')), "{}", id);
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn unbounded_recursion_stops_with_depth_error_on_default_stack() {
+    // Runs on a test thread with the default 2 MB stack, not the 512 MB
+    // interpreter thread of main.rs.
+    let err = error(r#"
+        function f(n):
+            return f(n + 1)
+        f(0)
+    "#);
+    assert!(err.contains("maximum recursion depth exceeded"), "{}", err);
+    let err = error(r#"
+        class Walker:
+            function down(n):
+                x = n * 2
+                if x >= 0:
+                    return self.down(n + 1) + 1
+                return 0
+        w = Walker()
+        print(w.down(0))
+    "#);
+    assert!(err.contains("maximum recursion depth exceeded"), "{}", err);
+}
+
+#[test]
+fn depth_error_is_catchable() {
+    let out = output(r#"
+        function f(n):
+            return f(n + 1)
+        try:
+            f(0)
+        catch:
+            print("caught")
+    "#);
+    assert_eq!(out, "caught\n");
+}
+
+#[test]
+fn unbounded_recursion_on_a_small_thread() {
+    let handle = std::thread::Builder::new()
+        .stack_size(512 * 1024)
+        .spawn(|| {
+            let mut interp = forge_interpreter::Interpreter::new();
+            interp.set_stack_size(512 * 1024);
+            common::run_with(&mut interp, "function f(n):\n    return f(n + 1)\nf(0)")
+        })
+        .unwrap();
+    let err = handle.join().expect("interpreter overflowed its stack").unwrap_err();
+    assert!(err.contains("maximum recursion depth exceeded"), "{}", err);
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn union_intersect_difference() {
+    let out = output(r#"
+        a = set_of(1, 2, 3)
+        b = set_of(array(2, 3, 4))
+        print(set_union(a, b))
+        print(set_intersect(a, b))
+        print(set_difference(a, b))
+        print(set_difference(a, a))
+    "#);
+    assert_eq!(out, "{1, 2, 3, 4}\n{2, 3}\n{1}\nset_of()\n");
+}
+
+#[test]
+fn set_on_a_non_array_is_an_error_not_a_new_set() {
+    assert!(error("m = map(\"a\", 1)\nset(m, 0, 1)").contains("set: first argument must be array"));
+    assert!(error("set(1, 2)").contains("set expects 3 arguments"));
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn title_case_capitalize_swap_case() {
+    let out = output(r#"
+        print(title_case("hello world"))
+        print(capitalize("привет мир"))
+        print(swap_case("Hello Мир"))
+    "#);
+    assert_eq!(out, "Hello World\nПривет мир\nhELLO мИР\n");
+}
+
+#[test]
+fn count_occurrences_counts_non_overlapping_matches() {
+    assert_eq!(output(r#"print(count_occurrences("aaaa", "aa"))"#), "2\n");
+    assert_eq!(output(r#"print(count_occurrences("abc", "z"))"#), "0\n");
+}
+
+#[test]
+fn regex_split_on_whitespace() {
+    let out = output(r#"
+        parts = regex_split("\\s+", "a  b\tc")
+        print(length(parts))
+        print(parts)
+    "#);
+    assert_eq!(out, "3\n[a, b, c]\n");
+}
+
+#[test]
+fn regex_captures_date_groups() {
+    let out = output(r#"
+        print(regex_captures("(\\d+)-(\\d+)-(\\d+)", "on 2024-03-15"))
+        print(length(regex_captures("(\\d+)", "none")))
+    "#);
+    assert_eq!(out, "[2024-03-15, 2024, 03, 15]\n0\n");
+}
+
+#[test]
+fn character_class_checks() {
+    let out = output(r#"
+        print(is_digit("7"))
+        print(is_digit("a"))
+        print(is_alpha("Ж"))
+        print(is_alpha("1"))
+        print(is_whitespace("\t"))
+        print(is_whitespace("x"))
+    "#);
+    assert_eq!(out, "true\nfalse\ntrue\nfalse\ntrue\nfalse\n");
+    let err = error(r#"is_digit("12")"#);
+    assert!(err.contains("expected a single character"), "{}", err);
+}
+
+#[test]
+fn split_with_a_limit() {
+    let out = output(r#"
+        print(split("a=b=c", "=", 2))
+        print(split("a=b=c", "="))
+    "#);
+    assert_eq!(out, "[a, b=c]\n[a, b, c]\n");
+}
+
+#[test]
+fn glob_match_wildcards() {
+    let out = output(r#"
+        print(glob_match("*.rs", "main.rs"))
+        print(glob_match("ma?n.*", "main.rs"))
+        print(glob_match("*.rs", "main.rc"))
+    "#);
+    assert_eq!(out, "true\ntrue\nfalse\n");
+}
+
+#[test]
+fn ascii_and_latin1_round_trip() {
+    let out = output(r#"
+        b = encode("Hi!", "ascii")
+        print(b)
+        print(decode(b, "ascii"))
+        l = encode("café", "latin1")
+        print(l)
+        print(decode(l, "latin1"))
+    "#);
+    assert_eq!(out, "[72, 105, 33]\nHi!\n[99, 97, 102, 233]\ncafé\n");
+    let err = error(r#"encode("Ж", "latin1")"#);
+    assert!(err.contains("cannot be encoded as latin1"), "{}", err);
+}
+
+#[test]
+fn progress_bar_is_written_without_newline() {
+    let out = output("progress(50, 100)\nprogress(150, 100)");
+    let half = format!("\r[{}{}]  50%", "#".repeat(15), ".".repeat(15));
+    let full = format!("\r[{}] 100%", "#".repeat(30));
+    assert_eq!(out, half + &full);
+}
+
+#[test]
+fn min_max_compare_strings() {
+    let out = output(r#"
+        print(min(array("pear", "apple", "fig")))
+        print(max(array("pear", "apple", "fig")))
+    "#);
+    assert_eq!(out, "apple\npear\n");
+    let err = error(r#"max(array(1, "a"))"#);
+    assert!(err.contains("cannot compare numbers and strings"), "{}", err);
+}
+
+#[test]
+fn duration_format_round_trips() {
+    let out = output(r#"
+        print(format_duration(3723))
+        print(parse_duration(format_duration(3723)))
+    "#);
+    assert_eq!(out, "1h 2m 3s\n3723\n");
+}
+
+#[test]
+fn xor_cipher_round_trip() {
+    let out = output(r#"
+        secret = xor_cipher("Hello, world!", "key")
+        print(secret[0])
+        print(decode(xor_cipher(secret, "key"), "utf8"))
+    "#);
+    assert_eq!(out, "35\nHello, world!\n");
+}
+
+#[test]
+fn rot13_moves_letters_only() {
+    let out = output(r#"
+        print(rot("Hello, World! 123", 13))
+        print(rot(rot("Hello, World! 123", 13), 13))
+    "#);
+    assert_eq!(out, "Uryyb, Jbeyq! 123\nHello, World! 123\n");
+}
+
+#[test]
+fn string_index_counts_characters() {
+    let out = output(r#"
+        s = "héllo"
+        print(s[1])
+        print(s[4])
+    "#);
+    assert_eq!(out, "é\no\n");
+    let err = error(r#"x = "héllo"[5]"#);
+    assert!(err.contains("String index out of bounds"), "{}", err);
+}
+
+#[test]
+fn format_duration_carries_rounding_into_larger_units() {
+    let out = output(r#"
+        print(format_duration(59.9996))
+        print(format_duration(3599.9996))
+        print(format_duration(0.9999996))
+        print(format_duration(61.5))
+    "#);
+    assert_eq!(out, "1m\n1h\n1s\n1m 1.5s\n");
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{error, output};
+
+#[test]
+fn spawned_tasks_share_an_array() {
+    let out = output(r#"
+        log = array()
+        function worker(name, n):
+            for i = 1, n do
+                push(log, name)
+                sleep(1)
+            return n
+        a = spawn(worker, "a", 3)
+        b = spawn(worker, "b", 2)
+        print(join_task(a) + join_task(b))
+        print(length(log))
+        print(count_occurrences(join(log, ""), "a"))
+    "#);
+    assert_eq!(out, "5\n5\n3\n");
+}
+
+#[test]
+fn yield_now_lets_a_spawned_task_run() {
+    let out = output(r#"
+        log = array()
+        function worker():
+            push(log, "task")
+            return 1
+        t = spawn(worker)
+        for i = 1, 3 do
+            if i == 2:
+                yield_now()
+            push(log, i)
+        print(log)
+        print(join_task(t))
+    "#);
+    assert_eq!(out, "[1, task, 2, 3]\n1\n");
+}
+
+#[test]
+fn rate_wait_spaces_out_calls() {
+    let start = Instant::now();
+    let out = output(r#"
+        r = rate_limiter(20)
+        rate_wait(r)
+        rate_wait(r)
+        rate_wait(r)
+        print("ok")
+    "#);
+    assert_eq!(out, "ok\n");
+    assert!(start.elapsed() >= Duration::from_millis(38), "{:?}", start.elapsed());
+}
+
+#[test]
+fn rate_limiters_are_plain_maps() {
+    let out = output(r#"
+        r = rate_limiter(5)
+        print(r["last_ms"])
+        rate_wait(r)
+        print(type(r["last_ms"]))
+    "#);
+    assert_eq!(out, "null\nnumber\n");
+    assert!(error("rate_wait(1)").contains("rate_wait: argument must be a rate limiter"));
+}
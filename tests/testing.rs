@@ -0,0 +1,100 @@
+mod common;
+
+use common::{error, output, run_with};
+use forge_interpreter::Interpreter;
+
+#[test]
+fn assert_throws_passes_when_the_function_fails() {
+    let out = output(r#"
+        function bad():
+            x = 1 / 0
+        assert_throws(bad)
+        assert_throws(bad, "Division by zero")
+        print("ok")
+    "#);
+    assert_eq!(out, "ok\n");
+}
+
+#[test]
+fn assert_throws_fails_on_success_or_other_error() {
+    let err = error(r#"
+        function good():
+            return 1
+        assert_throws(good)
+    "#);
+    assert!(err.contains("function returned normally"), "{}", err);
+    let err = error(r#"
+        function bad():
+            x = 1 / 0
+        assert_throws(bad, "index")
+    "#);
+    assert!(err.contains("does not contain 'index'"), "{}", err);
+}
+
+#[test]
+fn assert_throws_rejects_a_non_function() {
+    let err = error("assert_throws(42)");
+    assert!(err.contains("assert_throws: expected a function, got number"), "{}", err);
+}
+
+#[test]
+fn expect_type_returns_value_or_fails() {
+    let out = output(r#"
+        print(expect_type(5, "number"))
+        print(expect_type(null, array("number", "null")))
+    "#);
+    assert_eq!(out, "5\nnull\n");
+    let err = error(r#"expect_type("a", array("number", "null"))"#);
+    assert!(err.contains("expected number or null, got string"), "{}", err);
+    let err = error(r#"expect_type(1, "numbr")"#);
+    assert!(err.contains("unknown type name 'numbr'"), "{}", err);
+}
+
+#[test]
+fn debug_assert_runs_only_when_enabled() {
+    let script = r#"
+        calls = array()
+        function check():
+            push(calls, 1)
+            return true
+        debug_assert(check)
+        debug_assert(1 < 2, "fine")
+        print(length(calls))
+    "#;
+    assert_eq!(output(script), "1\n");
+    let err = error(r#"debug_assert(false, "heap corrupted")"#);
+    assert!(err.contains("debug_assert failed: heap corrupted"), "{}", err);
+
+    let mut interp = Interpreter::new();
+    interp.set_debug_asserts(false);
+    assert_eq!(run_with(&mut interp, script).unwrap(), "0\n");
+    assert_eq!(run_with(&mut interp, r#"debug_assert(false, "skipped")"#).unwrap(), "");
+}
+
+#[test]
+fn assert_approx_allows_a_tolerance() {
+    let out = output(r#"
+        assert_approx(0.1 + 0.2, 0.3)
+        assert_approx(1.0, 1.05, 0.1)
+        print("ok")
+    "#);
+    assert_eq!(out, "ok\n");
+    let err = error("assert_approx(1.0, 1.5, 0.1)");
+    assert!(err.contains("is not within 0.1 of 1.5"), "{}", err);
+}
+
+#[test]
+fn built_executable_skips_debug_asserts() {
+    // the interpreter that `main` generated by builder runs the script with
+    let mut interp = Interpreter::for_built_executable();
+    let out = run_with(&mut interp, r#"
+        calls = array()
+        function check():
+            push(calls, 1)
+            return false
+        debug_assert(check, "not called")
+        debug_assert(false, "skipped")
+        print(length(calls))
+    "#);
+    assert_eq!(out.unwrap(), "0\n");
+}
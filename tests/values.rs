@@ -0,0 +1,44 @@
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn deep_equal_compares_structure() {
+    let out = output(r#"
+        a = array(1, array(2, map("k", 3)))
+        b = array(1, array(2, map("k", 3)))
+        print(deep_equal(a, b))
+        print(a == b)
+        print(deep_equal(map("a", 1, "b", array(1)), map("a", 1, "b", array(2))))
+        print(deep_equal(map("a", 1), map("a", 1, "b", 2)))
+    "#);
+    assert_eq!(out, "true\nfalse\nfalse\nfalse\n");
+}
+
+#[test]
+fn dump_prints_nested_values_on_several_lines() {
+    let out = output(r#"dump(map("name", "forge", "tags", array(1, array(2, 3)), "empty", array()))"#);
+    assert_eq!(
+        out,
+        "{\n  \"empty\": [],\n  \"name\": \"forge\",\n  \"tags\": [\n    1,\n    [\n      2,\n      3\n    ]\n  ]\n}\n"
+    );
+}
+
+#[test]
+fn is_empty_for_strings_arrays_and_maps() {
+    let out = output(r#"
+        print(is_empty(""))
+        print(is_empty("a"))
+        print(is_empty(array()))
+        print(is_empty(array(1)))
+        print(is_empty(map()))
+        print(is_empty(map("a", 1)))
+    "#);
+    assert_eq!(out, "true\nfalse\ntrue\nfalse\ntrue\nfalse\n");
+}
+
+#[test]
+fn is_empty_rejects_numbers() {
+    let err = error("is_empty(5)");
+    assert!(err.contains("is_empty: argument must be array, string, map or set, got number"), "{}", err);
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::run_with;
+use forge_interpreter::ast::Stmt;
+use forge_interpreter::{try_parse, vm, Interpreter};
+
+/// Number of top-level loops in `source` that the VM compiles.
+fn compiled_loops(source: &str) -> usize {
+    let mut stmts = try_parse(&common::dedent(source)).expect("parse failed");
+    vm::compile_program(&mut stmts);
+    stmts.iter().filter(|s| matches!(s, Stmt::Compiled { .. })).count()
+}
+
+/// Output of `source` on the VM, checked against the tree-walking evaluator.
+fn same_output(source: &str) -> String {
+    let expected = run_with(&mut Interpreter::new(), source).unwrap_or_else(|e| panic!("script failed: {}", e));
+    let mut interp = Interpreter::new();
+    interp.set_vm(true);
+    let actual = run_with(&mut interp, source).unwrap_or_else(|e| panic!("script failed on the VM: {}", e));
+    assert_eq!(actual, expected);
+    actual
+}
+
+#[test]
+fn arithmetic_loops_match_the_evaluator() {
+    let script = r#"
+        total = 0
+        for i = 1, 100 do
+            total = total + i * 2 - 1
+        n = 10
+        fact = 1
+        while n > 1:
+            fact = fact * n
+            n = n - 1
+        print(total, fact, total / 3)
+    "#;
+    assert_eq!(compiled_loops(script), 2);
+    assert_eq!(same_output(script), "10000 3628800 3333.3333333333335\n");
+}
+
+#[test]
+fn nested_if_and_while_match_the_evaluator() {
+    let script = r#"
+        evens = 0
+        odds = 0
+        i = 0
+        while i < 5:
+            j = 0
+            while j < 4:
+                if (i + j) % 2 == 0:
+                    evens = evens + 1
+                elif j == 3:
+                    odds = odds + 10
+                else:
+                    odds = odds + 1
+                j = j + 1
+            i = i + 1
+        print(evens, odds)
+    "#;
+    assert_eq!(compiled_loops(script), 1);
+    assert_eq!(same_output(script), "10 37\n");
+}
+
+#[test]
+fn stepped_and_descending_ranges_match_the_evaluator() {
+    let script = r#"
+        up = 0
+        for i = 1, 10, 3 do
+            up = up * 10 + i
+        down = 0
+        for i = 5, 1, -2 do
+            down = down * 10 + i
+        print(up, down, i)
+    "#;
+    assert_eq!(compiled_loops(script), 2);
+    assert_eq!(same_output(script), "1480 531 1\n");
+}
+
+#[test]
+fn block_scope_matches_the_evaluator() {
+    let script = r#"
+        total = 0
+        for i = 1, 3 do
+            step = i * 2
+            total = total + step
+        print(total, i)
+        print(step)
+    "#;
+    assert_eq!(compiled_loops(script), 1);
+    let expected = run_with(&mut Interpreter::new(), script).unwrap_err();
+    let mut interp = Interpreter::new();
+    interp.set_vm(true);
+    assert_eq!(run_with(&mut interp, script).unwrap_err(), expected);
+    assert!(expected.contains("step"), "{}", expected);
+}
+
+#[test]
+fn printing_an_instance_calls_str_on_the_vm() {
+    let script = r#"
+        class Point:
+            x = 0
+            function __str__():
+                return "P(" + tostring(self.x) + ")"
+        p = Point()
+        for i = 1, 2 do
+            print(p, i)
+    "#;
+    assert_eq!(compiled_loops(script), 1);
+    assert_eq!(same_output(script), "P(0) 1\nP(0) 2\n");
+}
+
+#[test]
+fn instance_operand_calls_add_on_the_vm() {
+    let script = r#"
+        class Counter:
+            n = 0
+            function __add__(k):
+                c = Counter()
+                c.n = self.n + k
+                return c
+            function __str__():
+                return "Counter(" + tostring(self.n) + ")"
+        c = Counter()
+        for i = 1, 4 do
+            c = c + i
+        print(c)
+    "#;
+    assert_eq!(compiled_loops(script), 1);
+    assert_eq!(same_output(script), "Counter(10)\n");
+}